@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// Automatic archiving of closed tasks when a cluster is opened
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// Archive tasks closed more than this many days ago (null disables)
+    #[serde(default)]
+    pub auto_archive_days: Option<u32>,
+}
+
+impl ArchiveConfig {
+    pub const FILE: &'static str = "archive.json";
+
+    /// Read `archive.json`; nothing is archived automatically if it can't be parsed
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_default()
+    }
+
+    /// Read `archive.json`, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        paths::load_config(Self::FILE)
+    }
+
+    /// Timestamp before which closed tasks are archived automatically
+    pub fn cutoff(&self, now: i64) -> Option<i64> {
+        self.auto_archive_days.map(|days| now - days as i64 * 86400)
+    }
+}
@@ -2,8 +2,11 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::paths;
+
 /// Color configuration for the application
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColorConfig {
     // Main backgrounds
     pub main_bg: String,
@@ -57,6 +60,14 @@ pub struct ColorConfig {
     pub abandoned_marker: String,
     #[serde(default = "default_abandoned_text")]
     pub abandoned_text: String,
+
+    // Status marker colors
+    #[serde(default = "default_in_progress_marker")]
+    pub in_progress_marker: String,
+    #[serde(default = "default_blocked_marker")]
+    pub blocked_marker: String,
+    #[serde(default = "default_blocked_text")]
+    pub blocked_text: String,
 }
 
 fn default_abandoned_marker() -> String {
@@ -67,6 +78,18 @@ fn default_abandoned_text() -> String {
     "#5c6370".to_string()
 }
 
+fn default_in_progress_marker() -> String {
+    "#98c379".to_string()
+}
+
+fn default_blocked_marker() -> String {
+    "#d19a66".to_string()
+}
+
+fn default_blocked_text() -> String {
+    "#7f848e".to_string()
+}
+
 impl Default for ColorConfig {
     fn default() -> Self {
         Self {
@@ -120,35 +143,35 @@ impl Default for ColorConfig {
             // Abandoned task colors
             abandoned_marker: "#e06c75".to_string(),
             abandoned_text: "#5c6370".to_string(),
+
+            // Status marker colors
+            in_progress_marker: "#98c379".to_string(),
+            blocked_marker: "#d19a66".to_string(),
+            blocked_text: "#7f848e".to_string(),
         }
     }
 }
 
 impl ColorConfig {
-    pub fn load() -> Self {
-        let path = Self::config_path();
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(config) = serde_json::from_str(&content) {
-                    return config;
-                }
-            }
+    pub const FILE: &'static str = "colors.json";
+
+    /// Read `colors.json`, creating it with the defaults if it's missing.
+    /// Colors left out of the file keep their defaults.
+    pub fn try_load() -> Result<Self, String> {
+        if let Some(config) = paths::read_config(Self::FILE)? {
+            return Ok(config);
         }
 
         // Create default config
         let config = Self::default();
         if let Ok(json) = serde_json::to_string_pretty(&config) {
-            fs::write(&path, json).ok();
+            fs::write(Self::config_path(), json).ok();
         }
-        config
+        Ok(config)
     }
 
     fn config_path() -> PathBuf {
-        let config_dir = dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("zap");
-        fs::create_dir_all(&config_dir).ok();
-        config_dir.join("colors.json")
+        paths::config_file(Self::FILE)
     }
 
     /// Generate CSS from the color configuration
@@ -353,6 +376,92 @@ impl ColorConfig {
                 opacity: 0.7;
             }}
 
+            /* Status marker styles */
+            .in-progress-marker {{
+                color: {in_progress_marker};
+                font-family: monospace;
+                font-weight: bold;
+                font-size: 14px;
+            }}
+
+            .blocked-marker {{
+                color: {blocked_marker};
+                font-family: monospace;
+                font-weight: bold;
+                font-size: 14px;
+            }}
+
+            .blocked-text {{
+                color: {blocked_text};
+            }}
+
+            .blocked-row {{
+                opacity: 0.5;
+            }}
+
+            .completed-group-row {{
+                border-left: 3px solid {abandoned_text};
+            }}
+
+            .completed-group-text {{
+                color: {abandoned_text};
+                font-family: monospace;
+                font-style: italic;
+            }}
+
+            .trash-age,
+            .trash-empty {{
+                color: {text_secondary};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
+            .showcmd {{
+                color: {text_secondary};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
+            .hoist-breadcrumb {{
+                color: {cluster_title};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
+            .focus-indicator {{
+                color: {in_progress_marker};
+                font-family: monospace;
+                font-weight: bold;
+            }}
+
+            .focus-indicator.focus-break {{
+                color: {start_date_color};
+            }}
+
+            .focus-dimmed {{
+                opacity: 0.35;
+            }}
+
+            .time-tracked {{
+                color: {start_date_color};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
+            .time-running {{
+                color: {in_progress_marker};
+            }}
+
+            .time-over {{
+                color: {priority_high};
+            }}
+
+            .dependency-indicator {{
+                color: {blocked_marker};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
             /* Calendar styles */
             .calendar-header {{
                 color: {cluster_title};
@@ -407,6 +516,11 @@ impl ColorConfig {
                 text-decoration: line-through;
             }}
 
+            .calendar-task-selected {{
+                background-color: {todo_row_selected};
+                border-radius: 2px;
+            }}
+
             .calendar-task-more {{
                 color: {text_secondary};
                 font-family: monospace;
@@ -426,6 +540,59 @@ impl ColorConfig {
                 color: {priority_medium};
             }}
 
+            /* Week view styles */
+            .week-day {{
+                min-height: 320px;
+            }}
+
+            .week-task {{
+                color: {text_primary};
+                font-family: monospace;
+                font-size: 11px;
+                padding: 2px;
+                border-radius: 2px;
+            }}
+
+            .week-task-selected {{
+                background-color: {todo_row_selected};
+                border-left: 2px solid {checkbox_color};
+            }}
+
+            /* Board styles */
+            .board-column {{
+                background-color: {section_bg};
+                border-radius: 4px;
+                padding: 6px;
+            }}
+
+            .board-column-selected {{
+                border-top: 2px solid {mode_indicator};
+            }}
+
+            .board-column-header {{
+                color: {cluster_title};
+                font-family: monospace;
+                font-weight: bold;
+                font-size: 12px;
+                margin-bottom: 4px;
+            }}
+
+            .board-card {{
+                background-color: {todo_row_bg};
+                border-radius: 4px;
+            }}
+
+            .board-card-selected {{
+                background-color: {todo_row_selected};
+                border: 1px solid {checkbox_color};
+            }}
+
+            .board-card-text {{
+                color: {text_primary};
+                font-family: monospace;
+                font-size: 12px;
+            }}
+
             .calendar-nav-btn {{
                 background-color: {todo_row_bg};
                 color: {cluster_title};
@@ -474,6 +641,9 @@ impl ColorConfig {
             text_secondary = self.text_secondary,
             abandoned_marker = self.abandoned_marker,
             abandoned_text = self.abandoned_text,
+            in_progress_marker = self.in_progress_marker,
+            blocked_marker = self.blocked_marker,
+            blocked_text = self.blocked_text,
         )
     }
 }
@@ -0,0 +1,142 @@
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::todo::ARCHIVE_SUFFIX;
+
+/// Format version of `Envelope`
+const ENVELOPE_VERSION: u32 = 1;
+
+/// An encrypted cluster file: the cluster's JSON sealed with
+/// ChaCha20-Poly1305 under a key derived from a passphrase with Argon2id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub encrypted: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Envelope {
+    /// The envelope in a file's contents, None for a plain cluster file
+    pub fn parse(content: &str) -> Option<Self> {
+        serde_json::from_str(content).ok()
+    }
+
+    fn salt(&self) -> Result<Vec<u8>, String> {
+        BASE64.decode(&self.salt).map_err(|e| e.to_string())
+    }
+}
+
+/// A key derived from a passphrase, with the salt it was derived with
+#[derive(Clone)]
+pub struct Key {
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+impl Key {
+    /// Derive a key with a fresh random salt
+    pub fn generate(passphrase: &str) -> Result<Self, String> {
+        let salt: [u8; 16] = rand_bytes();
+        Self::derive(passphrase, &salt)
+    }
+
+    fn derive(passphrase: &str, salt: &[u8]) -> Result<Self, String> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| e.to_string())?;
+        Ok(Self { salt: salt.to_vec(), key })
+    }
+
+    /// Derive the key an envelope was sealed with, checking the passphrase
+    /// by opening it
+    pub fn for_envelope(passphrase: &str, envelope: &Envelope) -> Result<Self, String> {
+        let key = Self::derive(passphrase, &envelope.salt()?)?;
+        key.open(envelope)?;
+        Ok(key)
+    }
+
+    /// Encrypt with a fresh nonce
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Envelope, String> {
+        let cipher = ChaCha20Poly1305::new(&self.key.into());
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext).map_err(|e| e.to_string())?;
+        Ok(Envelope {
+            encrypted: ENVELOPE_VERSION,
+            salt: BASE64.encode(&self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    /// Decrypt, failing if the key is wrong or the envelope was tampered with
+    pub fn open(&self, envelope: &Envelope) -> Result<Vec<u8>, String> {
+        if envelope.encrypted != ENVELOPE_VERSION {
+            return Err(format!("unknown encryption format {}", envelope.encrypted));
+        }
+        let nonce = BASE64.decode(&envelope.nonce).map_err(|e| e.to_string())?;
+        let ciphertext = BASE64.decode(&envelope.ciphertext).map_err(|e| e.to_string())?;
+        if nonce.len() != 12 {
+            return Err("invalid nonce".to_string());
+        }
+        ChaCha20Poly1305::new(&self.key.into())
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| "wrong passphrase".to_string())
+    }
+}
+
+fn rand_bytes<const N: usize>() -> [u8; N] {
+    use chacha20poly1305::aead::rand_core::RngCore;
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+thread_local! {
+    /// Keys of the clusters unlocked in this run; never written anywhere
+    static KEYS: RefCell<HashMap<String, Key>> = RefCell::new(HashMap::new());
+}
+
+/// A cluster and its archive share a key
+fn key_name(cluster: &str) -> &str {
+    cluster.strip_suffix(ARCHIVE_SUFFIX).unwrap_or(cluster)
+}
+
+/// Encrypt a cluster (and its archive) with `key` from now on
+pub fn remember(cluster: &str, key: Key) {
+    KEYS.with(|keys| keys.borrow_mut().insert(key_name(cluster).to_string(), key));
+}
+
+/// Store a cluster (and its archive) in plain text from now on
+pub fn forget(cluster: &str) {
+    KEYS.with(|keys| keys.borrow_mut().remove(key_name(cluster)));
+}
+
+/// The key of an unlocked encrypted cluster
+pub fn key_for(cluster: &str) -> Option<Key> {
+    KEYS.with(|keys| keys.borrow().get(key_name(cluster)).cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = Key::generate("correct horse").unwrap();
+        let envelope = key.seal(b"{\"todos\": []}").unwrap();
+        let parsed = Envelope::parse(&serde_json::to_string(&envelope).unwrap()).unwrap();
+        assert_eq!(key.open(&parsed).unwrap(), b"{\"todos\": []}");
+
+        assert!(Key::for_envelope("correct horse", &parsed).is_ok());
+        assert_eq!(Key::for_envelope("wrong", &parsed).err().as_deref(), Some("wrong passphrase"));
+        assert!(Envelope::parse("{\"schema_version\": 2, \"todos\": []}").is_none());
+    }
+}
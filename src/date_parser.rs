@@ -71,7 +71,69 @@ pub fn parse_date(input: &str) -> (String, Option<NaiveDate>) {
     (input.to_string(), None)
 }
 
-fn try_parse_date(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+/// Parse dependency markers from input text.
+/// Returns (remaining text, id prefixes) after removing all [after:ID] markers.
+pub fn parse_dependencies(input: &str) -> (String, Vec<String>) {
+    let re = Regex::new(r"(?i)\[after:([^\]]+)\]").unwrap();
+
+    let prefixes: Vec<String> = re
+        .captures_iter(input)
+        .map(|caps| caps.get(1).unwrap().as_str().trim().to_lowercase())
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    if prefixes.is_empty() {
+        return (input.to_string(), prefixes);
+    }
+
+    let result = re.replace_all(input, "");
+    let result = result.split_whitespace().collect::<Vec<_>>().join(" ");
+    (result, prefixes)
+}
+
+/// Parse estimate markers from input text.
+/// Returns (remaining text, minutes) after removing an [est:DURATION] or [~DURATION] marker.
+/// Durations: 30m, 2h, 1h30m, 1.5h, or plain minutes
+pub fn parse_estimate(input: &str) -> (String, Option<u32>) {
+    let re = Regex::new(r"(?i)\[(?:est:|~)([^\]]+)\]").unwrap();
+
+    if let Some(caps) = re.captures(input) {
+        let full_match = caps.get(0).unwrap();
+        let duration_str = caps.get(1).unwrap().as_str().trim().to_lowercase();
+
+        if let Some(minutes) = parse_duration(&duration_str) {
+            let before = &input[..full_match.start()];
+            let after = &input[full_match.end()..];
+            let result = format!("{}{}", before, after);
+            let result = result.split_whitespace().collect::<Vec<_>>().join(" ");
+            return (result, Some(minutes));
+        }
+    }
+
+    (input.to_string(), None)
+}
+
+/// Parse a duration like "2h", "30m", "1h30m", "1.5h" or "45" into minutes.
+/// Expects lowercase input.
+pub fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.replace(' ', "");
+    if let Ok(minutes) = s.parse::<u32>() {
+        return Some(minutes);
+    }
+
+    let re = Regex::new(r"^(?:(\d+(?:\.\d+)?)h)?(?:(\d+)m)?$").unwrap();
+    let caps = re.captures(&s)?;
+    if caps.get(1).is_none() && caps.get(2).is_none() {
+        return None;
+    }
+    let hours: f64 = caps.get(1).map_or(Ok(0.0), |m| m.as_str().parse()).ok()?;
+    let minutes: u32 = caps.get(2).map_or(Ok(0), |m| m.as_str().parse()).ok()?;
+    Some((hours * 60.0).round() as u32 + minutes)
+}
+
+/// Parse a bare date expression (the part inside a `[date:...]` marker).
+/// Expects lowercase input.
+pub fn try_parse_date(s: &str, today: NaiveDate) -> Option<NaiveDate> {
     match s {
         "today" | "tod" => Some(today),
         "tomorrow" | "tom" => Some(today + Duration::days(1)),
@@ -390,6 +452,37 @@ mod tests {
         assert_eq!(priority, Priority::Medium);
     }
 
+    // Dependency parsing tests
+    #[test]
+    fn test_parse_dependencies() {
+        let (text, deps) = parse_dependencies("Deploy [after:3F2A] [after: 9b1c ] now");
+        assert_eq!(text, "Deploy now");
+        assert_eq!(deps, vec!["3f2a".to_string(), "9b1c".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_estimate() {
+        let (text, est) = parse_estimate("Write report [est:2h]");
+        assert_eq!(text, "Write report");
+        assert_eq!(est, Some(120));
+
+        let (text, est) = parse_estimate("Call [~30m] back");
+        assert_eq!(text, "Call back");
+        assert_eq!(est, Some(30));
+
+        assert_eq!(parse_duration("1h30m"), Some(90));
+        assert_eq!(parse_duration("1.5h"), Some(90));
+        assert_eq!(parse_duration("45"), Some(45));
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn test_no_dependencies() {
+        let (text, deps) = parse_dependencies("Deploy [d:tomorrow]");
+        assert_eq!(text, "Deploy [d:tomorrow]");
+        assert!(deps.is_empty());
+    }
+
     #[test]
     fn test_no_priority() {
         let (text, priority) = parse_priority("Regular task");
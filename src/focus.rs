@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// Interval lengths for focus (pomodoro) mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusConfig {
+    #[serde(default = "default_work_minutes")]
+    pub work_minutes: u32,
+    #[serde(default = "default_break_minutes")]
+    pub break_minutes: u32,
+    #[serde(default = "default_long_break_minutes")]
+    pub long_break_minutes: u32,
+    /// Every Nth break is a long one (0 disables long breaks)
+    #[serde(default = "default_long_break_every")]
+    pub long_break_every: u32,
+}
+
+fn default_work_minutes() -> u32 {
+    25
+}
+
+fn default_break_minutes() -> u32 {
+    5
+}
+
+fn default_long_break_minutes() -> u32 {
+    15
+}
+
+fn default_long_break_every() -> u32 {
+    4
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            work_minutes: default_work_minutes(),
+            break_minutes: default_break_minutes(),
+            long_break_minutes: default_long_break_minutes(),
+            long_break_every: default_long_break_every(),
+        }
+    }
+}
+
+impl FocusConfig {
+    pub const FILE: &'static str = "focus.json";
+
+    /// Read `focus.json`, using the defaults if it can't be parsed
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_default()
+    }
+
+    /// Read `focus.json`, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        paths::load_config(Self::FILE)
+    }
+
+    /// Length of the break after the given number of completed work intervals
+    pub fn break_after(&self, completed: u32) -> u32 {
+        if self.long_break_every > 0 && completed.is_multiple_of(self.long_break_every) {
+            self.long_break_minutes
+        } else {
+            self.break_minutes
+        }
+    }
+}
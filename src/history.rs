@@ -0,0 +1,442 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::paths;
+use crate::todo::{Status, Todo, TodoList};
+
+/// Opt-in git history and sync of the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// URL or path of the repository `:sync` pulls from and pushes to
+    #[serde(default)]
+    pub remote: Option<String>,
+    #[serde(default = "default_branch")]
+    pub branch: String,
+    /// Saves are collected into one commit this often
+    #[serde(default = "default_commit_interval")]
+    pub commit_interval_seconds: u32,
+}
+
+fn default_branch() -> String {
+    "main".to_string()
+}
+
+fn default_commit_interval() -> u32 {
+    30
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remote: None,
+            branch: default_branch(),
+            commit_interval_seconds: default_commit_interval(),
+        }
+    }
+}
+
+impl GitConfig {
+    pub const FILE: &'static str = "git.json";
+
+    /// Read `git.json`, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        paths::load_config(Self::FILE)
+    }
+}
+
+/// A commit that changed a cluster
+pub struct LogEntry {
+    pub hash: String,
+    /// Relative date, e.g. "2 hours ago"
+    pub when: String,
+    pub message: String,
+}
+
+/// A git repository holding cluster files, driven through the git command
+#[derive(Clone)]
+pub struct Repo {
+    dir: PathBuf,
+}
+
+impl Repo {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The data directory as a repository, if git history is enabled
+    pub fn open_data_dir(config: &GitConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let repo = Self::new(TodoList::data_dir());
+        let driver = std::env::current_exe().ok();
+        repo.init(&config.branch, driver.as_deref()).ok()?;
+        if let Some(remote) = &config.remote {
+            repo.set_remote(remote).ok()?;
+        }
+        Some(repo)
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String, String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| format!("git: {}", e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    /// Create the repository if needed. Cluster files are merged by task id
+    /// through `merge_driver` (the zap binary) when given.
+    pub fn init(&self, branch: &str, merge_driver: Option<&Path>) -> Result<(), String> {
+        if !self.dir.join(".git").exists() {
+            self.git(&["init", "-q", "-b", branch])?;
+        }
+        // Commits need an identity; don't override one the user has set
+        if self.git(&["config", "user.name"]).is_err() {
+            self.git(&["config", "user.name", "zap"])?;
+            self.git(&["config", "user.email", "zap@localhost"])?;
+        }
+        // Sessions, trash and merge bases are local to this machine
+        self.ensure_line(".gitignore", "*/")?;
+        if let Some(driver) = merge_driver {
+            self.ensure_line(".gitattributes", "*.json merge=zap-tasks")?;
+            self.git(&["config", "merge.zap-tasks.name", "merge zap clusters by task id"])?;
+            let command = format!("'{}' --merge-driver %O %A %B", driver.display());
+            self.git(&["config", "merge.zap-tasks.driver", &command])?;
+        }
+        Ok(())
+    }
+
+    /// Append `line` to a file in the repository unless it's already there,
+    /// keeping whatever else the user put in it
+    fn ensure_line(&self, file: &str, line: &str) -> Result<(), String> {
+        let path = self.dir.join(file);
+        let mut content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("{}: {}", file, err)),
+        };
+        if content.lines().any(|l| l.trim() == line) {
+            return Ok(());
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(line);
+        content.push('\n');
+        fs::write(&path, content).map_err(|e| format!("{}: {}", file, e))
+    }
+
+    pub fn set_remote(&self, url: &str) -> Result<(), String> {
+        if self.git(&["remote", "get-url", "origin"]).is_ok() {
+            self.git(&["remote", "set-url", "origin", url])?;
+        } else {
+            self.git(&["remote", "add", "origin", url])?;
+        }
+        Ok(())
+    }
+
+    /// Commit all changed files, with a message describing the task changes.
+    /// Returns false if there was nothing to commit.
+    pub fn commit_pending(&self) -> Result<bool, String> {
+        let status = self.git(&["status", "--porcelain"])?;
+        if status.trim().is_empty() {
+            return Ok(false);
+        }
+
+        let mut changes = Vec::new();
+        for line in status.lines() {
+            let file = line.get(3..).unwrap_or("").trim_matches('"');
+            let cluster = match file.strip_suffix(".json") {
+                Some(cluster) => cluster,
+                None => continue,
+            };
+            let old = self.git(&["show", &format!("HEAD:{}", file)]).ok().and_then(|s| TodoList::parse(&s).ok());
+            let new = fs::read_to_string(self.dir.join(file)).ok().and_then(|s| TodoList::parse(&s).ok());
+            match (old, new) {
+                (Some(old), Some(new)) => changes.extend(describe_changes(&old.todos, &new.todos)),
+                (None, Some(_)) => changes.push(format!("new cluster: {}", cluster)),
+                (Some(_), None) => changes.push(format!("remove cluster: {}", cluster)),
+                (None, None) => {}
+            }
+        }
+        let message = match changes.len() {
+            0 => "update tasks".to_string(),
+            1 => changes.remove(0),
+            n => format!("{} changes\n\n{}", n, changes.join("\n")),
+        };
+
+        self.git(&["add", "-A"])?;
+        self.git(&["commit", "-q", "-m", &message])?;
+        Ok(true)
+    }
+
+    /// Commits that changed a cluster, newest first
+    pub fn log(&self, cluster: &str, limit: usize) -> Result<Vec<LogEntry>, String> {
+        let output = self.git(&[
+            "log",
+            &format!("-n{}", limit),
+            "--format=%h%x09%ar%x09%s",
+            "--",
+            &format!("{}.json", cluster),
+        ])?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, '\t');
+                Some(LogEntry {
+                    hash: parts.next()?.to_string(),
+                    when: parts.next()?.to_string(),
+                    message: parts.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Write a cluster file as it was in a commit, and commit the change
+    pub fn revert(&self, cluster: &str, hash: &str) -> Result<(), String> {
+        self.commit_pending()?;
+        let file = format!("{}.json", cluster);
+        let content = self.git(&["show", &format!("{}:{}", hash, file)])?;
+        fs::write(self.dir.join(&file), content).map_err(|e| e.to_string())?;
+        if self.git(&["status", "--porcelain", "--", &file])?.trim().is_empty() {
+            return Ok(());
+        }
+        self.git(&["add", "--", &file])?;
+        self.git(&["commit", "-q", "-m", &format!("revert: {} to {}", cluster, hash)])?;
+        Ok(())
+    }
+
+    /// Commit pending changes, pull from and push to the remote
+    pub fn sync(&self, branch: &str) -> Result<(), String> {
+        self.commit_pending()?;
+        if self.git(&["ls-remote", "--exit-code", "--heads", "origin", branch]).is_ok() {
+            let pulled = self.git(&["pull", "-q", "--no-rebase", "--no-edit", "--allow-unrelated-histories", "origin", branch]);
+            if let Err(e) = pulled {
+                self.git(&["merge", "--abort"]).ok();
+                return Err(e);
+            }
+        }
+        self.git(&["push", "-q", "origin", &format!("HEAD:{}", branch)])?;
+        Ok(())
+    }
+}
+
+/// One line per task change between two versions of a cluster,
+/// e.g. "complete: Deploy API"
+pub fn describe_changes(old: &[Todo], new: &[Todo]) -> Vec<String> {
+    fn index<'a>(todos: &'a [Todo], map: &mut HashMap<&'a str, &'a Todo>, order: &mut Vec<&'a Todo>) {
+        for todo in todos {
+            map.insert(&todo.id, todo);
+            order.push(todo);
+            index(&todo.subtasks, map, order);
+        }
+    }
+    let (mut old_map, mut old_order) = (HashMap::new(), Vec::new());
+    let (mut new_map, mut new_order) = (HashMap::new(), Vec::new());
+    index(old, &mut old_map, &mut old_order);
+    index(new, &mut new_map, &mut new_order);
+
+    let mut changes = Vec::new();
+    for todo in &new_order {
+        let before = match old_map.get(todo.id.as_str()) {
+            Some(before) => before,
+            None => {
+                changes.push(format!("add: {}", todo.text));
+                continue;
+            }
+        };
+        let change = if before.status != todo.status {
+            match todo.status {
+                Status::Done => "complete",
+                Status::Abandoned => "abandon",
+                _ if before.status.is_closed() => "reopen",
+                _ => "status",
+            }
+        } else if before.text != todo.text {
+            "edit"
+        } else if without_subtasks(before) != without_subtasks(todo) {
+            "update"
+        } else {
+            continue;
+        };
+        changes.push(format!("{}: {}", change, todo.text));
+    }
+    for todo in &old_order {
+        if !new_map.contains_key(todo.id.as_str()) {
+            changes.push(format!("delete: {}", todo.text));
+        }
+    }
+    if changes.is_empty() && old != new {
+        changes.push("reorder tasks".to_string());
+    }
+    changes
+}
+
+fn without_subtasks(todo: &Todo) -> Todo {
+    Todo { subtasks: Vec::new(), ..todo.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Priority;
+
+    fn task(text: &str) -> Todo {
+        Todo::new(text.to_string(), None, Priority::None)
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let old = vec![task("Deploy API"), task("Write docs"), task("Old")];
+        let mut new = old.clone();
+        new[0].status = Status::Done;
+        new[1].priority = Priority::High;
+        new.remove(2);
+        new.push(task("Fresh"));
+        assert_eq!(
+            describe_changes(&old, &new),
+            ["complete: Deploy API", "update: Write docs", "add: Fresh", "delete: Old"]
+        );
+        let reordered = vec![old[1].clone(), old[0].clone(), old[2].clone()];
+        assert_eq!(describe_changes(&old, &reordered), ["reorder tasks"]);
+    }
+
+    #[test]
+    fn test_sync_through_bare_repo() {
+        let root = std::env::temp_dir().join(format!("zap-history-test-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        let (remote, a, b) = (root.join("remote.git"), root.join("a"), root.join("b"));
+        for dir in [&remote, &a, &b] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        Repo::new(&remote).git(&["init", "-q", "--bare", "-b", "main"]).unwrap();
+        let write = |dir: &Path, todos: Vec<Todo>| {
+            let mut list = TodoList::default();
+            list.todos = todos;
+            fs::write(dir.join("work.json"), serde_json::to_string_pretty(&list).unwrap()).unwrap();
+        };
+
+        let repo_a = Repo::new(&a);
+        repo_a.init("main", None).unwrap();
+        repo_a.set_remote(remote.to_str().unwrap()).unwrap();
+        let mut todos = vec![task("Deploy API")];
+        write(&a, todos.clone());
+        repo_a.sync("main").unwrap();
+
+        // The second machine gets the cluster, completes the task and pushes
+        let repo_b = Repo::new(&b);
+        repo_b.init("main", None).unwrap();
+        repo_b.set_remote(remote.to_str().unwrap()).unwrap();
+        repo_b.sync("main").unwrap();
+        todos[0].status = Status::Done;
+        write(&b, todos);
+        repo_b.sync("main").unwrap();
+        assert_eq!(repo_b.log("work", 10).unwrap()[0].message, "complete: Deploy API");
+
+        repo_a.sync("main").unwrap();
+        let list = TodoList::parse(&fs::read_to_string(a.join("work.json")).unwrap()).unwrap();
+        assert_eq!(list.todos[0].status, Status::Done);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_init_keeps_user_files() {
+        let dir = std::env::temp_dir().join(format!("zap-history-init-test-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".gitignore"), "*.bak").unwrap();
+        let repo = Repo::new(&dir);
+        repo.init("main", None).unwrap();
+        repo.init("main", None).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(".gitignore")).unwrap(), "*.bak\n*/\n");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Stands in for `zap --merge-driver` in `test_sync_merges_by_task`, which
+    /// runs this test binary with the files in ZAP_TEST_MERGE
+    #[test]
+    #[ignore]
+    fn merge_driver() {
+        let files = match std::env::var("ZAP_TEST_MERGE") {
+            Ok(files) => files,
+            Err(_) => return,
+        };
+        let paths: Vec<&Path> = files.lines().map(Path::new).collect();
+        assert!(crate::merge::merge_files(paths[0], paths[1], paths[2]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sync_merges_by_task() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("zap-history-driver-test-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        let (remote, a, b) = (root.join("remote.git"), root.join("a"), root.join("b"));
+        for dir in [&remote, &a, &b] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        Repo::new(&remote).git(&["init", "-q", "--bare", "-b", "main"]).unwrap();
+        let driver = root.join("driver.sh");
+        let exe = std::env::current_exe().unwrap();
+        let ran = root.join("driver-ran");
+        let script = format!(
+            "#!/bin/sh\ntouch '{}'\nZAP_TEST_MERGE=\"$2\n$3\n$4\" exec '{}' --exact history::tests::merge_driver --ignored --quiet\n",
+            ran.display(),
+            exe.display()
+        );
+        fs::write(&driver, script).unwrap();
+        fs::set_permissions(&driver, fs::Permissions::from_mode(0o755)).unwrap();
+        let read = |dir: &Path| TodoList::parse(&fs::read_to_string(dir.join("work.json")).unwrap()).unwrap().todos;
+        let write = |dir: &Path, todos: Vec<Todo>| {
+            let mut list = TodoList::default();
+            list.todos = todos;
+            fs::write(dir.join("work.json"), serde_json::to_string_pretty(&list).unwrap()).unwrap();
+        };
+
+        let (repo_a, repo_b) = (Repo::new(&a), Repo::new(&b));
+        for repo in [&repo_a, &repo_b] {
+            repo.init("main", Some(&driver)).unwrap();
+            repo.set_remote(remote.to_str().unwrap()).unwrap();
+        }
+        write(&a, vec![task("Deploy API"), task("Write docs")]);
+        repo_a.sync("main").unwrap();
+        repo_b.sync("main").unwrap();
+
+        // Both machines complete a different task and add one at the end
+        let mut todos = read(&a);
+        todos[0].status = Status::Done;
+        todos.push(task("From A"));
+        write(&a, todos);
+        repo_a.sync("main").unwrap();
+        let mut todos = read(&b);
+        todos[1].status = Status::Done;
+        todos.push(task("From B"));
+        write(&b, todos);
+        repo_b.sync("main").unwrap();
+        repo_a.sync("main").unwrap();
+        assert!(ran.exists());
+
+        // B merged, keeping its own task ahead of the one it pulled
+        for dir in [&a, &b] {
+            let todos = read(dir);
+            let texts: Vec<&str> = todos.iter().map(|t| t.text.as_str()).collect();
+            assert_eq!(texts, ["Deploy API", "Write docs", "From B", "From A"]);
+            assert!(todos[..2].iter().all(|t| t.status == Status::Done));
+        }
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,9 +1,12 @@
 use gtk4::gdk;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
 
+use crate::paths;
+
 /// All possible actions that can be triggered by keybindings
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -17,10 +20,22 @@ pub enum Action {
     // Task operations
     ToggleComplete,
     Abandon,  // Alt+Enter - mark as never done
+    CycleStatus,  // s - open -> in progress -> blocked -> done -> abandoned
     Delete,  // dd
     MoveTaskDown,
     MoveTaskUp,
     ToggleFold,  // za
+    HoistIn,     // zi - show only the selected task's subtree
+    HoistOut,    // zo - go back up one level
+
+    // Scheduling
+    DateForward,  // + - push due date a day later
+    DateBack,     // - - pull due date a day earlier
+    SetDate,      // ds - prompt for a date expression
+    ClearDate,    // dx
+
+    // Time tracking
+    ToggleTimer,  // ts - start/stop the timer on the selected task
 
     // Insert modes
     Insert,
@@ -32,221 +47,853 @@ pub enum Action {
 
     // Cancel/escape
     Cancel,
+
+    // Calendar view
+    PrevDay,      // h
+    NextDay,      // l
+    PrevWeek,     // k - same weekday a week earlier
+    NextWeek,     // j
+    PrevMonth,    // <
+    NextMonth,    // >
+    NextDayTask,  // n - next task on the selected day
+    PrevDayTask,  // N
+
+    // Board view
+    PrevColumn,    // h
+    NextColumn,    // l
+    MoveCardPrev,  // H - move the card to the previous column
+    MoveCardNext,  // L
+
+    // Trash view
+    Restore,  // r - put the task back where it was deleted from
+
+    // Insert and command modes
+    HistoryPrev,  // replace the text with what was entered before it
+    HistoryNext,
+    Complete,     // Tab - complete the command
+}
+
+impl Action {
+    /// Whether the action changes tasks (not allowed in read-only archives)
+    pub fn modifies_tasks(self) -> bool {
+        !matches!(
+            self,
+            Action::MoveDown
+                | Action::MoveUp
+                | Action::JumpToFirst
+                | Action::JumpToLast
+                | Action::ToggleFold
+                | Action::HoistIn
+                | Action::HoistOut
+                | Action::CommandMode
+                | Action::Cancel
+                | Action::PrevDay
+                | Action::NextDay
+                | Action::PrevWeek
+                | Action::NextWeek
+                | Action::PrevMonth
+                | Action::NextMonth
+                | Action::NextDayTask
+                | Action::PrevDayTask
+                | Action::PrevColumn
+                | Action::NextColumn
+                | Action::Restore
+                | Action::HistoryPrev
+                | Action::HistoryNext
+                | Action::Complete
+        )
+    }
+
+    /// Whether the action works on the selected task, which a "Completed (N)"
+    /// row is not
+    pub fn acts_on_task(self) -> bool {
+        matches!(
+            self,
+            Action::ToggleComplete
+                | Action::Abandon
+                | Action::CycleStatus
+                | Action::Delete
+                | Action::MoveTaskDown
+                | Action::MoveTaskUp
+                | Action::HoistIn
+                | Action::DateForward
+                | Action::DateBack
+                | Action::SetDate
+                | Action::ClearDate
+                | Action::ToggleTimer
+                | Action::InsertSubtask
+                | Action::Edit
+        )
+    }
+
+    fn is_calendar(self) -> bool {
+        matches!(
+            self,
+            Action::PrevDay
+                | Action::NextDay
+                | Action::PrevWeek
+                | Action::NextWeek
+                | Action::PrevMonth
+                | Action::NextMonth
+                | Action::NextDayTask
+                | Action::PrevDayTask
+        )
+    }
+
+    fn is_board(self) -> bool {
+        matches!(self, Action::PrevColumn | Action::NextColumn | Action::MoveCardPrev | Action::MoveCardNext)
+    }
+
+    pub fn is_scheduling(self) -> bool {
+        matches!(self, Action::DateForward | Action::DateBack | Action::SetDate | Action::ClearDate)
+    }
+}
+
+/// Where a set of bindings applies: a view in normal mode, or one of the
+/// text entry modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    List,
+    Calendar,
+    Week,
+    Board,
+    Trash,
+    /// Typing a task or a date
+    Insert,
+    /// Typing a command or a passphrase
+    Command,
 }
+
+impl Mode {
+    fn parse(name: &str) -> Option<Self> {
+        serde_json::from_value(name.into()).ok()
+    }
+
+    /// Whether the action does anything in this mode
+    fn allows(self, action: Action) -> bool {
+        match self {
+            Mode::List => {
+                !action.is_calendar()
+                    && !action.is_board()
+                    && !matches!(action, Action::Restore | Action::HistoryPrev | Action::HistoryNext | Action::Complete)
+            }
+            Mode::Calendar => {
+                action.is_calendar() || action.is_scheduling() || matches!(action, Action::Insert | Action::CommandMode)
+            }
+            Mode::Week => {
+                action.is_scheduling()
+                    || matches!(
+                        action,
+                        Action::PrevDay
+                            | Action::NextDay
+                            | Action::PrevWeek
+                            | Action::NextWeek
+                            | Action::NextDayTask
+                            | Action::PrevDayTask
+                            | Action::Insert
+                            | Action::CommandMode
+                    )
+            }
+            Mode::Board => action.is_board() || matches!(action, Action::MoveDown | Action::MoveUp | Action::CommandMode),
+            Mode::Trash => matches!(action, Action::MoveDown | Action::MoveUp | Action::Restore | Action::CommandMode),
+            Mode::Insert => matches!(action, Action::Cancel | Action::HistoryPrev | Action::HistoryNext),
+            Mode::Command => matches!(action, Action::Cancel | Action::HistoryPrev | Action::HistoryNext | Action::Complete),
+        }
+    }
+
+    /// Whether keys go to a text field, so that only single keys that don't
+    /// type anything can be bound
+    fn is_typing(self) -> bool {
+        matches!(self, Mode::Insert | Mode::Command)
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self).ok();
+        write!(f, "{}", name.as_ref().and_then(|v| v.as_str()).unwrap_or_default())
+    }
+}
+
 /// Keybindings configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeybindingsConfig {
-    pub bindings: HashMap<String, KeyBinding>,
+    /// What `<leader>` stands for in bindings
+    #[serde(default = "default_leader")]
+    pub leader: String,
+    /// How long a key that is also the start of a longer binding waits for
+    /// the next key; 0 waits forever
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Bindings by name, for each mode
+    #[serde(flatten)]
+    pub modes: BTreeMap<Mode, HashMap<String, KeyBinding>>,
+}
+
+fn default_leader() -> String {
+    "\\".to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyBinding {
+    /// Keys in vim notation, e.g. "j", "gg", "<C-d>" or "<leader>ct"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<String>,
+    /// A single key by its GDK name, as older config files bind it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub key: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub shift: bool,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub ctrl: bool,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub alt: bool,
     pub action: Action,
-    /// For multi-key sequences like "gg" or "dd", this is the pending key
-    #[serde(default)]
+    /// With `key`, the first key of a two-key sequence like "gg" or "dd"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub pending: Option<String>,
 }
 
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl KeyBinding {
+    fn new(keys: &str, action: Action) -> Self {
+        Self {
+            keys: Some(keys.to_string()),
+            key: String::new(),
+            shift: false,
+            ctrl: false,
+            alt: false,
+            action,
+            pending: None,
+        }
+    }
+
+    /// The keys to press, `<leader>` standing for `leader`
+    fn chords(&self, leader: &[Chord]) -> Result<Vec<Chord>, String> {
+        if let Some(ref keys) = self.keys {
+            return parse_keys(keys, leader);
+        }
+        let mut chords = Vec::new();
+        if let Some(ref pending) = self.pending {
+            chords.push(Chord::named(pending, false, false, false)?);
+        }
+        chords.push(Chord::named(&self.key, self.shift, self.ctrl, self.alt)?);
+        Ok(chords)
+    }
+}
+
+/// One key press with its modifiers
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    /// GDK key name, e.g. "g", "G", "colon" or "Return"
+    key: String,
+    ctrl: bool,
+    alt: bool,
+    /// Only for keys whose name doesn't already include it, like Return
+    shift: bool,
+}
+
+impl Chord {
+    /// Whether the chord types a character into a text field
+    fn types_text(&self) -> bool {
+        !self.ctrl && !self.alt && (key_char(&self.key).is_some() || self.key == "space")
+    }
+
+    /// The chord of a key press
+    pub fn from_key(key: &gdk::Key, shift: bool, ctrl: bool, alt: bool) -> Self {
+        Self::new(key_to_string(key), shift, ctrl, alt)
+    }
+
+    fn new(key: String, shift: bool, ctrl: bool, alt: bool) -> Self {
+        // Shift already picked the key of printable ones: "G", "colon", "plus"
+        let printable = key_char(&key).is_some();
+        let key = if shift && key.chars().count() == 1 { key.to_uppercase() } else { key };
+        Self { key, ctrl, alt, shift: shift && !printable }
+    }
+
+    /// A chord from a key name, which GDK has to know
+    fn named(name: &str, shift: bool, ctrl: bool, alt: bool) -> Result<Self, String> {
+        if name.is_empty() {
+            return Err("has no keys".to_string());
+        }
+        if gdk::Key::from_name(name).is_none() {
+            return Err(format!("has unknown key '{}'", name));
+        }
+        Ok(Self::new(name.to_string(), shift, ctrl, alt))
+    }
+}
+
+/// In vim notation
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain = key_char(&self.key).filter(|c| *c != ' ' && *c != '<');
+        if let (Some(c), false, false, false) = (plain, self.ctrl, self.alt, self.shift) {
+            return write!(f, "{}", c);
+        }
+        write!(f, "<")?;
+        for (held, prefix) in [(self.ctrl, "C-"), (self.alt, "A-"), (self.shift, "S-")] {
+            if held {
+                write!(f, "{}", prefix)?;
+            }
+        }
+        match self.key.as_str() {
+            "space" => write!(f, "Space>"),
+            "less" => write!(f, "lt>"),
+            "Return" => write!(f, "CR>"),
+            "Escape" => write!(f, "Esc>"),
+            key => match key_char(key) {
+                Some(c) => write!(f, "{}>", c),
+                None => write!(f, "{}>", key),
+            },
+        }
+    }
+}
+
+/// Keys as typed, e.g. "gg" or "\ct"
+pub fn show_keys(keys: &[Chord]) -> String {
+    keys.iter().map(Chord::to_string).collect()
+}
+
+/// GDK names of the printable keys that aren't named after their character
+const PUNCTUATION: [(char, &str); 33] = [
+    (' ', "space"), ('!', "exclam"), ('"', "quotedbl"), ('#', "numbersign"), ('$', "dollar"),
+    ('%', "percent"), ('&', "ampersand"), ('\'', "apostrophe"), ('(', "parenleft"), (')', "parenright"),
+    ('*', "asterisk"), ('+', "plus"), (',', "comma"), ('-', "minus"), ('.', "period"),
+    ('/', "slash"), (':', "colon"), (';', "semicolon"), ('<', "less"), ('=', "equal"),
+    ('>', "greater"), ('?', "question"), ('@', "at"), ('[', "bracketleft"), ('\\', "backslash"),
+    (']', "bracketright"), ('^', "asciicircum"), ('_', "underscore"), ('`', "grave"), ('{', "braceleft"),
+    ('|', "bar"), ('}', "braceright"), ('~', "asciitilde"),
+];
+
+/// The character a printable key types
+fn key_char(key: &str) -> Option<char> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => PUNCTUATION.iter().find(|(_, name)| *name == key).map(|(c, _)| *c),
+    }
+}
+
+/// The GDK name of the key that types `c`
+fn char_key(c: char) -> String {
+    match PUNCTUATION.iter().find(|(p, _)| *p == c) {
+        Some((_, name)) => name.to_string(),
+        None => c.to_string(),
+    }
+}
+
+/// Parse keys in vim notation. Characters stand for themselves and `<...>`
+/// holds a key name with optional `C-`, `A-` and `S-` modifiers, e.g. "gg",
+/// "<C-d>", "<S-CR>" or "<leader>ct".
+pub fn parse_keys(notation: &str, leader: &[Chord]) -> Result<Vec<Chord>, String> {
+    let mut chords = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        // A "<" without a closing ">" is just the key
+        let special = match c {
+            '<' => rest[1..].find('>').filter(|&len| len > 0).map(|len| &rest[1..=len]),
+            _ => None,
+        };
+        match special {
+            Some(inner) if inner.eq_ignore_ascii_case("leader") => {
+                rest = &rest[inner.len() + 2..];
+                chords.extend_from_slice(leader);
+            }
+            Some(inner) => {
+                rest = &rest[inner.len() + 2..];
+                chords.push(parse_special(inner)?);
+            }
+            None => {
+                rest = &rest[c.len_utf8()..];
+                chords.push(Chord::named(&char_key(c), false, false, false)?);
+            }
+        }
+    }
+    if chords.is_empty() {
+        return Err("has no keys".to_string());
+    }
+    Ok(chords)
+}
+
+/// The key inside `<...>`, e.g. "C-d", "S-Tab" or "Esc"
+fn parse_special(inner: &str) -> Result<Chord, String> {
+    let unknown = || format!("has unknown key '<{}>'", inner);
+    let (mut shift, mut ctrl, mut alt) = (false, false, false);
+    let mut name = inner;
+    // The key itself may be "-", as in "<C-->"
+    while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+        match modifier.to_ascii_uppercase().as_str() {
+            "C" => ctrl = true,
+            "A" | "M" => alt = true,
+            "S" => shift = true,
+            _ => return Err(unknown()),
+        }
+        name = rest;
+    }
+    let mut chars = name.chars();
+    let key = match (chars.next(), chars.next()) {
+        (Some(c), None) => char_key(c),
+        _ => match name.to_ascii_lowercase().as_str() {
+            "cr" | "enter" | "return" => "Return".to_string(),
+            "esc" => "Escape".to_string(),
+            "bs" => "BackSpace".to_string(),
+            "tab" => "Tab".to_string(),
+            "space" => "space".to_string(),
+            "lt" => "less".to_string(),
+            _ => name.to_string(),
+        },
+    };
+    Chord::named(&key, shift, ctrl, alt).map_err(|_| unknown())
+}
+
+/// `keybindings.json` with the bindings left unparsed, so that one bad
+/// binding doesn't take the others down with it
+#[derive(Deserialize)]
+struct RawConfig {
+    #[serde(default = "default_leader")]
+    leader: String,
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+    /// The modes' bindings, and `bindings` for the list bindings of older
+    /// files
+    #[serde(flatten)]
+    modes: HashMap<String, serde_json::Value>,
+}
+
+/// A binding and the line of `keybindings.json` it's on, None for a default
+/// missing from the file
+struct Entry {
+    mode: Mode,
+    name: String,
+    binding: KeyBinding,
+    line: Option<usize>,
+}
+
+impl Entry {
+    fn at(&self) -> String {
+        match self.line {
+            Some(line) => format!("{} '{}' (line {})", self.mode, self.name, line),
+            None => format!("built-in {} '{}'", self.mode, self.name),
+        }
+    }
+
+    fn problem(&self, message: &str) -> String {
+        problem(&format!("{} '{}'", self.mode, self.name), self.line, message)
+    }
+}
+
+fn problem(what: &str, line: Option<usize>, message: &str) -> String {
+    match line {
+        Some(line) => format!("{} line {}: {} {}", Keybindings::FILE, line, what, message),
+        None => format!("{}: built-in {} {}", Keybindings::FILE, what, message),
+    }
+}
+
+/// Bindings sharing their first keys share a path from the root
+#[derive(Default)]
+struct Node {
+    action: Option<Action>,
+    next: HashMap<Chord, Node>,
+}
+
+/// What the keys typed so far amount to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Action(Action),
+    /// The start of a longer binding, waiting for the next key
+    Prefix,
+    Unbound,
+}
+
 /// Runtime keybindings manager
 pub struct Keybindings {
-    /// Direct key -> action mappings (single key press)
-    single_key: HashMap<(String, bool, bool, bool), Action>,
-    /// Two-key sequences like "gg", "dd", "za"
-    sequences: HashMap<(String, String), Action>,
+    keymaps: HashMap<Mode, Node>,
+    timeout_ms: u64,
+    /// What's wrong with the bindings; bad ones are left out
+    problems: Vec<String>,
 }
 
 impl Keybindings {
-    pub fn load() -> Self {
-        let config = Self::load_config();
-        Self::from_config(&config)
+    pub const FILE: &'static str = "keybindings.json";
+
+    /// Read `keybindings.json`, creating it with the defaults if it's missing.
+    /// Fails only if the file isn't valid JSON; bad bindings are left out and
+    /// listed in `problems`.
+    pub fn try_load() -> Result<Self, String> {
+        let path = Self::config_path();
+        let (raw, content) = if path.exists() {
+            let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", Self::FILE, e))?;
+            let raw: RawConfig = serde_json::from_str(&content).map_err(|e| format!("{}: {}", Self::FILE, e))?;
+            (raw, content)
+        } else {
+            // Create default config
+            if let Ok(json) = serde_json::to_string_pretty(&Self::default_config()) {
+                fs::write(&path, json).ok();
+            }
+            let raw = RawConfig { leader: default_leader(), timeout_ms: default_timeout_ms(), modes: HashMap::new() };
+            (raw, String::new())
+        };
+
+        let mut problems = Vec::new();
+        let leader = parse_keys(&raw.leader, &[]).unwrap_or_else(|err| {
+            problems.push(problem("'leader'", line_of_key(&content, "leader", 0), &err));
+            Self::default_leader()
+        });
+        let entries = Self::load_entries(raw.modes, &content, &mut problems);
+        Ok(Self::from_entries(&entries, &leader, raw.timeout_ms, problems))
+    }
+
+    /// Problems in `keybindings.json` as it is on disk, for `:checkbindings`
+    pub fn check() -> Vec<String> {
+        match Self::try_load() {
+            Ok(keybindings) => keybindings.problems,
+            Err(err) => vec![err],
+        }
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
     }
 
     fn config_path() -> PathBuf {
-        let config_dir = dirs::config_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("zap");
-        fs::create_dir_all(&config_dir).ok();
-        config_dir.join("keybindings.json")
+        paths::config_file(Self::FILE)
     }
 
-    fn load_config() -> KeybindingsConfig {
-        let path = Self::config_path();
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(config) = serde_json::from_str(&content) {
-                    return config;
+    fn default_leader() -> Vec<Chord> {
+        parse_keys(&default_leader(), &[]).expect("default leader is a valid key")
+    }
+
+    /// The bindings in file order, followed by the defaults the file doesn't
+    /// have. A name given twice in a mode isn't noticed: the JSON parser keeps
+    /// the last binding, but problems with it are reported at the first line.
+    fn load_entries(modes: HashMap<String, serde_json::Value>, content: &str, problems: &mut Vec<String>) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut named = HashSet::new();
+        for (mode_name, bindings) in modes {
+            let mode_line = line_of_key(content, &mode_name, 0);
+            let mode = match mode_name.as_str() {
+                "bindings" => Some(Mode::List),
+                name => Mode::parse(name),
+            };
+            let (mode, bindings) = match (mode, bindings) {
+                (Some(mode), serde_json::Value::Object(bindings)) => (mode, bindings),
+                (Some(_), _) => {
+                    problems.push(problem(&format!("'{}'", mode_name), mode_line, "isn't an object of bindings"));
+                    continue;
+                }
+                (None, _) => {
+                    problems.push(problem(&format!("'{}'", mode_name), mode_line, "isn't a mode"));
+                    continue;
+                }
+            };
+            // Names repeat across modes, so look for each after its mode's key
+            let start = content.lines().take(mode_line.unwrap_or(1) - 1).map(|l| l.len() + 1).sum();
+            for (name, value) in bindings {
+                let line = line_of_key(content, &name, start);
+                named.insert((mode, name.clone()));
+                let action = value.get("action").cloned();
+                match serde_json::from_value::<KeyBinding>(value) {
+                    Ok(binding) => entries.push(Entry { mode, name, binding, line }),
+                    Err(err) => {
+                        let message = match action {
+                            Some(action) if serde_json::from_value::<Action>(action.clone()).is_err() => {
+                                format!("has unknown action {}", action)
+                            }
+                            _ => err.to_string(),
+                        };
+                        problems.push(problem(&format!("{} '{}'", mode, name), line, &message));
+                    }
                 }
             }
         }
+        entries.sort_by_key(|entry| entry.line);
+
+        // Bindings added in newer versions are missing from older config files
+        entries.extend(Self::default_entries(&named));
+        entries
+    }
 
-        // Create default config
-        let config = Self::default_config();
-        if let Ok(json) = serde_json::to_string_pretty(&config) {
-            fs::write(&path, json).ok();
+    /// The default bindings not named in `except`, by mode and name
+    fn default_entries(except: &HashSet<(Mode, String)>) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for (mode, bindings) in Self::default_config().modes {
+            let mut defaults: Vec<Entry> = bindings
+                .into_iter()
+                .filter(|(name, _)| !except.contains(&(mode, name.clone())))
+                .map(|(name, binding)| Entry { mode, name, binding, line: None })
+                .collect();
+            defaults.sort_by(|a, b| a.name.cmp(&b.name));
+            entries.extend(defaults);
         }
-        config
+        entries
     }
 
     fn default_config() -> KeybindingsConfig {
+        let mut modes = BTreeMap::new();
         let mut bindings = HashMap::new();
-
         // Navigation
-        bindings.insert("move_down".to_string(), KeyBinding {
-            key: "j".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::MoveDown,
-            pending: None,
-        });
-        bindings.insert("move_up".to_string(), KeyBinding {
-            key: "k".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::MoveUp,
-            pending: None,
-        });
-        bindings.insert("jump_to_first".to_string(), KeyBinding {
-            key: "g".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::JumpToFirst,
-            pending: Some("g".to_string()),
-        });
-        bindings.insert("jump_to_last".to_string(), KeyBinding {
-            key: "G".to_string(),
-            shift: true, ctrl: false, alt: false,
-            action: Action::JumpToLast,
-            pending: None,
-        });
+        bindings.insert("move_down".to_string(), KeyBinding::new("j", Action::MoveDown));
+        bindings.insert("move_up".to_string(), KeyBinding::new("k", Action::MoveUp));
+        bindings.insert("jump_to_first".to_string(), KeyBinding::new("gg", Action::JumpToFirst));
+        bindings.insert("jump_to_last".to_string(), KeyBinding::new("G", Action::JumpToLast));
 
         // Task operations
-        bindings.insert("toggle_complete".to_string(), KeyBinding {
-            key: "Return".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::ToggleComplete,
-            pending: None,
-        });
-        bindings.insert("abandon".to_string(), KeyBinding {
-            key: "Return".to_string(),
-            shift: false, ctrl: false, alt: true,
-            action: Action::Abandon,
-            pending: None,
-        });
-        bindings.insert("delete".to_string(), KeyBinding {
-            key: "d".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::Delete,
-            pending: Some("d".to_string()),
-        });
-        bindings.insert("move_task_down".to_string(), KeyBinding {
-            key: "J".to_string(),
-            shift: true, ctrl: false, alt: false,
-            action: Action::MoveTaskDown,
-            pending: None,
-        });
-        bindings.insert("move_task_up".to_string(), KeyBinding {
-            key: "K".to_string(),
-            shift: true, ctrl: false, alt: false,
-            action: Action::MoveTaskUp,
-            pending: None,
-        });
-        bindings.insert("toggle_fold".to_string(), KeyBinding {
-            key: "a".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::ToggleFold,
-            pending: Some("z".to_string()),
-        });
+        bindings.insert("toggle_complete".to_string(), KeyBinding::new("<CR>", Action::ToggleComplete));
+        bindings.insert("abandon".to_string(), KeyBinding::new("<A-CR>", Action::Abandon));
+        bindings.insert("cycle_status".to_string(), KeyBinding::new("s", Action::CycleStatus));
+        bindings.insert("delete".to_string(), KeyBinding::new("dd", Action::Delete));
+        bindings.insert("move_task_down".to_string(), KeyBinding::new("J", Action::MoveTaskDown));
+        bindings.insert("move_task_up".to_string(), KeyBinding::new("K", Action::MoveTaskUp));
+        bindings.insert("toggle_fold".to_string(), KeyBinding::new("za", Action::ToggleFold));
+        bindings.insert("hoist_in".to_string(), KeyBinding::new("zi", Action::HoistIn));
+        bindings.insert("hoist_out".to_string(), KeyBinding::new("zo", Action::HoistOut));
+
+        // Scheduling
+        for (name, binding) in Self::scheduling_bindings() {
+            bindings.insert(name, binding);
+        }
+
+        // Time tracking
+        bindings.insert("toggle_timer".to_string(), KeyBinding::new("ts", Action::ToggleTimer));
 
         // Insert modes
-        bindings.insert("insert".to_string(), KeyBinding {
-            key: "i".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::Insert,
-            pending: None,
-        });
-        bindings.insert("insert_subtask".to_string(), KeyBinding {
-            key: "Return".to_string(),
-            shift: true, ctrl: false, alt: false,
-            action: Action::InsertSubtask,
-            pending: None,
-        });
-        bindings.insert("edit".to_string(), KeyBinding {
-            key: "e".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::Edit,
-            pending: None,
-        });
+        bindings.insert("insert".to_string(), KeyBinding::new("i", Action::Insert));
+        bindings.insert("insert_subtask".to_string(), KeyBinding::new("<S-CR>", Action::InsertSubtask));
+        bindings.insert("edit".to_string(), KeyBinding::new("e", Action::Edit));
 
         // Command mode
-        bindings.insert("command_mode".to_string(), KeyBinding {
-            key: "colon".to_string(),
-            shift: true, ctrl: false, alt: false,
-            action: Action::CommandMode,
-            pending: None,
-        });
+        bindings.insert("command_mode".to_string(), KeyBinding::new(":", Action::CommandMode));
 
         // Cancel
-        bindings.insert("cancel".to_string(), KeyBinding {
-            key: "Escape".to_string(),
-            shift: false, ctrl: false, alt: false,
-            action: Action::Cancel,
-            pending: None,
-        });
+        bindings.insert("cancel".to_string(), KeyBinding::new("<Esc>", Action::Cancel));
+        modes.insert(Mode::List, bindings);
 
-        KeybindingsConfig { bindings }
-    }
-
-    fn from_config(config: &KeybindingsConfig) -> Self {
-        let mut single_key = HashMap::new();
-        let mut sequences = HashMap::new();
-
-        for binding in config.bindings.values() {
-            if let Some(ref pending) = binding.pending {
-                // This is a sequence like "gg", "dd", "za"
-                sequences.insert(
-                    (pending.clone(), binding.key.clone()),
-                    binding.action,
-                );
-            } else {
-                // Single key binding
-                single_key.insert(
-                    (binding.key.clone(), binding.shift, binding.ctrl, binding.alt),
-                    binding.action,
-                );
+        let mut bindings = HashMap::new();
+        // Moving around
+        bindings.insert("prev_day".to_string(), KeyBinding::new("h", Action::PrevDay));
+        bindings.insert("prev_day_arrow".to_string(), KeyBinding::new("<Left>", Action::PrevDay));
+        bindings.insert("next_day".to_string(), KeyBinding::new("l", Action::NextDay));
+        bindings.insert("next_day_arrow".to_string(), KeyBinding::new("<Right>", Action::NextDay));
+        bindings.insert("prev_week".to_string(), KeyBinding::new("k", Action::PrevWeek));
+        bindings.insert("prev_week_arrow".to_string(), KeyBinding::new("<Up>", Action::PrevWeek));
+        bindings.insert("next_week".to_string(), KeyBinding::new("j", Action::NextWeek));
+        bindings.insert("next_week_arrow".to_string(), KeyBinding::new("<Down>", Action::NextWeek));
+        bindings.insert("prev_month".to_string(), KeyBinding::new("<", Action::PrevMonth));
+        bindings.insert("prev_month_arrow".to_string(), KeyBinding::new("<C-Left>", Action::PrevMonth));
+        bindings.insert("next_month".to_string(), KeyBinding::new(">", Action::NextMonth));
+        bindings.insert("next_month_arrow".to_string(), KeyBinding::new("<C-Right>", Action::NextMonth));
+        bindings.insert("next_day_task".to_string(), KeyBinding::new("n", Action::NextDayTask));
+        bindings.insert("prev_day_task".to_string(), KeyBinding::new("N", Action::PrevDayTask));
+
+        // Tasks on the selected day
+        bindings.insert("insert".to_string(), KeyBinding::new("i", Action::Insert));
+        bindings.insert("command_mode".to_string(), KeyBinding::new(":", Action::CommandMode));
+        for (name, binding) in Self::scheduling_bindings() {
+            bindings.insert(name, binding);
+        }
+        modes.insert(Mode::Calendar, bindings);
+
+        let mut bindings = HashMap::new();
+        // Moving around, with `<` and `>` for weeks and j/k for the tasks of the day
+        bindings.insert("prev_day".to_string(), KeyBinding::new("h", Action::PrevDay));
+        bindings.insert("prev_day_arrow".to_string(), KeyBinding::new("<Left>", Action::PrevDay));
+        bindings.insert("next_day".to_string(), KeyBinding::new("l", Action::NextDay));
+        bindings.insert("next_day_arrow".to_string(), KeyBinding::new("<Right>", Action::NextDay));
+        bindings.insert("prev_week".to_string(), KeyBinding::new("<", Action::PrevWeek));
+        bindings.insert("next_week".to_string(), KeyBinding::new(">", Action::NextWeek));
+        bindings.insert("next_day_task".to_string(), KeyBinding::new("j", Action::NextDayTask));
+        bindings.insert("next_day_task_arrow".to_string(), KeyBinding::new("<Down>", Action::NextDayTask));
+        bindings.insert("prev_day_task".to_string(), KeyBinding::new("k", Action::PrevDayTask));
+        bindings.insert("prev_day_task_arrow".to_string(), KeyBinding::new("<Up>", Action::PrevDayTask));
+
+        // Tasks on the selected day; H/L move the task a day
+        bindings.insert("insert".to_string(), KeyBinding::new("i", Action::Insert));
+        bindings.insert("command_mode".to_string(), KeyBinding::new(":", Action::CommandMode));
+        bindings.insert("move_task_prev_day".to_string(), KeyBinding::new("H", Action::DateBack));
+        bindings.insert("move_task_next_day".to_string(), KeyBinding::new("L", Action::DateForward));
+        for (name, binding) in Self::scheduling_bindings() {
+            bindings.insert(name, binding);
+        }
+        modes.insert(Mode::Week, bindings);
+
+        let mut bindings = HashMap::new();
+        bindings.insert("prev_column".to_string(), KeyBinding::new("h", Action::PrevColumn));
+        bindings.insert("prev_column_arrow".to_string(), KeyBinding::new("<Left>", Action::PrevColumn));
+        bindings.insert("next_column".to_string(), KeyBinding::new("l", Action::NextColumn));
+        bindings.insert("next_column_arrow".to_string(), KeyBinding::new("<Right>", Action::NextColumn));
+        bindings.insert("move_down".to_string(), KeyBinding::new("j", Action::MoveDown));
+        bindings.insert("move_down_arrow".to_string(), KeyBinding::new("<Down>", Action::MoveDown));
+        bindings.insert("move_up".to_string(), KeyBinding::new("k", Action::MoveUp));
+        bindings.insert("move_up_arrow".to_string(), KeyBinding::new("<Up>", Action::MoveUp));
+        bindings.insert("move_card_prev".to_string(), KeyBinding::new("H", Action::MoveCardPrev));
+        bindings.insert("move_card_next".to_string(), KeyBinding::new("L", Action::MoveCardNext));
+        bindings.insert("command_mode".to_string(), KeyBinding::new(":", Action::CommandMode));
+        modes.insert(Mode::Board, bindings);
+
+        let mut bindings = HashMap::new();
+        bindings.insert("move_down".to_string(), KeyBinding::new("j", Action::MoveDown));
+        bindings.insert("move_down_arrow".to_string(), KeyBinding::new("<Down>", Action::MoveDown));
+        bindings.insert("move_up".to_string(), KeyBinding::new("k", Action::MoveUp));
+        bindings.insert("move_up_arrow".to_string(), KeyBinding::new("<Up>", Action::MoveUp));
+        bindings.insert("restore".to_string(), KeyBinding::new("r", Action::Restore));
+        bindings.insert("restore_enter".to_string(), KeyBinding::new("<CR>", Action::Restore));
+        bindings.insert("command_mode".to_string(), KeyBinding::new(":", Action::CommandMode));
+        modes.insert(Mode::Trash, bindings);
+
+        let mut bindings = HashMap::new();
+        bindings.insert("cancel".to_string(), KeyBinding::new("<Esc>", Action::Cancel));
+        modes.insert(Mode::Insert, bindings);
+
+        let mut bindings = HashMap::new();
+        bindings.insert("cancel".to_string(), KeyBinding::new("<Esc>", Action::Cancel));
+        bindings.insert("complete".to_string(), KeyBinding::new("<Tab>", Action::Complete));
+        bindings.insert("history_prev".to_string(), KeyBinding::new("<C-p>", Action::HistoryPrev));
+        bindings.insert("history_prev_arrow".to_string(), KeyBinding::new("<Up>", Action::HistoryPrev));
+        bindings.insert("history_next".to_string(), KeyBinding::new("<C-n>", Action::HistoryNext));
+        bindings.insert("history_next_arrow".to_string(), KeyBinding::new("<Down>", Action::HistoryNext));
+        modes.insert(Mode::Command, bindings);
+
+        KeybindingsConfig { leader: default_leader(), timeout_ms: default_timeout_ms(), modes }
+    }
+
+    /// Rescheduling keys, shared by the list, calendar and week views
+    fn scheduling_bindings() -> [(String, KeyBinding); 4] {
+        [
+            ("date_forward".to_string(), KeyBinding::new("+", Action::DateForward)),
+            ("date_back".to_string(), KeyBinding::new("-", Action::DateBack)),
+            ("set_date".to_string(), KeyBinding::new("ds", Action::SetDate)),
+            ("clear_date".to_string(), KeyBinding::new("dx", Action::ClearDate)),
+        ]
+    }
+
+    /// Build a trie for each mode, the first binding of a key sequence winning.
+    /// Without a timeout, keys that start a longer binding never run theirs.
+    fn from_entries(entries: &[Entry], leader: &[Chord], timeout_ms: u64, mut problems: Vec<String>) -> Self {
+        let mut keymaps: HashMap<Mode, Node> = HashMap::new();
+        let mut taken: HashMap<(Mode, Vec<Chord>), &Entry> = HashMap::new();
+        let mut bound: Vec<(&Entry, Vec<Chord>)> = Vec::new();
+
+        for entry in entries {
+            if !entry.mode.allows(entry.binding.action) {
+                let action = serde_json::to_value(entry.binding.action).unwrap_or_default();
+                problems.push(entry.problem(&format!("has action {}, which does nothing in {} mode", action, entry.mode)));
+                continue;
+            }
+            let keys = match entry.binding.chords(leader) {
+                Ok(keys) => keys,
+                Err(err) => {
+                    problems.push(entry.problem(&err));
+                    continue;
+                }
+            };
+            if entry.mode.is_typing() && (keys.len() > 1 || keys[0].types_text()) {
+                let message = format!("uses {}, but only single keys that don't type text work in {} mode", show_keys(&keys), entry.mode);
+                problems.push(entry.problem(&message));
+                continue;
+            }
+            if let Some(first) = taken.get(&(entry.mode, keys.clone())) {
+                problems.push(entry.problem(&format!("uses {}, already bound by {}", show_keys(&keys), first.at())));
+                continue;
+            }
+            let root = keymaps.entry(entry.mode).or_default();
+            let node = keys.iter().fold(root, |node, chord| node.next.entry(chord.clone()).or_default());
+            node.action = Some(entry.binding.action);
+            taken.insert((entry.mode, keys.clone()), entry);
+            bound.push((entry, keys));
+        }
+
+        if timeout_ms == 0 {
+            for (entry, keys) in &bound {
+                let longer = bound
+                    .iter()
+                    .find(|(other, other_keys)| other.mode == entry.mode && other_keys.len() > keys.len() && other_keys.starts_with(keys));
+                if let Some((longer, _)) = longer {
+                    let message = format!("can't be reached: {} starts the sequence of {}", show_keys(keys), longer.at());
+                    problems.push(entry.problem(&message));
+                }
             }
         }
 
-        Self { single_key, sequences }
+        Self { keymaps, timeout_ms, problems }
     }
 
-    /// Get action for a single key press
-    pub fn get_action(&self, key: &gdk::Key, shift: bool, ctrl: bool, alt: bool) -> Option<Action> {
-        let key_name = key_to_string(key);
-        self.single_key.get(&(key_name, shift, ctrl, alt)).copied()
+    fn node(&self, mode: Mode, keys: &[Chord]) -> Option<&Node> {
+        keys.iter().try_fold(self.keymaps.get(&mode)?, |node, chord| node.next.get(chord))
     }
 
-    /// Get action for a two-key sequence
-    pub fn get_sequence_action(&self, pending: &str, key: &gdk::Key) -> Option<Action> {
-        let key_name = key_to_string(key);
-        self.sequences.get(&(pending.to_string(), key_name)).copied()
+    /// Look up the keys typed so far. Keys that start a longer binding wait
+    /// for more, even if they're bound themselves.
+    pub fn lookup(&self, mode: Mode, keys: &[Chord]) -> Lookup {
+        match self.node(mode, keys) {
+            Some(node) if !node.next.is_empty() => Lookup::Prefix,
+            Some(Node { action: Some(action), .. }) => Lookup::Action(*action),
+            _ => Lookup::Unbound,
+        }
+    }
+
+    /// The binding of exactly these keys, e.g. once waiting for more timed out
+    pub fn action(&self, mode: Mode, keys: &[Chord]) -> Option<Action> {
+        self.node(mode, keys)?.action
     }
 
-    /// Check if a key starts a sequence
-    pub fn is_sequence_start(&self, key: &gdk::Key) -> Option<String> {
-        let key_name = key_to_string(key);
-        for (pending, _) in self.sequences.keys() {
-            if pending == &key_name {
-                return Some(key_name);
+    /// Add a key to those typed so far, which are cleared unless they're the
+    /// start of a longer binding. A key that doesn't continue any binding
+    /// starts over on its own.
+    pub fn feed(&self, mode: Mode, keys: &mut Vec<Chord>, chord: Chord) -> Lookup {
+        keys.push(chord);
+        match self.lookup(mode, keys) {
+            Lookup::Prefix => Lookup::Prefix,
+            Lookup::Unbound if keys.len() > 1 => {
+                let chord = keys.pop().expect("just pushed");
+                keys.clear();
+                self.feed(mode, keys, chord)
+            }
+            found => {
+                keys.clear();
+                found
             }
         }
-        None
+    }
+
+    /// How long to wait for the next key of a sequence, None for no limit
+    pub fn timeout(&self) -> Option<std::time::Duration> {
+        (self.timeout_ms > 0).then(|| std::time::Duration::from_millis(self.timeout_ms))
     }
 }
 
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self::from_entries(&Self::default_entries(&HashSet::new()), &Self::default_leader(), default_timeout_ms(), Vec::new())
+    }
+}
+
+/// Line of the first `"name":` from byte `from` of a JSON document
+fn line_of_key(content: &str, name: &str, from: usize) -> Option<usize> {
+    let quoted = format!("\"{}\"", name);
+    content[from..].match_indices(&quoted).find_map(|(start, _)| {
+        let start = from + start;
+        let rest = content[start + quoted.len()..].trim_start();
+        rest.starts_with(':').then(|| content[..start].matches('\n').count() + 1)
+    })
+}
+
+/// Whether the key is a modifier on its own, like the Shift of Shift+g,
+/// which shouldn't break a sequence
+pub fn is_modifier_key(key: &gdk::Key) -> bool {
+    key.name().is_some_and(|name| {
+        ["Shift_", "Control_", "Alt_", "Meta_", "Super_", "Hyper_", "ISO_Level"].iter().any(|p| name.starts_with(p))
+            || name == "Caps_Lock"
+    })
+}
+
 /// Convert a GDK key to a string name
 fn key_to_string(key: &gdk::Key) -> String {
     match *key {
@@ -259,3 +906,134 @@ fn key_to_string(key: &gdk::Key) -> String {
         _ => key.name().map(|s| s.to_string()).unwrap_or_default(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(key: &str) -> Chord {
+        Chord::new(key.to_string(), false, false, false)
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let leader = parse_keys("\\", &[]).unwrap();
+        assert_eq!(leader, vec![chord("backslash")]);
+        assert_eq!(parse_keys("<leader>ct", &leader).unwrap(), vec![chord("backslash"), chord("c"), chord("t")]);
+        assert_eq!(parse_keys("gg", &leader).unwrap(), vec![chord("g"), chord("g")]);
+
+        // The key itself may be "-"
+        let ctrl_minus = Chord::new("minus".to_string(), false, true, false);
+        assert_eq!(parse_keys("<C-->", &leader).unwrap(), vec![ctrl_minus]);
+        assert_eq!(parse_keys("<lt>", &leader).unwrap(), vec![chord("less")]);
+        assert_eq!(parse_keys("<S-CR>", &leader).unwrap(), vec![Chord::new("Return".to_string(), true, false, false)]);
+
+        // A "<" that isn't closed, or holds nothing, is just the key
+        assert_eq!(parse_keys("a<b", &leader).unwrap(), vec![chord("a"), chord("less"), chord("b")]);
+        assert_eq!(parse_keys("<>", &leader).unwrap(), vec![chord("less"), chord("greater")]);
+
+        assert_eq!(parse_keys("", &leader), Err("has no keys".to_string()));
+        assert_eq!(parse_keys("<Foo>", &leader), Err("has unknown key '<Foo>'".to_string()));
+        assert_eq!(parse_keys("<X-a>", &leader), Err("has unknown key '<X-a>'".to_string()));
+        assert_eq!(show_keys(&parse_keys("<C-d>gg<lt><Space>", &leader).unwrap()), "<C-d>gg<lt><Space>");
+    }
+
+    #[test]
+    fn test_chord_shift() {
+        // Shift picks the key of letters and punctuation, and only stays for
+        // keys like Return
+        assert_eq!(Chord::new("g".to_string(), true, false, false), chord("G"));
+        assert_eq!(Chord::new("colon".to_string(), true, false, false), chord("colon"));
+        let shift_return = Chord::new("Return".to_string(), true, false, false);
+        assert!(shift_return.shift);
+        assert_eq!(shift_return.to_string(), "<S-CR>");
+        assert_eq!(parse_keys("<S-g>", &[]).unwrap(), vec![chord("G")]);
+    }
+
+    #[test]
+    fn test_feed_and_lookup() {
+        let keybindings = Keybindings::default();
+        let mut keys = Vec::new();
+        assert_eq!(keybindings.feed(Mode::List, &mut keys, chord("g")), Lookup::Prefix);
+        assert_eq!(keys, vec![chord("g")]);
+        assert_eq!(keybindings.feed(Mode::List, &mut keys, chord("g")), Lookup::Action(Action::JumpToFirst));
+        assert!(keys.is_empty());
+
+        // A key that doesn't continue the binding starts over on its own
+        keybindings.feed(Mode::List, &mut keys, chord("g"));
+        assert_eq!(keybindings.feed(Mode::List, &mut keys, chord("j")), Lookup::Action(Action::MoveDown));
+        assert!(keys.is_empty());
+        keybindings.feed(Mode::List, &mut keys, chord("d"));
+        assert_eq!(keybindings.feed(Mode::List, &mut keys, chord("d")), Lookup::Action(Action::Delete));
+        assert_eq!(keybindings.feed(Mode::List, &mut keys, chord("q")), Lookup::Unbound);
+        assert!(keys.is_empty());
+
+        // Keys that are bound and start a longer binding wait for more
+        let binding = KeyBinding::new("t", Action::ToggleComplete);
+        let mut entries = vec![Entry { mode: Mode::List, name: "complete".to_string(), binding, line: Some(1) }];
+        entries.extend(Keybindings::default_entries(&HashSet::new()));
+        let keybindings = Keybindings::from_entries(&entries, &Keybindings::default_leader(), 1000, Vec::new());
+        assert!(keybindings.problems().is_empty());
+        assert_eq!(keybindings.lookup(Mode::List, &[chord("t")]), Lookup::Prefix);
+        assert_eq!(keybindings.action(Mode::List, &[chord("t")]), Some(Action::ToggleComplete));
+        assert_eq!(keybindings.lookup(Mode::List, &[chord("t"), chord("s")]), Lookup::Action(Action::ToggleTimer));
+
+        // Waiting forever, they never run
+        let keybindings = Keybindings::from_entries(&entries, &Keybindings::default_leader(), 0, Vec::new());
+        assert_eq!(keybindings.timeout(), None);
+        assert_eq!(
+            keybindings.problems(),
+            ["keybindings.json line 1: list 'complete' can't be reached: t starts the sequence of built-in list 'toggle_timer'"]
+        );
+    }
+
+    #[test]
+    fn test_problems_and_lines() {
+        let content = r#"{
+  "list": {
+    "move_down": { "keys": "j", "action": "move_down" },
+    "down_again": { "keys": "j", "action": "move_up" },
+    "jump": { "keys": "<Foo>", "action": "jump_to_first" },
+    "fly": { "keys": "f", "action": "fly" }
+  },
+  "calendar": {
+    "move_down": { "keys": "x", "action": "move_down" }
+  },
+  "sideways": {}
+}"#;
+        assert_eq!(line_of_key(content, "move_down", 0), Some(3));
+        // Names repeat across modes, and values aren't keys
+        let calendar = content.find("\"calendar\"").unwrap();
+        assert_eq!(line_of_key(content, "move_down", calendar), Some(9));
+        assert_eq!(line_of_key(content, "fly", 0), Some(6));
+        assert_eq!(line_of_key(content, "jump_to_first", 0), None);
+
+        let raw: RawConfig = serde_json::from_str(content).unwrap();
+        let mut problems = Vec::new();
+        let entries = Keybindings::load_entries(raw.modes, content, &mut problems);
+        problems.sort();
+        assert_eq!(
+            problems,
+            vec![
+                "keybindings.json line 11: 'sideways' isn't a mode".to_string(),
+                "keybindings.json line 6: list 'fly' has unknown action \"fly\"".to_string(),
+            ]
+        );
+        let lines: Vec<Option<usize>> = entries.iter().take_while(|e| e.line.is_some()).map(|e| e.line).collect();
+        assert_eq!(lines, vec![Some(3), Some(4), Some(5), Some(9)]);
+
+        let keybindings = Keybindings::from_entries(&entries, &Keybindings::default_leader(), 1000, Vec::new());
+        assert_eq!(
+            keybindings.problems(),
+            [
+                "keybindings.json line 4: list 'down_again' uses j, already bound by list 'move_down' (line 3)",
+                "keybindings.json line 5: list 'jump' has unknown key '<Foo>'",
+                "keybindings.json line 9: calendar 'move_down' has action \"move_down\", which does nothing in calendar mode",
+            ]
+        );
+        // The first binding of the keys wins, and defaults fill in the rest
+        assert_eq!(keybindings.lookup(Mode::List, &[chord("j")]), Lookup::Action(Action::MoveDown));
+        assert_eq!(keybindings.lookup(Mode::List, &[chord("k")]), Lookup::Action(Action::MoveUp));
+        assert_eq!(keybindings.lookup(Mode::Calendar, &[chord("x")]), Lookup::Unbound);
+    }
+}
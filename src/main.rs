@@ -1,22 +1,86 @@
+mod archive;
 mod colors;
+mod crypto;
 mod date_parser;
+mod focus;
+mod history;
 mod keybindings;
+mod merge;
+mod paths;
+mod schema;
+mod settings;
+mod storage;
 mod todo;
+mod trash;
 mod ui;
 
 use gtk4::prelude::*;
-use gtk4::Application;
+use gtk4::{gio, Application};
+use std::path::{Path, PathBuf};
+use storage::with_storage;
 use ui::ZapWindow;
 
 fn main() {
+    // Our own flags are stripped before GTK sees the arguments
+    let mut args: Vec<String> = std::env::args().collect();
+    let data_dir = paths::take_flag(&mut args, "--data-dir");
+    let config_dir = paths::take_flag(&mut args, "--config-dir");
+    let profile = paths::take_flag(&mut args, "--profile");
+    paths::init(data_dir.map(PathBuf::from), config_dir.map(PathBuf::from), profile.as_deref());
+
+    // Git merge driver for cluster files: zap --merge-driver BASE LOCAL REMOTE
+    if args.len() == 5 && args[1] == "--merge-driver" {
+        let merged = merge::merge_files(Path::new(&args[2]), Path::new(&args[3]), Path::new(&args[4]));
+        std::process::exit(if merged { 0 } else { 1 });
+    }
+    if args.len() == 2 && args[1] == "doctor" {
+        std::process::exit(doctor());
+    }
+    let restore = !args.iter().any(|a| a == "--no-restore");
+    let gtk_args: Vec<String> = args.into_iter().filter(|a| a != "--no-restore").collect();
+
+    let mut app_flags = gio::ApplicationFlags::empty();
+    if paths::is_custom() {
+        // A second window on other directories must not join the running instance
+        app_flags |= gio::ApplicationFlags::NON_UNIQUE;
+    }
     let app = Application::builder()
         .application_id("com.zap.todo")
+        .flags(app_flags)
         .build();
 
-    app.connect_activate(|app| {
-        let zap = ZapWindow::new(app);
+    app.connect_activate(move |app| {
+        let zap = ZapWindow::new(app, restore);
         zap.window.present();
     });
 
-    app.run();
+    app.run_with_args(&gtk_args);
+}
+
+/// Check that every cluster loads and is consistent: zap doctor
+fn doctor() -> i32 {
+    let mut problems = 0;
+    let clusters = with_storage(|storage| storage.list_clusters());
+    for cluster in &clusters {
+        let issues = match with_storage(|storage| storage.load(cluster)) {
+            Ok(todos) => schema::validate(&todos.unwrap_or_default()),
+            Err(schema::LoadError::Locked) => {
+                println!("locked  {} (encrypted, not checked)", cluster);
+                continue;
+            }
+            Err(err) => vec![err.to_string()],
+        };
+        if issues.is_empty() {
+            println!("ok      {}", cluster);
+        }
+        for issue in &issues {
+            println!("ERROR   {}: {}", cluster, issue);
+        }
+        problems += issues.len();
+    }
+    for (cluster, path) in merge::find_conflict_files() {
+        println!("WARNING {}: unresolved sync conflict copy {}", cluster, path.display());
+    }
+    println!("{} cluster(s) checked, {} problem(s)", clusters.len(), problems);
+    if problems == 0 { 0 } else { 1 }
 }
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::todo::{Todo, TodoList};
+
+/// Marker in the names of the conflict copies Syncthing writes,
+/// e.g. `work.sync-conflict-20240101-123456-ABCDEFG.json`
+pub const SYNC_CONFLICT_MARKER: &str = ".sync-conflict-";
+
+/// A change both sides made differently. The local version is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Task the conflict is about (empty for the top level)
+    pub id: String,
+    pub text: String,
+    pub field: &'static str,
+}
+
+pub struct MergeResult {
+    pub todos: Vec<Todo>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A task without its subtasks, and the id of its parent
+#[derive(Clone)]
+struct Entry {
+    todo: Todo,
+    parent: Option<String>,
+}
+
+/// One version of a cluster, indexed by task id
+struct Index {
+    entries: HashMap<String, Entry>,
+    /// Child ids of each parent in order (None is the top level)
+    children: HashMap<Option<String>, Vec<String>>,
+    /// All ids in tree order
+    order: Vec<String>,
+}
+
+impl Index {
+    fn new(todos: &[Todo]) -> Self {
+        fn walk(todos: &[Todo], parent: Option<String>, index: &mut Index) {
+            for todo in todos {
+                let mut entry = Entry { todo: todo.clone(), parent: parent.clone() };
+                entry.todo.subtasks.clear();
+                index.entries.insert(todo.id.clone(), entry);
+                index.children.entry(parent.clone()).or_default().push(todo.id.clone());
+                index.order.push(todo.id.clone());
+                walk(&todo.subtasks, Some(todo.id.clone()), index);
+            }
+        }
+        let mut index = Index { entries: HashMap::new(), children: HashMap::new(), order: Vec::new() };
+        walk(todos, None, &mut index);
+        index
+    }
+
+    fn children_of(&self, parent: &Option<String>) -> &[String] {
+        self.children.get(parent).map_or(&[], |c| c.as_slice())
+    }
+}
+
+/// Field-by-field three-way merge of one task, recording conflicts
+struct FieldMerge<'a> {
+    id: &'a str,
+    text: &'a str,
+    conflicts: &'a mut Vec<Conflict>,
+}
+
+impl FieldMerge<'_> {
+    /// Take the side that changed the value; if both did, keep the local value
+    fn merge<T: PartialEq + Clone>(&mut self, field: &'static str, base: Option<&T>, local: &T, remote: &T) -> T {
+        if local == remote || base == Some(remote) {
+            local.clone()
+        } else if base == Some(local) {
+            remote.clone()
+        } else {
+            self.conflicts.push(Conflict { id: self.id.to_string(), text: self.text.to_string(), field });
+            local.clone()
+        }
+    }
+}
+
+/// Merge two versions of a cluster that both started from `base`, matching
+/// tasks by id. Changes made on only one side are taken; changes made on both
+/// sides are conflicts, for which the local version wins.
+pub fn merge(base: &[Todo], local: &[Todo], remote: &[Todo]) -> MergeResult {
+    let (base, local, remote) = (Index::new(base), Index::new(local), Index::new(remote));
+    let mut conflicts = Vec::new();
+
+    // Decide which tasks survive, with their merged fields and parent
+    let ids = local.order.iter().chain(remote.order.iter().filter(|id| !local.entries.contains_key(*id)));
+    let mut merged: HashMap<String, Entry> = HashMap::new();
+    let mut merged_order = Vec::new();
+    for id in ids {
+        let entry = match (base.entries.get(id), local.entries.get(id), remote.entries.get(id)) {
+            (b, Some(l), Some(r)) => Some(merge_entry(b, l, r, &mut conflicts)),
+            // Deleted on one side: fine unless the other side changed the task
+            (Some(b), Some(kept), None) | (Some(b), None, Some(kept)) => {
+                if kept.todo == b.todo && kept.parent == b.parent {
+                    None
+                } else {
+                    conflicts.push(Conflict { id: id.clone(), text: kept.todo.text.clone(), field: "deleted" });
+                    Some(kept.clone())
+                }
+            }
+            // Added on one side
+            (None, Some(added), None) | (None, None, Some(added)) => Some(added.clone()),
+            (_, None, None) => None,
+        };
+        if let Some(entry) = entry {
+            merged_order.push(id.clone());
+            merged.insert(id.clone(), entry);
+        }
+    }
+
+    // Tasks whose parent is gone move to the top level
+    let orphans: Vec<String> = merged_order
+        .iter()
+        .filter(|id| merged[*id].parent.as_ref().is_some_and(|p| !merged.contains_key(p)))
+        .cloned()
+        .collect();
+    for id in orphans {
+        let entry = merged.get_mut(&id).unwrap();
+        conflicts.push(Conflict { id: id.clone(), text: entry.todo.text.clone(), field: "parent" });
+        entry.parent = None;
+    }
+
+    // Order each parent's children
+    let mut children: HashMap<Option<String>, Vec<String>> = HashMap::new();
+    for id in &merged_order {
+        children.entry(merged[id].parent.clone()).or_default().push(id.clone());
+    }
+    let mut ordered: HashMap<Option<String>, Vec<String>> = HashMap::new();
+    for (parent, kids) in children {
+        let text = parent.as_ref().map_or("(top level)".to_string(), |p| merged[p].todo.text.clone());
+        let order = merge_order(
+            base.children_of(&parent),
+            local.children_of(&parent),
+            remote.children_of(&parent),
+            &kids,
+        );
+        let order = match order {
+            Some(order) => order,
+            None => {
+                conflicts.push(Conflict { id: parent.clone().unwrap_or_default(), text, field: "subtask order" });
+                merge_order(base.children_of(&parent), local.children_of(&parent), &[], &kids).unwrap_or(kids)
+            }
+        };
+        ordered.insert(parent, order);
+    }
+
+    fn build(parent: Option<String>, merged: &mut HashMap<String, Entry>, ordered: &HashMap<Option<String>, Vec<String>>) -> Vec<Todo> {
+        let ids = ordered.get(&parent).cloned().unwrap_or_default();
+        ids.into_iter()
+            .filter_map(|id| {
+                let mut todo = merged.remove(&id)?.todo;
+                todo.subtasks = build(Some(id), merged, ordered);
+                Some(todo)
+            })
+            .collect()
+    }
+    let todos = build(None, &mut merged, &ordered);
+    MergeResult { todos, conflicts }
+}
+
+fn merge_entry(base: Option<&Entry>, local: &Entry, remote: &Entry, conflicts: &mut Vec<Conflict>) -> Entry {
+    let (l, r) = (&local.todo, &remote.todo);
+    let b = base.map(|b| &b.todo);
+    let mut m = FieldMerge { id: &l.id, text: &l.text, conflicts };
+
+    let mut todo = l.clone();
+    todo.text = m.merge("text", b.map(|b| &b.text), &l.text, &r.text);
+    todo.priority = m.merge("priority", b.map(|b| &b.priority), &l.priority, &r.priority);
+    todo.due_date = m.merge("due date", b.map(|b| &b.due_date), &l.due_date, &r.due_date);
+    (todo.status, todo.completed_at) = m.merge(
+        "status",
+        b.map(|b| (b.status, b.completed_at)).as_ref(),
+        &(l.status, l.completed_at),
+        &(r.status, r.completed_at),
+    );
+    todo.is_section = m.merge("section", b.map(|b| &b.is_section), &l.is_section, &r.is_section);
+    todo.depends_on = m.merge("dependencies", b.map(|b| &b.depends_on), &l.depends_on, &r.depends_on);
+    todo.estimate = m.merge("estimate", b.map(|b| &b.estimate), &l.estimate, &r.estimate);
+    let parent = m.merge("parent", base.map(|b| &b.parent), &local.parent, &remote.parent);
+
+    // Work sessions are only ever added, so keep those of both sides
+    for session in &r.sessions {
+        match todo.sessions.iter_mut().find(|s| s.start == session.start) {
+            Some(existing) => existing.end = existing.end.or(session.end),
+            None => todo.sessions.push(*session),
+        }
+    }
+    todo.sessions.sort_by_key(|s| s.start);
+
+    Entry { todo, parent }
+}
+
+/// Order of a parent's children: the order of the side that rearranged them,
+/// followed by children only the other side has. None if both sides
+/// rearranged them differently.
+fn merge_order(base: &[String], local: &[String], remote: &[String], kids: &[String]) -> Option<Vec<String>> {
+    let keep = |order: &[String]| -> Vec<String> {
+        order.iter().filter(|id| kids.contains(id)).cloned().collect()
+    };
+    let (base, local, remote) = (keep(base), keep(local), keep(remote));
+    // Whether a side changed the relative order of the tasks it shares with the base
+    let rearranged = |side: &[String]| {
+        let shared: Vec<&String> = side.iter().filter(|id| base.contains(id)).collect();
+        let in_base: Vec<&String> = base.iter().filter(|id| side.contains(id)).collect();
+        shared != in_base
+    };
+
+    let (first, second) = match (rearranged(&local), rearranged(&remote)) {
+        (true, true) if local != remote => return None,
+        (false, true) => (remote, local),
+        _ => (local, remote),
+    };
+    let mut order = first;
+    let seen: HashSet<String> = order.iter().cloned().collect();
+    order.extend(second.into_iter().filter(|id| !seen.contains(id)));
+    // Children that moved here from a parent that is gone
+    let placed: HashSet<String> = order.iter().cloned().collect();
+    order.extend(kids.iter().filter(|id| !placed.contains(*id)).cloned());
+    Some(order)
+}
+
+/// Merge two versions of a cluster file into `local`, as a git merge driver.
+/// A missing or empty base (both sides added the file) merges two-way.
+pub fn merge_files(base: &Path, local: &Path, remote: &Path) -> bool {
+    let (local_list, remote_list) = match (TodoList::read(local), TodoList::read(remote)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return false,
+    };
+    let base_todos = TodoList::read(base).map(|b| b.todos).unwrap_or_default();
+    let mut merged = TodoList::default();
+    merged.todos = merge(&base_todos, &local_list.todos, &remote_list.todos).todos;
+    match serde_json::to_string_pretty(&merged) {
+        Ok(json) => fs::write(local, json).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Syncthing conflict copies in the data dir, with the cluster each belongs to
+pub fn find_conflict_files() -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(TodoList::data_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            if let Some((cluster, _)) = stem.split_once(SYNC_CONFLICT_MARKER) {
+                files.push((cluster.to_string(), path));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{Priority, Status};
+
+    fn task(text: &str) -> Todo {
+        Todo::new(text.to_string(), None, Priority::None)
+    }
+
+    #[test]
+    fn test_merge_field_changes() {
+        let mut a = task("A");
+        a.subtasks.push(task("A1"));
+        let base = vec![a, task("B")];
+
+        // Local renames A and completes A1, remote raises B's priority and adds C
+        let mut local = base.clone();
+        local[0].text = "A renamed".to_string();
+        local[0].subtasks[0].status = Status::Done;
+        let mut remote = base.clone();
+        remote[1].priority = Priority::High;
+        remote.push(task("C"));
+
+        let result = merge(&base, &local, &remote);
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.todos.len(), 3);
+        assert_eq!(result.todos[0].text, "A renamed");
+        assert_eq!(result.todos[0].subtasks[0].status, Status::Done);
+        assert_eq!(result.todos[1].priority, Priority::High);
+        assert_eq!(result.todos[2].text, "C");
+    }
+
+    #[test]
+    fn test_merge_conflicts_keep_local() {
+        let base = vec![task("A"), task("B")];
+        let mut local = base.clone();
+        local[0].text = "Local".to_string();
+        local.remove(1);
+        let mut remote = base.clone();
+        remote[0].text = "Remote".to_string();
+        remote[1].text = "B edited".to_string();
+
+        let result = merge(&base, &local, &remote);
+        assert_eq!(result.todos[0].text, "Local");
+        // B was deleted locally but edited remotely, so it is kept
+        assert_eq!(result.todos[1].text, "B edited");
+        let fields: Vec<&str> = result.conflicts.iter().map(|c| c.field).collect();
+        assert_eq!(fields, ["text", "deleted"]);
+    }
+
+    #[test]
+    fn test_merge_order_and_deletion() {
+        let base = vec![task("A"), task("B"), task("C")];
+        // Local deletes B, remote moves C to the top
+        let mut local = base.clone();
+        local.remove(1);
+        let remote = vec![base[2].clone(), base[0].clone(), base[1].clone()];
+
+        let result = merge(&base, &local, &remote);
+        assert!(result.conflicts.is_empty());
+        let texts: Vec<&str> = result.todos.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["C", "A"]);
+    }
+}
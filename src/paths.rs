@@ -0,0 +1,171 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directories zap reads and writes, fixed at startup
+struct Dirs {
+    data: PathBuf,
+    config: PathBuf,
+    /// Whether either directory differs from the platform default
+    custom: bool,
+}
+
+static DIRS: OnceLock<Dirs> = OnceLock::new();
+
+/// Choose the data and config directories: a flag wins over the environment
+/// (`ZAP_DATA_DIR`, `ZAP_CONFIG_DIR`), which wins over the platform default.
+/// A profile gets its own directories under the environment's or the
+/// defaults; a flag's directory is used as is. Only the first call has an
+/// effect.
+pub fn init(data_dir: Option<PathBuf>, config_dir: Option<PathBuf>, profile: Option<&str>) {
+    DIRS.get_or_init(|| {
+        let data = resolve(data_dir, "ZAP_DATA_DIR", dirs::data_dir(), profile);
+        let config = resolve(config_dir, "ZAP_CONFIG_DIR", dirs::config_dir(), profile);
+        let custom = data != default_dir(dirs::data_dir(), None) || config != default_dir(dirs::config_dir(), None);
+        Dirs { data, config, custom }
+    });
+}
+
+fn resolve(flag: Option<PathBuf>, env: &str, default: Option<PathBuf>, profile: Option<&str>) -> PathBuf {
+    if let Some(dir) = flag {
+        return dir;
+    }
+    match std::env::var_os(env).filter(|v| !v.is_empty()) {
+        Some(dir) => profile_dir(PathBuf::from(dir), profile),
+        None => default_dir(default, profile),
+    }
+}
+
+fn default_dir(base: Option<PathBuf>, profile: Option<&str>) -> PathBuf {
+    profile_dir(base.unwrap_or_else(|| PathBuf::from(".")).join("zap"), profile)
+}
+
+fn profile_dir(dir: PathBuf, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(profile) => dir.join("profiles").join(profile),
+        None => dir,
+    }
+}
+
+fn dirs() -> &'static Dirs {
+    // Tests get scratch directories, never the real ones
+    #[cfg(test)]
+    {
+        let root = std::env::temp_dir().join(format!("zap-test-{}", std::process::id()));
+        init(Some(root.join("data")), Some(root.join("config")), None);
+    }
+    init(None, None, None);
+    DIRS.get().expect("initialized above")
+}
+
+/// Whether zap runs on directories other than the default ones, e.g. with a
+/// profile, and so shouldn't hand over to an instance running on the defaults
+pub fn is_custom() -> bool {
+    dirs().custom
+}
+
+/// Where clusters, sessions and the trash are kept
+pub fn data_dir() -> PathBuf {
+    ensure(&dirs().data)
+}
+
+/// Where the `*.json` settings files are kept
+pub fn config_dir() -> PathBuf {
+    ensure(&dirs().config)
+}
+
+/// Path of a settings file, e.g. `config_file("colors.json")`
+pub fn config_file(name: &str) -> PathBuf {
+    config_dir().join(name)
+}
+
+/// Read and parse a settings file, None if it doesn't exist yet. The error
+/// names the file and where parsing failed.
+pub fn read_config<T: DeserializeOwned>(name: &str) -> Result<Option<T>, String> {
+    let path = config_file(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", name, e))?;
+    serde_json::from_str(&content).map(Some).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// Read a settings file, creating it with the defaults if it doesn't exist
+/// yet. A file that doesn't parse is reported and left as it is.
+pub fn load_config<T: DeserializeOwned + Serialize + Default>(name: &str) -> Result<T, String> {
+    if let Some(config) = read_config(name)? {
+        return Ok(config);
+    }
+    let config = T::default();
+    if let Ok(json) = serde_json::to_string_pretty(&config) {
+        fs::write(config_file(name), json).ok();
+    }
+    Ok(config)
+}
+
+fn ensure(dir: &Path) -> PathBuf {
+    fs::create_dir_all(dir).ok();
+    dir.to_path_buf()
+}
+
+/// Remove `--name value` or `--name=value` from the arguments, returning the value
+pub fn take_flag(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let index = args.iter().position(|a| a == name || a.starts_with(&prefix))?;
+    let arg = args.remove(index);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Some(value.to_string()),
+        None if index < args.len() => Some(args.remove(index)),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_precedence() {
+        let home = Some(PathBuf::from("/home/me/.local/share"));
+        let unset = "ZAP_TEST_UNSET_DIR";
+        assert_eq!(resolve(None, unset, home.clone(), None), PathBuf::from("/home/me/.local/share/zap"));
+        assert_eq!(
+            resolve(None, unset, home.clone(), Some("work")),
+            PathBuf::from("/home/me/.local/share/zap/profiles/work")
+        );
+        // An explicit directory is used as is, profile or not
+        assert_eq!(resolve(Some(PathBuf::from("/tmp/x")), unset, home.clone(), Some("work")), PathBuf::from("/tmp/x"));
+
+        // Profiles stay apart under a directory from the environment
+        let env = "ZAP_TEST_PROFILE_DIR";
+        std::env::set_var(env, "/srv/zap");
+        assert_eq!(resolve(None, env, home.clone(), None), PathBuf::from("/srv/zap"));
+        assert_eq!(resolve(None, env, home, Some("work")), PathBuf::from("/srv/zap/profiles/work"));
+    }
+
+    #[test]
+    fn test_take_flag() {
+        let mut args: Vec<String> = ["zap", "--profile", "work", "--data-dir=/tmp/d", "--no-restore"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(take_flag(&mut args, "--profile").as_deref(), Some("work"));
+        assert_eq!(take_flag(&mut args, "--data-dir").as_deref(), Some("/tmp/d"));
+        assert_eq!(take_flag(&mut args, "--config-dir"), None);
+        assert_eq!(args, ["zap", "--no-restore"]);
+    }
+
+    #[test]
+    fn test_load_config_leaves_broken_file() {
+        type Config = std::collections::BTreeMap<String, u32>;
+        let name = "load-config-test.json";
+        assert_eq!(load_config::<Config>(name), Ok(Config::new()));
+        assert!(config_file(name).exists());
+
+        fs::write(config_file(name), "{ broken").unwrap();
+        assert!(load_config::<Config>(name).unwrap_err().starts_with(name));
+        assert_eq!(fs::read_to_string(config_file(name)).unwrap(), "{ broken");
+    }
+}
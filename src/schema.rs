@@ -0,0 +1,172 @@
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::todo::{Status, Todo};
+
+/// Version of the cluster format written by this build. Files without a
+/// `schema_version` are version 0.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades of a single task (without its subtasks), index `n` taking a
+/// task from version `n` to `n + 1`
+const MIGRATIONS: [fn(&mut Value); SCHEMA_VERSION as usize] = [upgrade_legacy_status, rename_priorities];
+
+/// Why a cluster couldn't be read
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    Invalid(String),
+    /// Written by a newer zap with this schema version
+    Newer(u32),
+    /// Encrypted, and not unlocked with its passphrase in this run
+    Locked,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Invalid(reason) => write!(f, "not a valid cluster: {}", reason),
+            LoadError::Newer(version) => write!(
+                f,
+                "written by a newer zap (schema version {}, this one reads up to {})",
+                version, SCHEMA_VERSION
+            ),
+            LoadError::Locked => write!(f, "encrypted; open it with :e to enter the passphrase"),
+        }
+    }
+}
+
+/// Bring the tasks of a cluster file written at `version` up to the
+/// current schema
+pub fn migrate_todos(todos: &mut [Value], version: u32) -> Result<(), LoadError> {
+    if version > SCHEMA_VERSION {
+        return Err(LoadError::Newer(version));
+    }
+    for todo in todos {
+        migrate_task(todo, version);
+        if let Some(subtasks) = todo.get_mut("subtasks").and_then(|s| s.as_array_mut()) {
+            migrate_todos(subtasks, version)?;
+        }
+    }
+    Ok(())
+}
+
+/// Bring one task, not its subtasks, up to the current schema
+pub fn migrate_task(todo: &mut Value, version: u32) {
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(todo);
+    }
+}
+
+/// Version 1: `completed` and `abandoned` flags became `status`
+fn upgrade_legacy_status(todo: &mut Value) {
+    if let Some(obj) = todo.as_object_mut() {
+        let completed = obj.remove("completed").and_then(|v| v.as_bool()).unwrap_or(false);
+        let abandoned = obj.remove("abandoned").and_then(|v| v.as_bool()).unwrap_or(false);
+        if !obj.contains_key("status") {
+            let status = if abandoned {
+                Status::Abandoned
+            } else if completed {
+                Status::Done
+            } else {
+                Status::Todo
+            };
+            obj.insert("status".to_string(), serde_json::json!(status));
+        }
+    }
+}
+
+/// Version 2: priorities `Mid` and `Top` became `Medium` and `High`
+fn rename_priorities(todo: &mut Value) {
+    if let Some(priority) = todo.get_mut("priority") {
+        match priority.as_str() {
+            Some("Mid") => *priority = Value::from("Medium"),
+            Some("Top") => *priority = Value::from("High"),
+            _ => {}
+        }
+    }
+}
+
+/// Problems in a cluster that loaded, e.g. tasks sharing an id
+pub fn validate(todos: &[Todo]) -> Vec<String> {
+    fn collect<'a>(todos: &'a [Todo], all: &mut Vec<&'a Todo>) {
+        for todo in todos {
+            all.push(todo);
+            collect(&todo.subtasks, all);
+        }
+    }
+    let mut all = Vec::new();
+    collect(todos, &mut all);
+
+    let mut problems = Vec::new();
+    let mut ids = HashSet::new();
+    for todo in &all {
+        if todo.id.is_empty() {
+            problems.push(format!("'{}' has no id", todo.text));
+        } else if !ids.insert(todo.id.as_str()) {
+            problems.push(format!("'{}' shares id {} with another task", todo.text, todo.id));
+        }
+    }
+    for todo in &all {
+        for dep in &todo.depends_on {
+            if !ids.contains(dep.as_str()) {
+                problems.push(format!("'{}' depends on missing task {}", todo.text, dep));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Priority;
+
+    #[test]
+    fn test_upgrade_legacy_status() {
+        let mut todos = vec![serde_json::json!({
+            "id": "a",
+            "text": "Parent",
+            "completed": true,
+            "due_date": null,
+            "created_at": 0,
+            "subtasks": [
+                {"id": "b", "text": "Child", "completed": false, "abandoned": true, "due_date": null, "created_at": 0},
+                {"id": "c", "text": "Other", "completed": false, "due_date": null, "created_at": 0}
+            ]
+        })];
+        migrate_todos(&mut todos, 0).unwrap();
+        let todo: Todo = serde_json::from_value(todos.remove(0)).unwrap();
+        assert_eq!(todo.status, Status::Done);
+        assert_eq!(todo.subtasks[0].status, Status::Abandoned);
+        assert_eq!(todo.subtasks[1].status, Status::Todo);
+    }
+
+    #[test]
+    fn test_migration_chain() {
+        let mut todos = vec![serde_json::json!({
+            "id": "a", "text": "Old", "completed": true, "priority": "Top", "due_date": null, "created_at": 0
+        })];
+        migrate_todos(&mut todos, 0).unwrap();
+        let todo: Todo = serde_json::from_value(todos.remove(0)).unwrap();
+        assert_eq!((todo.status, todo.priority), (Status::Done, Priority::High));
+
+        // Already current tasks are left alone
+        let mut todos = vec![serde_json::json!({"priority": "Top"})];
+        migrate_todos(&mut todos, SCHEMA_VERSION).unwrap();
+        assert_eq!(todos[0]["priority"], "Top");
+        assert_eq!(migrate_todos(&mut [], SCHEMA_VERSION + 1), Err(LoadError::Newer(SCHEMA_VERSION + 1)));
+    }
+
+    #[test]
+    fn test_validate_finds_problems() {
+        let mut first = Todo::new("First".to_string(), None, Priority::None);
+        let mut second = Todo::new("Second".to_string(), None, Priority::None);
+        second.id = first.id.clone();
+        first.depends_on.push("gone".to_string());
+        let problems = validate(&[first, second]);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("shares id"));
+        assert!(problems[1].contains("missing task gone"));
+    }
+}
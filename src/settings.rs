@@ -0,0 +1,185 @@
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::paths;
+
+/// First day of the week in the calendar and week views
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Sunday,
+    Monday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Sunday => Weekday::Sun,
+            WeekStart::Monday => Weekday::Mon,
+        }
+    }
+}
+
+/// General behavior settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// Cluster opened when there is no session to restore
+    #[serde(default = "default_startup_cluster")]
+    pub startup_cluster: String,
+    /// How long notifications stay up
+    #[serde(default = "default_notification_ms")]
+    pub notification_ms: u32,
+    #[serde(default)]
+    pub week_start: WeekStart,
+    /// Whether completing a task moves it to the bottom of its list
+    #[serde(default = "default_move_done_to_bottom")]
+    pub move_done_to_bottom: bool,
+    /// Whether edits to the config files are picked up without `:reload`
+    #[serde(default)]
+    pub watch_config: bool,
+}
+
+fn default_startup_cluster() -> String {
+    "main".to_string()
+}
+
+fn default_notification_ms() -> u32 {
+    3000
+}
+
+fn default_move_done_to_bottom() -> bool {
+    true
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            startup_cluster: default_startup_cluster(),
+            notification_ms: default_notification_ms(),
+            week_start: WeekStart::default(),
+            move_done_to_bottom: default_move_done_to_bottom(),
+            watch_config: false,
+        }
+    }
+}
+
+/// Option names accepted by `:set`
+pub const OPTIONS: [&str; 5] = [
+    "startup_cluster",
+    "notification_ms",
+    "week_start",
+    "move_done_to_bottom",
+    "watch_config",
+];
+
+thread_local! {
+    static CURRENT: RefCell<Option<Settings>> = const { RefCell::new(None) };
+}
+
+impl Settings {
+    pub const FILE: &'static str = "settings.json";
+
+    /// Read `settings.json`, using the defaults if it can't be parsed
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_default()
+    }
+
+    /// Read `settings.json`, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        if let Some(config) = paths::read_config(Self::FILE)? {
+            return Ok(config);
+        }
+
+        // Create default config
+        let config = Self::default();
+        config.save();
+        Ok(config)
+    }
+
+    pub fn save(&self) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            fs::write(Self::config_path(), json).ok();
+        }
+    }
+
+    fn config_path() -> PathBuf {
+        paths::config_file(Self::FILE)
+    }
+
+    /// The settings in effect, read from `settings.json` on first use
+    pub fn current() -> Self {
+        CURRENT.with(|current| current.borrow_mut().get_or_insert_with(Self::load).clone())
+    }
+
+    /// Re-read `settings.json`; the settings in effect stay as they are if
+    /// it can't be parsed
+    pub fn reload() -> Result<(), String> {
+        let settings = Self::try_load()?;
+        CURRENT.with(|current| *current.borrow_mut() = Some(settings));
+        Ok(())
+    }
+
+    /// Change the settings in effect and write them to `settings.json`
+    pub fn update(f: impl FnOnce(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        let mut settings = Self::current();
+        f(&mut settings)?;
+        settings.save();
+        CURRENT.with(|current| *current.borrow_mut() = Some(settings));
+        Ok(())
+    }
+
+    /// An option's value as `:set option?` shows it
+    pub fn get(&self, option: &str) -> Option<String> {
+        Some(match option {
+            "startup_cluster" => self.startup_cluster.clone(),
+            "notification_ms" => self.notification_ms.to_string(),
+            "week_start" => serde_json::to_value(self.week_start).ok()?.as_str()?.to_string(),
+            "move_done_to_bottom" => self.move_done_to_bottom.to_string(),
+            "watch_config" => self.watch_config.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Set an option from its text form, as in `:set option=value`
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value for {}: '{}'", option, value);
+        match option {
+            "startup_cluster" if !value.is_empty() => self.startup_cluster = value.to_string(),
+            "notification_ms" => self.notification_ms = value.parse().map_err(|_| invalid())?,
+            "week_start" => self.week_start = serde_json::from_value(value.into()).map_err(|_| invalid())?,
+            "move_done_to_bottom" => self.move_done_to_bottom = value.parse().map_err(|_| invalid())?,
+            "watch_config" => self.watch_config = value.parse().map_err(|_| invalid())?,
+            _ if OPTIONS.contains(&option) => return Err(invalid()),
+            _ => return Err(format!("Unknown option: {}", option)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get_options() {
+        let mut settings = Settings::default();
+        settings.set("week_start", "monday").unwrap();
+        settings.set("notification_ms", "1500").unwrap();
+        settings.set("move_done_to_bottom", "false").unwrap();
+        assert_eq!(settings.week_start, WeekStart::Monday);
+        assert_eq!(settings.get("week_start").as_deref(), Some("monday"));
+        assert_eq!(settings.get("notification_ms").as_deref(), Some("1500"));
+        assert!(!settings.move_done_to_bottom);
+
+        assert!(settings.set("week_start", "friday").is_err());
+        assert!(settings.set("startup_cluster", "").is_err());
+        assert_eq!(settings.set("colour", "red"), Err("Unknown option: colour".to_string()));
+        for option in OPTIONS {
+            assert!(settings.get(option).is_some());
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::{self, Envelope};
+use crate::merge::SYNC_CONFLICT_MARKER;
+use crate::paths;
+use crate::schema::{migrate_task, LoadError, SCHEMA_VERSION};
+use crate::todo::{Todo, TodoList};
+
+/// Where clusters are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// One `<cluster>.json` file per cluster
+    #[default]
+    Json,
+    /// One row per task in `zap.db`
+    Sqlite,
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Backend::Json),
+            "sqlite" => Some(Backend::Sqlite),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Json => "json",
+            Backend::Sqlite => "sqlite",
+        }
+    }
+
+    /// Open the backend's storage in a data directory
+    pub fn open(self, dir: &Path) -> Result<Box<dyn Storage>, String> {
+        match self {
+            Backend::Json => Ok(Box::new(JsonStorage::new(dir))),
+            Backend::Sqlite => Ok(Box::new(SqliteStorage::open(&dir.join("zap.db"))?)),
+        }
+    }
+}
+
+/// Which storage backend holds the clusters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    #[serde(default)]
+    pub backend: Backend,
+}
+
+impl StorageConfig {
+    pub const FILE: &'static str = "storage.json";
+
+    /// Read `storage.json`, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        paths::load_config(Self::FILE)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(paths::config_file(Self::FILE), json).map_err(|e| format!("{}: {}", Self::FILE, e))
+    }
+}
+
+/// A task without its subtasks, and where it sits in its cluster
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRow {
+    pub todo: Todo,
+    pub parent_id: Option<String>,
+    /// Position among its siblings
+    pub position: usize,
+}
+
+/// Flatten a task tree into rows, parents before their subtasks
+pub fn task_rows(todos: &[Todo]) -> Vec<TaskRow> {
+    fn collect(todos: &[Todo], parent_id: Option<&str>, rows: &mut Vec<TaskRow>) {
+        for (position, todo) in todos.iter().enumerate() {
+            rows.push(TaskRow {
+                todo: Todo { subtasks: Vec::new(), ..todo.clone() },
+                parent_id: parent_id.map(str::to_string),
+                position,
+            });
+            collect(&todo.subtasks, Some(&todo.id), rows);
+        }
+    }
+    let mut rows = Vec::new();
+    collect(todos, None, &mut rows);
+    rows
+}
+
+/// Rebuild a task tree from rows. Rows whose parent is missing become
+/// top-level tasks rather than being lost.
+pub fn build_tree(rows: Vec<TaskRow>) -> Vec<Todo> {
+    fn attach(parent_id: Option<&str>, children: &mut HashMap<Option<String>, Vec<TaskRow>>) -> Vec<Todo> {
+        let mut rows = children.remove(&parent_id.map(str::to_string)).unwrap_or_default();
+        rows.sort_by_key(|row| row.position);
+        rows.into_iter()
+            .map(|row| {
+                let mut todo = row.todo;
+                todo.subtasks = attach(Some(&todo.id), children);
+                todo
+            })
+            .collect()
+    }
+    let ids: HashSet<String> = rows.iter().map(|row| row.todo.id.clone()).collect();
+    let mut children: HashMap<Option<String>, Vec<TaskRow>> = HashMap::new();
+    for mut row in rows {
+        if row.parent_id.as_ref().is_some_and(|id| !ids.contains(id)) {
+            row.parent_id = None;
+            row.position = usize::MAX;
+        }
+        children.entry(row.parent_id.clone()).or_default().push(row);
+    }
+    attach(None, &mut children)
+}
+
+/// Persistence of clusters
+pub trait Storage {
+    /// Names of all stored clusters, archives included
+    fn list_clusters(&self) -> Vec<String>;
+
+    fn exists(&self, cluster: &str) -> bool;
+
+    /// A cluster's tasks, None if it doesn't exist
+    fn load(&self, cluster: &str) -> Result<Option<Vec<Todo>>, LoadError>;
+
+    /// Move a cluster that can't be read out of the way, so that it is kept
+    /// but no longer listed. Returns where it went.
+    fn set_aside(&self, cluster: &str) -> Result<String, String> {
+        Err(format!("can't set '{}' aside", cluster))
+    }
+
+    /// Whether saves encrypt clusters that have a key in `crypto`
+    fn supports_encryption(&self) -> bool {
+        false
+    }
+
+    /// The sealed contents of an encrypted cluster
+    fn envelope(&self, cluster: &str) -> Option<Envelope> {
+        let _ = cluster;
+        None
+    }
+
+    /// Replace everything stored for a cluster
+    fn save(&self, cluster: &str, todos: &[Todo]) -> Result<(), String>;
+
+    /// Store the rows that changed since the last save and drop removed
+    /// tasks. `todos` is the whole cluster, for backends that can only
+    /// write it in full.
+    fn upsert(&self, cluster: &str, todos: &[Todo], changed: &[&TaskRow], removed: &[&str]) -> Result<(), String> {
+        let _ = (changed, removed);
+        self.save(cluster, todos)
+    }
+}
+
+/// One JSON file per cluster, rewritten on every save. Clusters with a key
+/// are written as an encrypted `Envelope` instead.
+pub struct JsonStorage {
+    dir: PathBuf,
+}
+
+impl JsonStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, cluster: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", cluster))
+    }
+}
+
+impl Storage for JsonStorage {
+    fn list_clusters(&self) -> Vec<String> {
+        let mut clusters = Vec::new();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if let (Some(name), Some(ext)) = (path.file_stem(), path.extension()) {
+                    let name = name.to_string_lossy().to_string();
+                    // Sync conflict copies are merged back with :resolve
+                    if ext == "json" && !name.contains(SYNC_CONFLICT_MARKER) {
+                        clusters.push(name);
+                    }
+                }
+            }
+        }
+        clusters.sort();
+        clusters
+    }
+
+    fn exists(&self, cluster: &str) -> bool {
+        self.path(cluster).exists()
+    }
+
+    fn load(&self, cluster: &str) -> Result<Option<Vec<Todo>>, LoadError> {
+        let path = self.path(cluster);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut content = fs::read_to_string(&path).map_err(|e| LoadError::Invalid(e.to_string()))?;
+        if let Some(envelope) = Envelope::parse(&content) {
+            let key = crypto::key_for(cluster).ok_or(LoadError::Locked)?;
+            let plaintext = key.open(&envelope).map_err(|_| LoadError::Locked)?;
+            content = String::from_utf8(plaintext).map_err(|e| LoadError::Invalid(e.to_string()))?;
+        }
+        TodoList::parse(&content).map(|list| Some(list.todos))
+    }
+
+    fn set_aside(&self, cluster: &str) -> Result<String, String> {
+        let name = format!("{}.json.invalid-{}", cluster, chrono::Utc::now().timestamp());
+        fs::rename(self.path(cluster), self.dir.join(&name)).map_err(|e| e.to_string())?;
+        Ok(name)
+    }
+
+    fn save(&self, cluster: &str, todos: &[Todo]) -> Result<(), String> {
+        #[derive(Serialize)]
+        struct ClusterFile<'a> {
+            schema_version: u32,
+            todos: &'a [Todo],
+        }
+        let file = ClusterFile {
+            schema_version: SCHEMA_VERSION,
+            todos,
+        };
+        let mut json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        if let Some(key) = crypto::key_for(cluster) {
+            let envelope = key.seal(json.as_bytes())?;
+            json = serde_json::to_string_pretty(&envelope).map_err(|e| e.to_string())?;
+        }
+        fs::write(self.path(cluster), json).map_err(|e| e.to_string())
+    }
+
+    fn supports_encryption(&self) -> bool {
+        true
+    }
+
+    fn envelope(&self, cluster: &str) -> Option<Envelope> {
+        Envelope::parse(&fs::read_to_string(self.path(cluster)).ok()?)
+    }
+}
+
+/// Tasks as rows of a SQLite database, so a save only touches the tasks
+/// that changed. Task fields are kept as JSON so new fields need no table
+/// change; the database's `user_version` is the schema version of that JSON.
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Self::init(Connection::open(path).map_err(|e| e.to_string())?)
+    }
+
+    #[cfg(test)]
+    fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE IF NOT EXISTS clusters (name TEXT PRIMARY KEY);
+             CREATE TABLE IF NOT EXISTS tasks (
+                 cluster TEXT NOT NULL REFERENCES clusters(name) ON DELETE CASCADE,
+                 id TEXT NOT NULL,
+                 parent_id TEXT,
+                 position INTEGER NOT NULL,
+                 data TEXT NOT NULL,
+                 PRIMARY KEY (cluster, id)
+             );",
+        )
+        .map_err(|e| e.to_string())?;
+        let storage = Self { conn };
+        storage.upgrade()?;
+        Ok(storage)
+    }
+
+    /// Migrate every task written by an older zap to the current schema
+    fn upgrade(&self) -> Result<(), String> {
+        let version: u32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if version > SCHEMA_VERSION {
+            return Err(LoadError::Newer(version).to_string());
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+        self.transaction(|| {
+            let tasks = self
+                .conn
+                .prepare("SELECT cluster, id, data FROM tasks")?
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (cluster, id, data) in tasks {
+                // Rows that aren't JSON are left for load to report
+                if let Ok(mut todo) = serde_json::from_str::<serde_json::Value>(&data) {
+                    migrate_task(&mut todo, version);
+                    self.conn.execute(
+                        "UPDATE tasks SET data = ?1 WHERE cluster = ?2 AND id = ?3",
+                        params![todo.to_string(), cluster, id],
+                    )?;
+                }
+            }
+            self.conn.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+        })
+    }
+
+    fn put(&self, cluster: &str, row: &TaskRow) -> rusqlite::Result<()> {
+        let data = serde_json::to_string(&row.todo).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO tasks (cluster, id, parent_id, position, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![cluster, row.todo.id, row.parent_id, row.position as i64, data],
+        )?;
+        Ok(())
+    }
+
+    /// Run statements in one transaction, rolling back if any fails
+    fn transaction(&self, f: impl FnOnce() -> rusqlite::Result<()>) -> Result<(), String> {
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        match f() {
+            Ok(()) => self.conn.execute_batch("COMMIT").map_err(|e| e.to_string()),
+            Err(e) => {
+                self.conn.execute_batch("ROLLBACK").ok();
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn list_clusters(&self) -> Vec<String> {
+        let names = self.conn.prepare("SELECT name FROM clusters ORDER BY name").and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<String>>>()
+        });
+        names.unwrap_or_default()
+    }
+
+    fn exists(&self, cluster: &str) -> bool {
+        self.conn
+            .query_row("SELECT 1 FROM clusters WHERE name = ?1", [cluster], |_| Ok(()))
+            .optional()
+            .is_ok_and(|found| found.is_some())
+    }
+
+    fn load(&self, cluster: &str) -> Result<Option<Vec<Todo>>, LoadError> {
+        if !self.exists(cluster) {
+            return Ok(None);
+        }
+        let invalid = |e: rusqlite::Error| LoadError::Invalid(e.to_string());
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, parent_id, position, data FROM tasks WHERE cluster = ?1")
+            .map_err(invalid)?;
+        let rows = stmt
+            .query_map([cluster], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(invalid)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(invalid)?;
+        let rows = rows
+            .into_iter()
+            .map(|(id, parent_id, position, data)| {
+                let todo = serde_json::from_str(&data).map_err(|e| LoadError::Invalid(format!("task {}: {}", id, e)))?;
+                Ok(TaskRow {
+                    todo,
+                    parent_id,
+                    position: position as usize,
+                })
+            })
+            .collect::<Result<Vec<_>, LoadError>>()?;
+        Ok(Some(build_tree(rows)))
+    }
+
+    fn save(&self, cluster: &str, todos: &[Todo]) -> Result<(), String> {
+        self.transaction(|| {
+            self.conn.execute("INSERT OR IGNORE INTO clusters (name) VALUES (?1)", [cluster])?;
+            self.conn.execute("DELETE FROM tasks WHERE cluster = ?1", [cluster])?;
+            for row in task_rows(todos) {
+                self.put(cluster, &row)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn upsert(&self, cluster: &str, _todos: &[Todo], changed: &[&TaskRow], removed: &[&str]) -> Result<(), String> {
+        self.transaction(|| {
+            self.conn.execute("INSERT OR IGNORE INTO clusters (name) VALUES (?1)", [cluster])?;
+            for row in changed {
+                self.put(cluster, row)?;
+            }
+            for id in removed {
+                self.conn.execute("DELETE FROM tasks WHERE cluster = ?1 AND id = ?2", [cluster, id])?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Copy every cluster from one backend to another. Returns how many
+/// clusters were copied.
+pub fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<usize, String> {
+    let clusters = from.list_clusters();
+    for cluster in &clusters {
+        if from.envelope(cluster).is_some() && !to.supports_encryption() {
+            return Err(format!("'{}' is encrypted; :decrypt it first", cluster));
+        }
+        let todos = from
+            .load(cluster)
+            .map_err(|e| format!("Can't read '{}': {}", cluster, e))?
+            .unwrap_or_default();
+        to.save(cluster, &todos)?;
+    }
+    Ok(clusters.len())
+}
+
+thread_local! {
+    static STORAGE: RefCell<Option<(Backend, Box<dyn Storage>)>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with the configured backend, opening it on first use. Falls back
+/// to JSON files if the configured backend can't be opened, or storage.json
+/// can't be read (which is reported at startup).
+pub fn with_storage<R>(f: impl FnOnce(&dyn Storage) -> R) -> R {
+    STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let (_, backend) = storage.get_or_insert_with(|| {
+            let backend = StorageConfig::try_load().unwrap_or_default().backend;
+            match backend.open(&TodoList::data_dir()) {
+                Ok(opened) => (backend, opened),
+                Err(_) => (Backend::Json, Box::new(JsonStorage::new(TodoList::data_dir()))),
+            }
+        });
+        f(backend.as_ref())
+    })
+}
+
+/// The backend clusters are currently read from and written to
+pub fn current_backend() -> Backend {
+    with_storage(|_| ());
+    STORAGE.with(|storage| storage.borrow().as_ref().map(|(backend, _)| *backend).unwrap_or_default())
+}
+
+/// Copy all clusters into `target` and use it from now on, also for later
+/// runs. Returns how many clusters were copied.
+pub fn switch_backend(target: Backend) -> Result<usize, String> {
+    // storage.json is rewritten below, so one that doesn't parse is left to be fixed first
+    let mut config = StorageConfig::try_load()?;
+    let opened = target.open(&TodoList::data_dir())?;
+    let count = with_storage(|current| migrate(current, opened.as_ref()))?;
+    STORAGE.with(|storage| *storage.borrow_mut() = Some((target, opened)));
+    config.backend = target;
+    config.save()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{Priority, Status};
+
+    fn task(text: &str) -> Todo {
+        Todo::new(text.to_string(), None, Priority::None)
+    }
+
+    fn sample() -> Vec<Todo> {
+        let mut project = task("Project");
+        project.subtasks = vec![task("Design"), task("Build")];
+        vec![project, task("Errands")]
+    }
+
+    #[test]
+    fn test_rows_round_trip() {
+        let todos = sample();
+        let rows = task_rows(&todos);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[2].parent_id.as_deref(), Some(todos[0].id.as_str()));
+        assert_eq!(rows[2].position, 1);
+        assert_eq!(build_tree(rows), todos);
+    }
+
+    #[test]
+    fn test_sqlite_upsert_changes_only_given_rows() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let mut todos = sample();
+        storage.save("work", &todos).unwrap();
+        assert_eq!(storage.list_clusters(), ["work"]);
+
+        todos[0].subtasks[0].status = Status::Done;
+        let removed = todos.pop().unwrap();
+        let rows = task_rows(&todos);
+        storage.upsert("work", &todos, &[&rows[1]], &[&removed.id]).unwrap();
+        assert_eq!(storage.load("work").unwrap(), Some(todos));
+        assert_eq!(storage.load("missing"), Ok(None));
+    }
+
+    #[test]
+    fn test_migrate_json_to_sqlite() {
+        let dir = std::env::temp_dir().join(format!("zap-storage-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let json = JsonStorage::new(&dir);
+        json.save("work", &sample()).unwrap();
+        json.save("work.archive", &[task("Old")]).unwrap();
+
+        let sqlite = SqliteStorage::open_in_memory().unwrap();
+        assert_eq!(migrate(&json, &sqlite).unwrap(), 2);
+        assert_eq!(sqlite.list_clusters(), ["work", "work.archive"]);
+        assert_eq!(sqlite.load("work"), json.load("work"));
+
+        // A file that doesn't parse is reported, then moved out of the list
+        fs::write(dir.join("broken.json"), "{ not json").unwrap();
+        assert!(matches!(json.load("broken"), Err(LoadError::Invalid(_))));
+        json.set_aside("broken").unwrap();
+        assert!(!json.exists("broken"));
+        assert_eq!(json.list_clusters(), ["work", "work.archive"]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_encrypted_json_cluster() {
+        let dir = std::env::temp_dir().join(format!("zap-crypto-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let json = JsonStorage::new(&dir);
+        crypto::remember("secret", crypto::Key::generate("hunter2").unwrap());
+        json.save("secret", &sample()).unwrap();
+        json.save("secret.archive", &[task("Old")]).unwrap();
+
+        let content = fs::read_to_string(dir.join("secret.json")).unwrap();
+        assert!(!content.contains("Project"));
+        let envelope = json.envelope("secret").unwrap();
+        assert!(json.envelope("secret.archive").is_some());
+        assert_eq!(json.load("secret").unwrap().unwrap()[0].text, "Project");
+
+        // Without the key the cluster is locked, not invalid
+        crypto::forget("secret");
+        assert_eq!(json.load("secret"), Err(LoadError::Locked));
+        assert!(crypto::Key::for_envelope("hunter2", &envelope).is_ok());
+        let sqlite = SqliteStorage::open_in_memory().unwrap();
+        assert!(migrate(&json, &sqlite).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,25 +1,30 @@
-use chrono::{NaiveDate, Utc};
+use chrono::{Duration, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+use crate::crypto::{self, Envelope, Key};
+use crate::merge::{merge, Conflict};
+use crate::paths;
+use crate::schema::{migrate_todos, LoadError, SCHEMA_VERSION};
+use crate::storage::{current_backend, task_rows, with_storage, Backend, TaskRow};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum Priority {
     #[default]
     None,
     Low,
-    #[serde(alias = "Mid")]
     Medium,
-    #[serde(alias = "Top")]
     High,
     Max,
 }
 
 impl Priority {
     /// Returns sort order (lower = higher priority, sorted first)
-    fn sort_order(&self) -> u8 {
+    pub fn sort_order(&self) -> u8 {
         match self {
             Priority::Max => 0,
             Priority::High => 1,
@@ -30,11 +35,62 @@ impl Priority {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Workflow status of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum Status {
+    #[default]
+    Todo,
+    InProgress,
+    Blocked,
+    Done,
+    Abandoned,
+}
+
+impl Status {
+    /// Returns sort order (in-progress work first, closed tasks last)
+    pub fn sort_order(&self) -> u8 {
+        match self {
+            Status::InProgress => 0,
+            Status::Todo => 1,
+            Status::Blocked => 2,
+            Status::Done => 3,
+            Status::Abandoned => 4,
+        }
+    }
+
+    /// The next status when cycling with the status key
+    pub fn next(&self) -> Status {
+        match self {
+            Status::Todo => Status::InProgress,
+            Status::InProgress => Status::Blocked,
+            Status::Blocked => Status::Done,
+            Status::Done => Status::Abandoned,
+            Status::Abandoned => Status::Todo,
+        }
+    }
+
+    /// Done or abandoned - nothing left to do
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Status::Done | Status::Abandoned)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Status::Todo => "Open",
+            Status::InProgress => "In progress",
+            Status::Blocked => "Blocked",
+            Status::Done => "Done",
+            Status::Abandoned => "Abandoned",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Todo {
     pub id: String,
     pub text: String,
-    pub completed: bool,
+    #[serde(default)]
+    pub status: Status,
     pub due_date: Option<NaiveDate>,
     pub created_at: i64,
     #[serde(default)]
@@ -43,8 +99,34 @@ pub struct Todo {
     pub priority: Priority,
     #[serde(default)]
     pub is_section: bool,
+    /// Ids of tasks that must be closed before this one can start
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Estimated effort in minutes
     #[serde(default)]
-    pub abandoned: bool,
+    pub estimate: Option<u32>,
+    /// Time tracked on this task with the timer
+    #[serde(default)]
+    pub sessions: Vec<WorkSession>,
+    /// When the task was last marked done or abandoned
+    #[serde(default)]
+    pub completed_at: Option<i64>,
+}
+
+/// A stretch of time worked on a task (unix timestamps, like `created_at`)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WorkSession {
+    pub start: i64,
+    /// None while the timer is still running
+    pub end: Option<i64>,
+}
+
+impl WorkSession {
+    /// Seconds of this session that fall inside `[from, to)`
+    fn seconds_between(&self, from: i64, to: i64) -> i64 {
+        let end = self.end.unwrap_or_else(|| Utc::now().timestamp());
+        (end.min(to) - self.start.max(from)).max(0)
+    }
 }
 
 impl Todo {
@@ -52,13 +134,16 @@ impl Todo {
         Self {
             id: Uuid::new_v4().to_string(),
             text,
-            completed: false,
+            status: Status::Todo,
             due_date,
             created_at: Utc::now().timestamp(),
             subtasks: Vec::new(),
             priority,
             is_section: false,
-            abandoned: false,
+            depends_on: Vec::new(),
+            estimate: None,
+            sessions: Vec::new(),
+            completed_at: None,
         }
     }
 
@@ -66,23 +151,62 @@ impl Todo {
         Self {
             id: Uuid::new_v4().to_string(),
             text,
-            completed: false,
+            status: Status::Todo,
             due_date: None,
             created_at: Utc::now().timestamp(),
             subtasks: Vec::new(),
             priority: Priority::None,
             is_section: true,
-            abandoned: false,
+            depends_on: Vec::new(),
+            estimate: None,
+            sessions: Vec::new(),
+            completed_at: None,
         }
     }
 
-    pub fn toggle(&mut self) {
-        self.completed = !self.completed;
+    pub fn is_done(&self) -> bool {
+        self.status == Status::Done
+    }
+
+    pub fn is_abandoned(&self) -> bool {
+        self.status == Status::Abandoned
     }
 
     pub fn has_subtasks(&self) -> bool {
         !self.subtasks.is_empty()
     }
+
+    /// Whether the timer is running on this task
+    pub fn is_timing(&self) -> bool {
+        self.sessions.last().is_some_and(|s| s.end.is_none())
+    }
+
+    /// Estimate including all subtasks, in minutes
+    pub fn total_estimate(&self) -> Option<u32> {
+        let from_subtasks = self.subtasks.iter().filter_map(|t| t.total_estimate());
+        self.estimate.into_iter().chain(from_subtasks).reduce(|a, b| a + b)
+    }
+
+    /// Seconds tracked on this task and all subtasks inside `[from, to)`
+    pub fn tracked_between(&self, from: i64, to: i64) -> i64 {
+        let own: i64 = self.sessions.iter().map(|s| s.seconds_between(from, to)).sum();
+        own + self.subtasks.iter().map(|t| t.tracked_between(from, to)).sum::<i64>()
+    }
+
+    /// Seconds tracked on this task and all subtasks
+    pub fn total_tracked(&self) -> i64 {
+        self.tracked_between(i64::MIN, i64::MAX)
+    }
+}
+
+/// Format minutes compactly: "45m", "2h", "1h20"
+pub fn format_minutes(minutes: i64) -> String {
+    let (hours, mins) = (minutes / 60, minutes % 60);
+    match (hours, mins) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{:02}", h, m),
+    }
 }
 
 /// A flattened view of a todo with its depth level for display
@@ -94,74 +218,431 @@ pub struct FlatTodo {
     pub has_subtasks: bool,
     pub is_folded: bool,
     pub hierarchy_path: Vec<String>,  // Names of parent tasks for breadcrumb display
+    pub is_blocked: bool,  // Waiting on an open dependency
+    pub hidden_count: usize,  // For a "Completed (N)" placeholder row: closed tasks behind it
+}
+
+/// Why a dependency could not be added
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencyError {
+    NotFound(String),
+    Ambiguous(String),
+    SelfDependency,
+    /// Adding the dependency would close a loop; holds the text of the task
+    /// that already (transitively) depends on the selected one
+    Cycle(String),
+}
+
+impl std::fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyError::NotFound(prefix) => write!(f, "No task with id '{}'", prefix),
+            DependencyError::Ambiguous(prefix) => write!(f, "Id '{}' matches more than one task", prefix),
+            DependencyError::SelfDependency => write!(f, "A task cannot depend on itself"),
+            DependencyError::Cycle(text) => write!(f, "Dependency cycle: '{}' already depends on this task", text),
+        }
+    }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// Suffix of the cluster name holding a cluster's archived tasks
+pub const ARCHIVE_SUFFIX: &str = ".archive";
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TodoList {
+    /// Format version the tasks are in, see `schema::SCHEMA_VERSION`
+    #[serde(default)]
+    schema_version: u32,
     pub todos: Vec<Todo>,
     #[serde(skip)]
     cluster_name: String,
     #[serde(skip)]
     folded_ids: HashSet<String>,
+    /// Task whose subtree is shown as if it were the whole cluster
+    #[serde(skip)]
+    hoisted_id: Option<String>,
+    /// JSON last read from or written to the cluster file
+    #[serde(skip)]
+    disk_snapshot: RefCell<String>,
+    /// Rows as last read or written, to find what a save changed
+    #[serde(skip)]
+    stored_rows: RefCell<HashMap<String, TaskRow>>,
+    /// Why the cluster couldn't be read; such a list is never saved
+    #[serde(skip)]
+    load_error: Option<String>,
+}
+
+impl Default for TodoList {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            todos: Vec::new(),
+            cluster_name: String::new(),
+            folded_ids: HashSet::new(),
+            hoisted_id: None,
+            disk_snapshot: RefCell::default(),
+            stored_rows: RefCell::default(),
+            load_error: None,
+        }
+    }
 }
 
 impl TodoList {
     pub fn data_dir() -> PathBuf {
-        let data_dir = dirs::data_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("zap");
-        fs::create_dir_all(&data_dir).ok();
-        data_dir
+        paths::data_dir()
     }
 
+    /// File of a cluster in the JSON backend
     pub fn cluster_path(name: &str) -> PathBuf {
         Self::data_dir().join(format!("{}.json", name))
     }
 
+    /// Load a cluster, empty if it doesn't exist yet. A cluster that can't be
+    /// read comes back empty and read-only (see `load_error`), and is set aside
+    /// where the backend supports it, so it is never saved over.
     pub fn load(cluster_name: &str) -> Self {
-        let path = Self::cluster_path(cluster_name);
-        let mut list = if path.exists() {
-            fs::read_to_string(&path)
-                .ok()
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default()
-        } else {
-            Self::default()
+        let mut list = Self {
+            cluster_name: cluster_name.to_string(),
+            ..Self::default()
         };
-        list.cluster_name = cluster_name.to_string();
+        match with_storage(|storage| storage.load(cluster_name)) {
+            Ok(todos) => list.todos = todos.unwrap_or_default(),
+            Err(err) => {
+                let mut message = format!("Can't read '{}': {}", cluster_name, err);
+                if let LoadError::Invalid(_) = err {
+                    if let Ok(moved_to) = with_storage(|storage| storage.set_aside(cluster_name)) {
+                        message.push_str(&format!("; moved to {}", moved_to));
+                    }
+                }
+                list.load_error = Some(message);
+            }
+        }
+        list.mark_synced();
         list
     }
 
+    /// Why the cluster couldn't be read, if it couldn't
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Whether the storage backend has a cluster of this name
+    pub fn exists(cluster_name: &str) -> bool {
+        with_storage(|storage| storage.exists(cluster_name))
+    }
+
+    /// Parse a cluster file without making it the list's file
+    pub fn read(path: &Path) -> Option<Self> {
+        Self::parse(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Parse the contents of a cluster file, migrating it to the current schema
+    pub fn parse(content: &str) -> Result<Self, LoadError> {
+        let invalid = |e: serde_json::Error| LoadError::Invalid(e.to_string());
+        let mut value = serde_json::from_str::<serde_json::Value>(content).map_err(invalid)?;
+        let version = value.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        match value.get_mut("todos").and_then(|t| t.as_array_mut()) {
+            Some(todos) => migrate_todos(todos, version)?,
+            None => migrate_todos(&mut [], version)?,
+        }
+        let mut list: Self = serde_json::from_value(value).map_err(invalid)?;
+        list.schema_version = SCHEMA_VERSION;
+        Ok(list)
+    }
+
+    fn merge_base_path(name: &str) -> PathBuf {
+        let dir = Self::data_dir().join(".merge-base");
+        fs::create_dir_all(&dir).ok();
+        dir.join(format!("{}.json", name))
+    }
+
+    /// Keep the current tasks as the common version for merging sync conflicts.
+    /// Encrypted clusters keep no plain copy.
+    pub fn save_merge_base(&self) {
+        if crypto::key_for(&self.cluster_name).is_some() {
+            fs::remove_file(Self::merge_base_path(&self.cluster_name)).ok();
+        } else if let Ok(json) = serde_json::to_string_pretty(self) {
+            fs::write(Self::merge_base_path(&self.cluster_name), json).ok();
+        }
+    }
+
+    /// Tasks as they were when the cluster was last read from disk
+    pub fn load_merge_base(name: &str) -> Option<Self> {
+        Self::read(&Self::merge_base_path(name))
+    }
+
+    /// Remember the tasks in memory as matching the stored cluster
+    fn mark_synced(&self) {
+        *self.disk_snapshot.borrow_mut() = serde_json::to_string_pretty(self).unwrap_or_default();
+        *self.stored_rows.borrow_mut() = task_rows(&self.todos).into_iter().map(|row| (row.todo.id.clone(), row)).collect();
+    }
+
+    /// Whether the tasks in memory differ from what was last read or written,
+    /// e.g. because writing the file failed
+    pub fn has_unsaved_changes(&self) -> bool {
+        serde_json::to_string_pretty(self).ok().as_ref() != Some(&*self.disk_snapshot.borrow())
+    }
+
+    /// Whether something other than this list wrote the cluster file since
+    /// it was last read or written (JSON backend only)
+    pub fn changed_on_disk(&self) -> bool {
+        if current_backend() != Backend::Json {
+            return false;
+        }
+        let content = match fs::read_to_string(Self::cluster_path(&self.cluster_name)) {
+            Ok(content) => content,
+            Err(_) => return false,
+        };
+        // Encrypted files differ on every write; compare what they hold
+        match (Envelope::parse(&content), crypto::key_for(&self.cluster_name)) {
+            (Some(envelope), Some(key)) => key.open(&envelope).map_or(true, |plain| plain != self.disk_snapshot.borrow().as_bytes()),
+            _ => content != *self.disk_snapshot.borrow(),
+        }
+    }
+
+    /// Replace the tasks with the stored cluster's, keeping fold and hoist state.
+    /// Returns false (keeping the tasks) if the cluster can't be read.
+    pub fn reload(&mut self) -> bool {
+        match with_storage(|storage| storage.load(&self.cluster_name)) {
+            Ok(Some(todos)) => {
+                self.todos = todos;
+                self.mark_synced();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Store the cluster. Archives are read-only and only change
+    /// through `archive_done` and `restore_archived`; clusters that failed
+    /// to load are never written, and neither are lists without a cluster,
+    /// like a blank tab's. A change made on disk by someone else is never
+    /// written over: the tasks stay unsaved until `merge_disk_changes`.
     pub fn save(&self) {
-        let path = Self::cluster_path(&self.cluster_name);
-        if let Ok(json) = serde_json::to_string_pretty(self) {
-            fs::write(path, json).ok();
+        if !self.is_read_only() && !self.cluster_name.is_empty() && !self.changed_on_disk() {
+            self.write().ok();
+        }
+    }
+
+    /// Merge what changed on disk since the last read or write with the
+    /// unsaved changes in memory, and save the result. Returns the fields
+    /// both sides changed (keeping the local value), or None if the stored
+    /// cluster can't be read.
+    pub fn merge_disk_changes(&mut self) -> Option<Vec<Conflict>> {
+        let base = Self::parse(&self.disk_snapshot.borrow()).map(|list| list.todos).unwrap_or_default();
+        let local = std::mem::take(&mut self.todos);
+        if !self.reload() {
+            self.todos = local;
+            return None;
+        }
+        let result = merge(&base, &local, &self.todos);
+        self.todos = result.todos;
+        self.save();
+        Some(result.conflicts)
+    }
+
+    /// Store the cluster, failing if it's read-only, changed on disk since it
+    /// was read, or can't be written
+    pub fn try_save(&self) -> Result<(), String> {
+        if self.cluster_name.is_empty() {
+            return Err("no cluster to save to".to_string());
+        }
+        if Self::is_archive(&self.cluster_name) {
+            return Err(format!("'{}' is a read-only archive", self.cluster_name));
+        }
+        if self.changed_on_disk() {
+            return Err(format!("'{}' changed on disk since it was read", self.cluster_name));
+        }
+        self.write()
+    }
+
+    /// Hand the backend the tasks changed since the last read or write
+    fn write(&self) -> Result<(), String> {
+        if let Some(err) = &self.load_error {
+            return Err(err.clone());
+        }
+        let rows: HashMap<String, TaskRow> = task_rows(&self.todos).into_iter().map(|row| (row.todo.id.clone(), row)).collect();
+        let stored = self.stored_rows.borrow();
+        let changed: Vec<&TaskRow> = rows.values().filter(|row| stored.get(&row.todo.id) != Some(*row)).collect();
+        let removed: Vec<&str> = stored.keys().filter(|id| !rows.contains_key(*id)).map(String::as_str).collect();
+        let saved = with_storage(|storage| storage.upsert(&self.cluster_name, &self.todos, &changed, &removed));
+        drop(stored);
+        saved?;
+        *self.disk_snapshot.borrow_mut() = serde_json::to_string_pretty(self).unwrap_or_default();
+        *self.stored_rows.borrow_mut() = rows;
+        Ok(())
+    }
+
+    /// Whether a cluster is stored encrypted
+    pub fn is_encrypted(cluster_name: &str) -> bool {
+        with_storage(|storage| storage.envelope(cluster_name)).is_some()
+    }
+
+    /// Check the passphrase of an encrypted cluster and keep its key for the
+    /// rest of the run, so the cluster and its archive can be loaded
+    pub fn unlock(cluster_name: &str, passphrase: &str) -> Result<(), String> {
+        let envelope = with_storage(|storage| storage.envelope(cluster_name))
+            .ok_or_else(|| format!("'{}' is not encrypted", cluster_name))?;
+        crypto::remember(cluster_name, Key::for_envelope(passphrase, &envelope)?);
+        Ok(())
+    }
+
+    /// Store this cluster and its archive encrypted with a passphrase from now on
+    pub fn encrypt(&self, passphrase: &str) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err("Can't encrypt a read-only cluster".to_string());
+        }
+        if !with_storage(|storage| storage.supports_encryption()) {
+            return Err("Encryption needs the json storage backend".to_string());
+        }
+        let key = Key::generate(passphrase)?;
+        let archive = self.own_archive()?;
+        crypto::remember(&self.cluster_name, key);
+        let stored = self.store_with_archive(archive.as_ref());
+        if stored.is_err() {
+            crypto::forget(&self.cluster_name);
+        }
+        self.save_merge_base();
+        stored
+    }
+
+    /// Store this cluster and its archive in plain text again
+    pub fn decrypt(&self) -> Result<(), String> {
+        if crypto::key_for(&self.cluster_name).is_none() {
+            return Err(format!("'{}' is not encrypted", self.cluster_name));
+        }
+        if self.is_read_only() {
+            return Err("Can't decrypt a read-only cluster".to_string());
+        }
+        let archive = self.own_archive()?;
+        crypto::forget(&self.cluster_name);
+        self.store_with_archive(archive.as_ref())?;
+        self.save_merge_base();
+        Ok(())
+    }
+
+    /// This cluster's archive, if it has one
+    fn own_archive(&self) -> Result<Option<TodoList>, String> {
+        let name = format!("{}{}", self.cluster_name, ARCHIVE_SUFFIX);
+        if !Self::exists(&name) {
+            return Ok(None);
+        }
+        let archive = Self::load(&name);
+        match archive.load_error {
+            Some(err) => Err(err),
+            None => Ok(Some(archive)),
+        }
+    }
+
+    /// Rewrite the whole cluster, and its archive, with the current key
+    fn store_with_archive(&self, archive: Option<&TodoList>) -> Result<(), String> {
+        for list in std::iter::once(self).chain(archive) {
+            with_storage(|storage| storage.save(&list.cluster_name, &list.todos))?;
+            list.mark_synced();
         }
+        Ok(())
     }
 
     pub fn cluster_name(&self) -> &str {
         &self.cluster_name
     }
 
-    /// List available clusters
+    /// Whether a cluster name refers to another cluster's archive
+    pub fn is_archive(name: &str) -> bool {
+        name.ends_with(ARCHIVE_SUFFIX)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        Self::is_archive(&self.cluster_name) || self.load_error.is_some()
+    }
+
+    /// List available clusters (archives are not clusters of their own)
     pub fn list_clusters() -> Vec<String> {
-        let dir = Self::data_dir();
-        let mut clusters = Vec::new();
-        if let Ok(entries) = fs::read_dir(dir) {
-            for entry in entries.flatten() {
-                if let Some(name) = entry.path().file_stem() {
-                    if let Some(ext) = entry.path().extension() {
-                        if ext == "json" {
-                            clusters.push(name.to_string_lossy().to_string());
-                        }
-                    }
-                }
-            }
-        }
-        clusters.sort();
+        let mut clusters = with_storage(|storage| storage.list_clusters());
+        clusters.retain(|name| !Self::is_archive(name));
         clusters
     }
 
+    /// Move closed tasks into the `<cluster>.archive` cluster, only those closed
+    /// before `cutoff` if given. Returns how many tasks were archived. The
+    /// archive is stored first; if either list can't be, nothing moves.
+    pub fn archive_done(&mut self, cutoff: Option<i64>) -> Result<usize, String> {
+        if self.is_read_only() {
+            return Err(format!("'{}' is read-only", self.cluster_name));
+        }
+        let mut archive = Self::load(&format!("{}{}", self.cluster_name, ARCHIVE_SUFFIX));
+        if let Some(err) = archive.load_error.take() {
+            return Err(err);
+        }
+        let (todos, archived) = (self.todos.clone(), archive.todos.clone());
+        let count = self.archive_closed(&mut archive, cutoff);
+        if count == 0 {
+            return Ok(0);
+        }
+        if let Err(err) = archive.write() {
+            self.todos = todos;
+            return Err(err);
+        }
+        if let Err(err) = self.try_save() {
+            self.todos = todos;
+            archive.todos = archived;
+            archive.write().ok();
+            return Err(err);
+        }
+        Ok(count)
+    }
+
+    /// Move closed tasks into `archive`, each under copies of its open
+    /// ancestors so it can be put back where it was
+    pub fn archive_closed(&mut self, archive: &mut TodoList, cutoff: Option<i64>) -> usize {
+        let mut moved = Vec::new();
+        let is_old = |todo: &Todo| {
+            // Tasks closed before completion times were recorded count as old
+            cutoff.is_none_or(|cutoff| todo.completed_at.is_none_or(|t| t < cutoff))
+        };
+        extract_tasks(
+            &mut self.todos,
+            &mut Vec::new(),
+            &|todo| todo.status.is_closed() && !todo.is_section && !todo.is_timing() && is_old(todo),
+            &mut moved,
+        );
+        let count = moved.len();
+        for (ancestors, todo) in moved {
+            insert_under(&mut archive.todos, ancestors, todo);
+        }
+        count
+    }
+
+    /// Move an archived task (viewed in `archive`) back into this cluster,
+    /// recreating any of its ancestors that no longer exist. This cluster is
+    /// stored first; if either list can't be, nothing moves.
+    pub fn restore_archived(&mut self, archive: &mut TodoList, id: &str) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err(format!("'{}' is read-only", self.cluster_name));
+        }
+        let (todos, archived) = (self.todos.clone(), archive.todos.clone());
+        let mut moved = Vec::new();
+        extract_tasks(&mut archive.todos, &mut Vec::new(), &|todo| todo.id == id, &mut moved);
+        if moved.is_empty() {
+            return Err("no such task in the archive".to_string());
+        }
+        prune_ancestor_copies(&mut archive.todos);
+        for (ancestors, todo) in moved {
+            insert_under(&mut self.todos, ancestors, todo);
+        }
+        if let Err(err) = self.try_save() {
+            self.todos = todos;
+            archive.todos = archived;
+            return Err(err);
+        }
+        if let Err(err) = archive.write() {
+            self.todos = todos;
+            archive.todos = archived;
+            self.write().ok();
+            return Err(err);
+        }
+        Ok(())
+    }
+
     /// Toggle fold state for a todo by ID
     pub fn toggle_fold(&mut self, id: &str) {
         if self.folded_ids.contains(id) {
@@ -175,15 +656,64 @@ impl TodoList {
         self.folded_ids.contains(id)
     }
 
-    /// Get a flattened list of all todos with depth info, respecting fold state
+    /// Get a flattened list of all todos with depth info, respecting fold state.
+    /// When hoisted, only the hoisted task's subtasks are included; paths stay
+    /// absolute while depth and hierarchy path are relative to the hoisted task.
     pub fn flatten(&self) -> Vec<FlatTodo> {
         let mut result = Vec::new();
-        for (i, todo) in self.todos.iter().enumerate() {
-            self.flatten_recursive(todo, 0, vec![i], Vec::new(), &mut result);
+        match self.hoisted_root() {
+            Some((root_path, root)) => {
+                for (i, todo) in root.subtasks.iter().enumerate() {
+                    let mut path = root_path.clone();
+                    path.push(i);
+                    self.flatten_recursive(todo, 0, path, Vec::new(), &mut result);
+                }
+            }
+            None => {
+                for (i, todo) in self.todos.iter().enumerate() {
+                    self.flatten_recursive(todo, 0, vec![i], Vec::new(), &mut result);
+                }
+            }
         }
         result
     }
 
+    /// Show only the subtree of the task with the given id
+    pub fn hoist(&mut self, id: &str) {
+        if self.path_of(id).is_some() {
+            self.hoisted_id = Some(id.to_string());
+        }
+    }
+
+    /// Hoist the parent of the current hoisted task, or show the whole cluster
+    /// if it is top-level. Returns the id of the task that was hoisted.
+    pub fn unhoist(&mut self) -> Option<String> {
+        let (root_path, root) = self.hoisted_root()?;
+        let previous = root.id.clone();
+        self.hoisted_id = self
+            .get_at_path(&root_path[..root_path.len() - 1])
+            .map(|parent| parent.id.clone());
+        Some(previous)
+    }
+
+    /// Path and task of the hoisted root, if hoisted and the task still exists
+    pub fn hoisted_root(&self) -> Option<(Vec<usize>, &Todo)> {
+        let path = self.path_of(self.hoisted_id.as_ref()?)?;
+        let todo = self.get_at_path(&path)?;
+        Some((path, todo))
+    }
+
+    /// Texts of the hoisted task and its ancestors, outermost first
+    pub fn hoist_breadcrumb(&self) -> Vec<String> {
+        let path = match self.hoisted_root() {
+            Some((path, _)) => path,
+            None => return Vec::new(),
+        };
+        (1..=path.len())
+            .filter_map(|len| self.get_at_path(&path[..len]).map(|t| t.text.clone()))
+            .collect()
+    }
+
     fn flatten_recursive(&self, todo: &Todo, depth: usize, path: Vec<usize>, hierarchy_path: Vec<String>, result: &mut Vec<FlatTodo>) {
         let is_folded = self.is_folded(&todo.id);
         let has_subtasks = todo.has_subtasks();
@@ -195,6 +725,8 @@ impl TodoList {
             has_subtasks,
             is_folded,
             hierarchy_path: hierarchy_path.clone(),
+            is_blocked: self.is_blocked(todo),
+            hidden_count: 0,
         });
 
         // Only include subtasks if not folded
@@ -210,6 +742,26 @@ impl TodoList {
         }
     }
 
+    /// Find the path of the task with the given id
+    pub fn path_of(&self, id: &str) -> Option<Vec<usize>> {
+        fn find(todos: &[Todo], id: &str, path: &mut Vec<usize>) -> bool {
+            for (i, todo) in todos.iter().enumerate() {
+                path.push(i);
+                if todo.id == id || find(&todo.subtasks, id, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = Vec::new();
+        if find(&self.todos, id, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
     /// Get mutable reference to todo at path
     fn get_mut_at_path(&mut self, path: &[usize]) -> Option<&mut Todo> {
         if path.is_empty() {
@@ -234,6 +786,104 @@ impl TodoList {
         Some(current)
     }
 
+    /// Find a todo anywhere in the tree by its id
+    pub fn find_by_id(&self, id: &str) -> Option<&Todo> {
+        fn find<'a>(todos: &'a [Todo], id: &str) -> Option<&'a Todo> {
+            for todo in todos {
+                if todo.id == id {
+                    return Some(todo);
+                }
+                if let Some(found) = find(&todo.subtasks, id) {
+                    return Some(found);
+                }
+            }
+            None
+        }
+        find(&self.todos, id)
+    }
+
+    /// Resolve a (case-insensitive) id prefix to a full task id
+    pub fn resolve_id_prefix(&self, prefix: &str) -> Result<String, DependencyError> {
+        fn collect(todos: &[Todo], prefix: &str, matches: &mut Vec<String>) {
+            for todo in todos {
+                if todo.id.starts_with(prefix) {
+                    matches.push(todo.id.clone());
+                }
+                collect(&todo.subtasks, prefix, matches);
+            }
+        }
+        let prefix = prefix.trim().to_lowercase();
+        let mut matches = Vec::new();
+        if !prefix.is_empty() {
+            collect(&self.todos, &prefix, &mut matches);
+        }
+        match matches.len() {
+            0 => Err(DependencyError::NotFound(prefix)),
+            1 => Ok(matches.remove(0)),
+            _ => Err(DependencyError::Ambiguous(prefix)),
+        }
+    }
+
+    /// A task is blocked while any of its dependencies is still open.
+    /// Dependencies on tasks that no longer exist are ignored.
+    pub fn is_blocked(&self, todo: &Todo) -> bool {
+        todo.depends_on
+            .iter()
+            .filter_map(|id| self.find_by_id(id))
+            .any(|dep| !dep.status.is_closed())
+    }
+
+    /// Returns the task that would close a loop if `task_id` started depending
+    /// on `dep_id`, i.e. a task reachable from `dep_id` that is `task_id` itself
+    fn find_cycle(&self, task_id: &str, dep_id: &str) -> Option<&Todo> {
+        let mut stack = vec![dep_id.to_string()];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            let todo = match self.find_by_id(&id) {
+                Some(t) => t,
+                None => continue,
+            };
+            if todo.depends_on.iter().any(|d| d == task_id) {
+                return Some(todo);
+            }
+            stack.extend(todo.depends_on.iter().cloned());
+        }
+        None
+    }
+
+    /// Make the task at path depend on the task identified by `id_prefix`.
+    /// Returns the full id of the dependency.
+    pub fn add_dependency_at_path(&mut self, path: &[usize], id_prefix: &str) -> Result<String, DependencyError> {
+        let dep_id = self.resolve_id_prefix(id_prefix)?;
+        let task_id = match self.get_at_path(path) {
+            Some(t) => t.id.clone(),
+            None => return Err(DependencyError::NotFound(id_prefix.to_string())),
+        };
+        if dep_id == task_id {
+            return Err(DependencyError::SelfDependency);
+        }
+        if let Some(todo) = self.find_cycle(&task_id, &dep_id) {
+            return Err(DependencyError::Cycle(todo.text.clone()));
+        }
+        if let Some(todo) = self.get_mut_at_path(path) {
+            if !todo.depends_on.contains(&dep_id) {
+                todo.depends_on.push(dep_id.clone());
+                self.save();
+            }
+        }
+        Ok(dep_id)
+    }
+
+    pub fn clear_dependencies_at_path(&mut self, path: &[usize]) {
+        if let Some(todo) = self.get_mut_at_path(path) {
+            todo.depends_on.clear();
+            self.save();
+        }
+    }
+
     /// Get the parent's subtask list and the index within it
     fn get_parent_list_mut(&mut self, path: &[usize]) -> Option<(&mut Vec<Todo>, usize)> {
         if path.is_empty() {
@@ -249,9 +899,15 @@ impl TodoList {
         }
     }
 
+    /// Add a task at the top level, or under the hoisted task when hoisted
     pub fn add(&mut self, todo: Todo) {
-        self.todos.push(todo);
-        self.save();
+        match self.hoisted_root() {
+            Some((path, _)) => self.add_subtask(&path, todo),
+            None => {
+                self.todos.push(todo);
+                self.save();
+            }
+        }
     }
 
     pub fn add_subtask(&mut self, path: &[usize], subtask: Todo) {
@@ -276,29 +932,107 @@ impl TodoList {
         }
     }
 
-    pub fn remove_at_path(&mut self, path: &[usize]) {
-        if let Some((list, idx)) = self.get_parent_list_mut(path) {
-            if idx < list.len() {
-                list.remove(idx);
-                self.save();
+    pub fn set_due_date_at_path(&mut self, path: &[usize], due_date: Option<NaiveDate>) {
+        if let Some(todo) = self.get_mut_at_path(path) {
+            todo.due_date = due_date;
+            self.save();
+        }
+    }
+
+    /// Move a task's due date by the given number of days.
+    /// Tasks without a due date are treated as due today.
+    pub fn shift_due_date_at_path(&mut self, path: &[usize], days: i64) -> Option<NaiveDate> {
+        let todo = self.get_mut_at_path(path)?;
+        let base = todo.due_date.unwrap_or_else(|| Local::now().date_naive());
+        let new_date = base + Duration::days(days);
+        todo.due_date = Some(new_date);
+        self.save();
+        Some(new_date)
+    }
+
+    /// Remove the task at path with its subtree, returning it
+    pub fn remove_at_path(&mut self, path: &[usize]) -> Option<Todo> {
+        let (list, idx) = self.get_parent_list_mut(path)?;
+        if idx >= list.len() {
+            return None;
+        }
+        let removed = list.remove(idx);
+        self.save();
+        Some(removed)
+    }
+
+    /// Put a removed task back at `index` under the task with `parent_id`,
+    /// or at the end of the top level if there is no such task anymore, and
+    /// store the cluster. Nothing changes if it can't be stored.
+    pub fn reinsert(&mut self, parent_id: Option<&str>, index: usize, todo: Todo) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err(format!("'{}' is read-only", self.cluster_name));
+        }
+        let id = todo.id.clone();
+        let parent_path = parent_id.and_then(|id| self.path_of(id));
+        match parent_path.and_then(|path| self.get_mut_at_path(&path)) {
+            Some(parent) => {
+                let index = index.min(parent.subtasks.len());
+                parent.subtasks.insert(index, todo);
+            }
+            None if parent_id.is_none() => {
+                let index = index.min(self.todos.len());
+                self.todos.insert(index, todo);
+            }
+            None => self.todos.push(todo),
+        }
+        if let Err(err) = self.try_save() {
+            if let Some((list, index)) = self.path_of(&id).and_then(|path| self.get_parent_list_mut(&path)) {
+                list.remove(index);
             }
+            return Err(err);
         }
+        Ok(())
     }
 
-    pub fn toggle_at_path(&mut self, path: &[usize]) -> Option<usize> {
-        let is_completed = if let Some(todo) = self.get_mut_at_path(path) {
-            todo.toggle();
-            // Completed and abandoned are mutually exclusive
-            if todo.completed {
-                todo.abandoned = false;
+    /// Toggle between done and open. Returns the task's new index if it moved.
+    pub fn toggle_at_path(&mut self, path: &[usize], move_done_to_bottom: bool) -> Option<usize> {
+        let status = match self.get_at_path(path)?.status {
+            Status::Done => Status::Todo,
+            _ => Status::Done,
+        };
+        self.set_status_at_path(path, status, move_done_to_bottom)
+    }
+
+    /// Toggle between abandoned and open
+    pub fn abandon_at_path(&mut self, path: &[usize]) {
+        if let Some(todo) = self.get_at_path(path) {
+            let status = match todo.status {
+                Status::Abandoned => Status::Todo,
+                _ => Status::Abandoned,
+            };
+            // Abandoned tasks stay where they are
+            self.set_status_at_path(path, status, false);
+        }
+    }
+
+    /// Advance the task to the next status in the cycle.
+    /// Returns the task's new index if it moved.
+    pub fn cycle_status_at_path(&mut self, path: &[usize], move_done_to_bottom: bool) -> Option<usize> {
+        let status = self.get_at_path(path)?.status.next();
+        self.set_status_at_path(path, status, move_done_to_bottom)
+    }
+
+    /// Set a task's status. With `move_done_to_bottom`, tasks that become done
+    /// move to the bottom of their list; returns the new index in that case.
+    pub fn set_status_at_path(&mut self, path: &[usize], status: Status, move_done_to_bottom: bool) -> Option<usize> {
+        let was_done = {
+            let todo = self.get_mut_at_path(path)?;
+            let was_done = todo.is_done();
+            if status != todo.status {
+                todo.completed_at = status.is_closed().then(|| Utc::now().timestamp());
             }
-            todo.completed
-        } else {
-            return None;
+            todo.status = status;
+            was_done
         };
 
         // Move completed tasks to the bottom of their list
-        let new_index = if is_completed {
+        let new_index = if status == Status::Done && !was_done && move_done_to_bottom {
             if let Some((list, idx)) = self.get_parent_list_mut(path) {
                 let task = list.remove(idx);
                 list.push(task);
@@ -314,15 +1048,74 @@ impl TodoList {
         new_index
     }
 
-    pub fn abandon_at_path(&mut self, path: &[usize]) {
+    pub fn set_estimate_at_path(&mut self, path: &[usize], estimate: Option<u32>) {
         if let Some(todo) = self.get_mut_at_path(path) {
-            todo.abandoned = !todo.abandoned;
-            // If abandoning, also mark as not completed
-            if todo.abandoned {
-                todo.completed = false;
-            }
+            todo.estimate = estimate;
+            self.save();
+        }
+    }
+
+    /// Start or stop the timer on a task. Starting stops any other timer
+    /// running in this cluster. Returns true if the timer is now running.
+    pub fn toggle_timer_at_path(&mut self, path: &[usize]) -> Option<bool> {
+        let todo = self.get_at_path(path)?;
+        if todo.is_timing() {
+            self.stop_timer();
+            Some(false)
+        } else {
+            let id = todo.id.clone();
+            Some(self.start_timer(&id))
+        }
+    }
+
+    /// Start the timer on the task with the given id, stopping any other
+    /// running timer. Returns false if there is no such task.
+    pub fn start_timer(&mut self, id: &str) -> bool {
+        let path = match self.path_of(id) {
+            Some(p) => p,
+            None => return false,
+        };
+        self.stop_timer();
+        if let Some(todo) = self.get_mut_at_path(&path) {
+            todo.sessions.push(WorkSession { start: Utc::now().timestamp(), end: None });
         }
         self.save();
+        true
+    }
+
+    /// Stop whichever timer is running in this cluster
+    pub fn stop_timer(&mut self) {
+        fn stop_all(todos: &mut [Todo], now: i64) -> bool {
+            let mut stopped = false;
+            for todo in todos {
+                if let Some(session) = todo.sessions.last_mut() {
+                    if session.end.is_none() {
+                        session.end = Some(now);
+                        stopped = true;
+                    }
+                }
+                stopped |= stop_all(&mut todo.subtasks, now);
+            }
+            stopped
+        }
+        if stop_all(&mut self.todos, Utc::now().timestamp()) {
+            self.save();
+        }
+    }
+
+    /// The task whose timer is running, if any
+    pub fn running_timer(&self) -> Option<&Todo> {
+        fn find(todos: &[Todo]) -> Option<&Todo> {
+            todos.iter().find_map(|t| if t.is_timing() { Some(t) } else { find(&t.subtasks) })
+        }
+        find(&self.todos)
+    }
+
+    pub fn set_priority_at_path(&mut self, path: &[usize], priority: Priority) {
+        if let Some(todo) = self.get_mut_at_path(path) {
+            todo.priority = priority;
+            self.save();
+        }
     }
 
     pub fn move_up(&mut self, path: &[usize]) -> bool {
@@ -360,7 +1153,12 @@ impl TodoList {
     /// Sort tasks by priority (highest first), then by date (earliest first, None last),
     /// then alphabetically. Also recursively sorts subtasks.
     pub fn sort(&mut self) {
-        Self::sort_todos(&mut self.todos);
+        // When hoisted, only the visible subtree is sorted
+        let root_path = self.hoisted_root().map(|(path, _)| path);
+        match root_path.and_then(|path| self.get_mut_at_path(&path)) {
+            Some(root) => Self::sort_todos(&mut root.subtasks),
+            None => Self::sort_todos(&mut self.todos),
+        }
         self.save();
     }
 
@@ -379,14 +1177,10 @@ impl TodoList {
                 return a.is_section.cmp(&b.is_section);
             }
 
-            // Abandoned tasks sort last (after completed)
-            if a.abandoned != b.abandoned {
-                return a.abandoned.cmp(&b.abandoned);
-            }
-
-            // Completed tasks sort after incomplete tasks
-            if a.completed != b.completed {
-                return a.completed.cmp(&b.completed);
+            // In-progress work first, then open, blocked, completed, abandoned
+            let status_cmp = a.status.sort_order().cmp(&b.status.sort_order());
+            if status_cmp != std::cmp::Ordering::Equal {
+                return status_cmp;
             }
 
             // Priority (lower sort_order = higher priority = comes first)
@@ -411,3 +1205,301 @@ impl TodoList {
         });
     }
 }
+
+/// Remove the tasks matching `take` from a tree, collecting each along with
+/// childless copies of its ancestors
+fn extract_tasks(
+    todos: &mut Vec<Todo>,
+    ancestors: &mut Vec<Todo>,
+    take: &dyn Fn(&Todo) -> bool,
+    out: &mut Vec<(Vec<Todo>, Todo)>,
+) {
+    let mut i = 0;
+    while i < todos.len() {
+        if take(&todos[i]) {
+            out.push((ancestors.clone(), todos.remove(i)));
+        } else {
+            let mut copy = todos[i].clone();
+            copy.subtasks.clear();
+            ancestors.push(copy);
+            extract_tasks(&mut todos[i].subtasks, ancestors, take, out);
+            ancestors.pop();
+            i += 1;
+        }
+    }
+}
+
+/// Insert a task under its ancestor chain, reusing ancestors that exist by id
+fn insert_under(todos: &mut Vec<Todo>, ancestors: Vec<Todo>, todo: Todo) {
+    let mut level = todos;
+    for ancestor in ancestors {
+        let index = match level.iter().position(|t| t.id == ancestor.id) {
+            Some(index) => index,
+            None => {
+                level.push(ancestor);
+                level.len() - 1
+            }
+        };
+        level = &mut level[index].subtasks;
+    }
+    level.push(todo);
+}
+
+/// Drop open ancestor copies that no longer hold any archived task
+fn prune_ancestor_copies(todos: &mut Vec<Todo>) {
+    for todo in todos.iter_mut() {
+        prune_ancestor_copies(&mut todo.subtasks);
+    }
+    todos.retain(|todo| todo.status.is_closed() || !todo.subtasks.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trip() {
+        let mut todo = Todo::new("Task".to_string(), None, Priority::None);
+        todo.status = Status::InProgress;
+        let json = serde_json::to_value(&todo).unwrap();
+        assert!(json.get("completed").is_none());
+        let back: Todo = serde_json::from_value(json).unwrap();
+        assert_eq!(back.status, Status::InProgress);
+    }
+
+    #[test]
+    fn test_dependency_cycle_detected() {
+        let mut list = TodoList::default();
+        list.todos.push(Todo::new("A".to_string(), None, Priority::None));
+        list.todos.push(Todo::new("B".to_string(), None, Priority::None));
+        list.todos.push(Todo::new("C".to_string(), None, Priority::None));
+        let (a, b, c) = (list.todos[0].id.clone(), list.todos[1].id.clone(), list.todos[2].id.clone());
+
+        // B after A, C after B
+        list.todos[1].depends_on.push(a.clone());
+        list.todos[2].depends_on.push(b.clone());
+        assert!(list.is_blocked(&list.todos[1]));
+
+        // A after C would close the loop A -> C -> B -> A
+        assert!(list.find_cycle(&a, &c).is_some());
+        assert!(list.find_cycle(&c, &a).is_none());
+
+        list.todos[0].status = Status::Done;
+        assert!(!list.is_blocked(&list.todos[1]));
+    }
+
+    #[test]
+    fn test_sort_respects_status() {
+        let mut todos = vec![
+            Todo::new("done".to_string(), None, Priority::Max),
+            Todo::new("open".to_string(), None, Priority::None),
+            Todo::new("active".to_string(), None, Priority::None),
+            Todo::new("blocked".to_string(), None, Priority::High),
+        ];
+        todos[0].status = Status::Done;
+        todos[2].status = Status::InProgress;
+        todos[3].status = Status::Blocked;
+        TodoList::sort_todos(&mut todos);
+        let order: Vec<&str> = todos.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(order, ["active", "open", "blocked", "done"]);
+    }
+
+    #[test]
+    fn test_completed_at_tracks_closing() {
+        let mut list = TodoList::default();
+        list.todos.push(Todo::new("Task".to_string(), None, Priority::None));
+        list.set_status_at_path(&[0], Status::InProgress, true);
+        assert!(list.todos[0].completed_at.is_none());
+        list.set_status_at_path(&[0], Status::Done, true);
+        assert!(list.todos[0].completed_at.is_some());
+        list.set_status_at_path(&[0], Status::Todo, true);
+        assert!(list.todos[0].completed_at.is_none());
+    }
+
+    #[test]
+    fn test_archive_keeps_parent_chain() {
+        let mut list = TodoList::default();
+        let mut project = Todo::new("Project".to_string(), None, Priority::None);
+        let mut done = Todo::new("Done".to_string(), None, Priority::None);
+        done.status = Status::Done;
+        done.completed_at = Some(1000);
+        let mut recent = Todo::new("Recent".to_string(), None, Priority::None);
+        recent.status = Status::Abandoned;
+        recent.completed_at = Some(5000);
+        project.subtasks.push(done);
+        project.subtasks.push(recent);
+        project.subtasks.push(Todo::new("Open".to_string(), None, Priority::None));
+        list.todos.push(project);
+
+        // Only tasks closed before the cutoff move
+        let mut archive = TodoList::default();
+        assert_eq!(list.archive_closed(&mut archive, Some(2000)), 1);
+        assert_eq!(list.todos[0].subtasks.len(), 2);
+        assert_eq!(archive.todos[0].text, "Project");
+        assert_eq!(archive.todos[0].subtasks[0].text, "Done");
+
+        // The parent copy is reused for later archiving
+        assert_eq!(list.archive_closed(&mut archive, None), 1);
+        assert_eq!(archive.todos.len(), 1);
+        assert_eq!(archive.todos[0].subtasks.len(), 2);
+        assert_eq!(list.todos[0].subtasks[0].text, "Open");
+
+        assert!(TodoList::is_archive("work.archive"));
+        assert!(!TodoList::is_archive("work"));
+    }
+
+    #[test]
+    fn test_archive_moves_only_what_is_stored() {
+        let mut list = TodoList::load("archive-move-test");
+        let mut done = Todo::new("Done".to_string(), None, Priority::None);
+        done.status = Status::Done;
+        let id = done.id.clone();
+        list.todos.push(done);
+        list.save();
+
+        // An archive that can't be read takes nothing
+        fs::write(TodoList::cluster_path("archive-move-test.archive"), "not json").unwrap();
+        assert!(list.archive_done(None).is_err());
+        assert_eq!(list.todos.len(), 1);
+
+        fs::remove_file(TodoList::cluster_path("archive-move-test.archive")).ok();
+        assert_eq!(list.archive_done(None), Ok(1));
+        assert!(TodoList::load("archive-move-test").todos.is_empty());
+
+        // Nor does a cluster changed on disk get the task back
+        let mut archive = TodoList::load("archive-move-test.archive");
+        let mut other = TodoList::load("archive-move-test");
+        other.todos.push(Todo::new("Elsewhere".to_string(), None, Priority::None));
+        other.save();
+        assert!(list.restore_archived(&mut archive, &id).is_err());
+        assert_eq!(archive.todos.len(), 1);
+        assert_eq!(TodoList::load("archive-move-test.archive").todos.len(), 1);
+
+        let mut list = TodoList::load("archive-move-test");
+        list.restore_archived(&mut archive, &id).unwrap();
+        assert_eq!(TodoList::load("archive-move-test").todos.len(), 2);
+        assert!(TodoList::load("archive-move-test.archive").todos.is_empty());
+    }
+
+    #[test]
+    fn test_reinsert_restores_position() {
+        let mut list = TodoList::load("reinsert-test");
+        let mut parent = Todo::new("Parent".to_string(), None, Priority::None);
+        parent.subtasks.push(Todo::new("First".to_string(), None, Priority::None));
+        parent.subtasks.push(Todo::new("Second".to_string(), None, Priority::None));
+        let parent_id = parent.id.clone();
+        list.todos.push(parent);
+
+        let removed = list.remove_at_path(&[0, 0]).unwrap();
+        assert_eq!(removed.text, "First");
+        list.reinsert(Some(&parent_id), 0, removed).unwrap();
+        assert_eq!(list.todos[0].subtasks[0].text, "First");
+
+        // Without its parent the task goes to the end of the top level
+        let child = list.remove_at_path(&[0, 1]).unwrap();
+        list.remove_at_path(&[0]);
+        list.todos.push(Todo::new("Other".to_string(), None, Priority::None));
+        list.reinsert(Some(&parent_id), 1, child).unwrap();
+        assert_eq!(list.todos.len(), 2);
+        assert_eq!(list.todos[1].text, "Second");
+        assert_eq!(TodoList::load("reinsert-test").todos.len(), 2);
+
+        // A cluster that can't be stored is left as it was
+        let mut unnamed = TodoList::default();
+        assert!(unnamed.reinsert(None, 0, Todo::new("Lost".to_string(), None, Priority::None)).is_err());
+        assert!(unnamed.todos.is_empty());
+    }
+
+    #[test]
+    fn test_unnamed_list_is_never_written() {
+        let mut list = TodoList::default();
+        list.todos.push(Todo::new("Nowhere".to_string(), None, Priority::None));
+        list.save();
+        assert!(list.try_save().is_err());
+        assert!(!TodoList::cluster_path("").exists());
+    }
+
+    #[test]
+    fn test_save_keeps_changes_made_on_disk() {
+        let mut list = TodoList::load("disk-merge-test");
+        list.todos.push(Todo::new("Mine".to_string(), None, Priority::None));
+        list.todos.push(Todo::new("Theirs".to_string(), None, Priority::None));
+        list.save();
+
+        // Another writer changes one task before the file monitor tells us
+        let mut other = TodoList::load("disk-merge-test");
+        other.todos[1].text = "Theirs, edited".to_string();
+        other.save();
+        list.todos[0].text = "Mine, edited".to_string();
+        list.save();
+        assert_eq!(TodoList::load("disk-merge-test").todos[1].text, "Theirs, edited");
+        assert!(list.has_unsaved_changes());
+
+        assert_eq!(list.merge_disk_changes(), Some(Vec::new()));
+        let stored = TodoList::load("disk-merge-test");
+        assert_eq!(stored.todos[0].text, "Mine, edited");
+        assert_eq!(stored.todos[1].text, "Theirs, edited");
+        assert!(!list.changed_on_disk());
+    }
+
+    #[test]
+    fn test_unsaved_changes_tracked() {
+        let mut list = TodoList::default();
+        list.mark_synced();
+        assert!(!list.has_unsaved_changes());
+        list.todos.push(Todo::new("Task".to_string(), None, Priority::None));
+        assert!(list.has_unsaved_changes());
+        // Fold state is not part of the file
+        list.mark_synced();
+        let id = list.todos[0].id.clone();
+        list.toggle_fold(&id);
+        assert!(!list.has_unsaved_changes());
+    }
+
+    #[test]
+    fn test_hoist_shows_subtree() {
+        let mut list = TodoList::default();
+        let mut project = Todo::new("Project".to_string(), None, Priority::None);
+        let mut phase = Todo::new("Phase".to_string(), None, Priority::None);
+        phase.subtasks.push(Todo::new("Step".to_string(), None, Priority::None));
+        project.subtasks.push(phase);
+        list.todos.push(Todo::new("Other".to_string(), None, Priority::None));
+        list.todos.push(project);
+        let phase_id = list.todos[1].subtasks[0].id.clone();
+
+        list.hoist(&phase_id);
+        let flat = list.flatten();
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].todo.text, "Step");
+        assert_eq!(flat[0].path, vec![1, 0, 0]);
+        assert_eq!(flat[0].depth, 0);
+        assert_eq!(list.hoist_breadcrumb(), ["Project", "Phase"]);
+
+        // Going up one level hoists the parent, then the whole cluster
+        assert_eq!(list.unhoist(), Some(phase_id));
+        assert_eq!(list.hoist_breadcrumb(), ["Project"]);
+        list.unhoist();
+        assert!(list.hoisted_root().is_none());
+        assert_eq!(list.flatten().len(), 4);
+    }
+
+    #[test]
+    fn test_estimate_and_time_roll_up() {
+        let mut parent = Todo::new("Parent".to_string(), None, Priority::None);
+        let mut child = Todo::new("Child".to_string(), None, Priority::None);
+        child.estimate = Some(90);
+        child.sessions.push(WorkSession { start: 0, end: Some(1200) });
+        parent.estimate = Some(30);
+        parent.sessions.push(WorkSession { start: 5000, end: Some(8000) });
+        parent.subtasks.push(child);
+        parent.subtasks.push(Todo::new("No estimate".to_string(), None, Priority::None));
+
+        assert_eq!(parent.total_estimate(), Some(120));
+        assert_eq!(parent.total_tracked(), 4200);
+        // Only the part of a session inside the range counts
+        assert_eq!(parent.tracked_between(600, 6000), 1600);
+        assert_eq!(format_minutes(80), "1h20");
+        assert_eq!(format_minutes(120), "2h");
+        assert_eq!(format_minutes(45), "45m");
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::crypto::{self, Envelope, Key};
+use crate::paths;
+use crate::todo::{Todo, TodoList};
+
+/// How long deleted tasks are kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashConfig {
+    /// Deleted tasks older than this are purged (0 keeps them forever)
+    #[serde(default = "default_purge_after_days")]
+    pub purge_after_days: u32,
+}
+
+fn default_purge_after_days() -> u32 {
+    30
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            purge_after_days: default_purge_after_days(),
+        }
+    }
+}
+
+impl TrashConfig {
+    pub const FILE: &'static str = "trash.json";
+
+    /// Read `trash.json` from the config dir, creating it with the defaults if it's missing
+    pub fn try_load() -> Result<Self, String> {
+        paths::load_config(Self::FILE)
+    }
+}
+
+/// A deleted task, with its subtree, and where it was deleted from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedTask {
+    pub todo: Todo,
+    pub cluster: String,
+    /// Id of the parent task, None for a top-level task
+    pub parent_id: Option<String>,
+    /// Texts of the task's ancestors, outermost first
+    #[serde(default)]
+    pub parent_path: Vec<String>,
+    /// Position among its siblings
+    pub index: usize,
+    pub deleted_at: i64,
+}
+
+/// A deleted task of an encrypted cluster, stored sealed with the cluster's key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SealedTask {
+    cluster: String,
+    deleted_at: i64,
+    /// The `TrashedTask` as JSON
+    envelope: Envelope,
+}
+
+impl SealedTask {
+    fn seal(item: &TrashedTask, key: &Key) -> Option<Self> {
+        let json = serde_json::to_vec(item).ok()?;
+        Some(Self {
+            cluster: item.cluster.clone(),
+            deleted_at: item.deleted_at,
+            envelope: key.seal(&json).ok()?,
+        })
+    }
+
+    fn open(&self, key: &Key) -> Option<TrashedTask> {
+        serde_json::from_slice(&key.open(&self.envelope).ok()?).ok()
+    }
+}
+
+/// Deleted tasks of all clusters, kept in `trash/trash.json` in the data dir
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Trash {
+    /// Deleted tasks, including those of encrypted clusters unlocked in this run
+    pub items: Vec<TrashedTask>,
+    /// Deleted tasks of encrypted clusters not unlocked in this run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sealed: Vec<SealedTask>,
+    /// Why trash.json couldn't be read; such a trash is never saved
+    #[serde(skip)]
+    load_error: Option<String>,
+}
+
+impl Trash {
+    /// Kept out of the data dir itself so it is not taken for a cluster
+    fn path() -> PathBuf {
+        let dir = TodoList::data_dir().join("trash");
+        fs::create_dir_all(&dir).ok();
+        dir.join("trash.json")
+    }
+
+    /// Load the trash, purging tasks older than the configured age. Tasks of
+    /// encrypted clusters are only in `items` once the cluster is unlocked.
+    /// A trash.json that can't be read comes back empty with `load_error`
+    /// set, and is set aside like a cluster file if it doesn't parse.
+    pub fn load() -> Self {
+        let path = Self::path();
+        let mut trash: Self = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(trash) => trash,
+                Err(err) => {
+                    let mut message = format!("Can't read trash.json: {}", err);
+                    let name = format!("trash.json.invalid-{}", Utc::now().timestamp());
+                    if fs::rename(&path, path.with_file_name(&name)).is_ok() {
+                        message.push_str(&format!("; moved to {}", name));
+                    }
+                    Self { load_error: Some(message), ..Self::default() }
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => Self {
+                load_error: Some(format!("Can't read trash.json: {}", err)),
+                ..Self::default()
+            },
+        };
+        // Nothing is purged while the configured age can't be read
+        let days = TrashConfig::try_load().map_or(0, |config| config.purge_after_days);
+        if days > 0 {
+            let cutoff = Utc::now().timestamp() - days as i64 * 86400;
+            let before = trash.items.len() + trash.sealed.len();
+            trash.items.retain(|item| item.deleted_at >= cutoff);
+            trash.sealed.retain(|item| item.deleted_at >= cutoff);
+            if trash.items.len() + trash.sealed.len() != before {
+                trash.save().ok();
+            }
+        }
+
+        let mut locked = Vec::new();
+        for sealed in std::mem::take(&mut trash.sealed) {
+            match crypto::key_for(&sealed.cluster).and_then(|key| sealed.open(&key)) {
+                Some(item) => trash.items.push(item),
+                None => locked.push(sealed),
+            }
+        }
+        trash.sealed = locked;
+        trash.items.sort_by_key(|item| item.deleted_at);
+        trash
+    }
+
+    /// Why trash.json couldn't be read, if it couldn't
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Write the trash, sealing the tasks of encrypted clusters. A trash
+    /// that couldn't be read is not written over what's on disk.
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(err) = &self.load_error {
+            return Err(err.clone());
+        }
+        let mut stored = Self { items: Vec::new(), sealed: self.sealed.clone(), load_error: None };
+        for item in &self.items {
+            match crypto::key_for(&item.cluster) {
+                // A task that can't be sealed is dropped rather than written in plain text
+                Some(key) => stored.sealed.extend(SealedTask::seal(item, &key)),
+                None => stored.items.push(item.clone()),
+            }
+        }
+        let json = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+        fs::write(Self::path(), json).map_err(|e| e.to_string())
+    }
+
+    /// Delete the task at path from a cluster, moving it into the trash. The
+    /// task stays in the cluster unless the trash is stored with it.
+    pub fn delete_at_path(list: &mut TodoList, path: &[usize]) -> Result<(), String> {
+        let (parent_path, index) = match path.split_last() {
+            Some((&index, parent_path)) => (parent_path, index),
+            None => return Err("no task selected".to_string()),
+        };
+        let parent_id = list.get_at_path(parent_path).map(|t| t.id.clone());
+        let ancestors = (1..=parent_path.len())
+            .filter_map(|len| list.get_at_path(&parent_path[..len]).map(|t| t.text.clone()))
+            .collect();
+        let todo = match list.get_at_path(path) {
+            Some(todo) => todo.clone(),
+            None => return Err("no task selected".to_string()),
+        };
+        let mut trash = Self::load();
+        trash.items.push(TrashedTask {
+            todo,
+            cluster: list.cluster_name().to_string(),
+            parent_id,
+            parent_path: ancestors,
+            index,
+            deleted_at: Utc::now().timestamp(),
+        });
+        trash.save()?;
+        list.remove_at_path(path);
+        Ok(())
+    }
+
+    /// Put an item back into `list`, which must be the item's cluster. It
+    /// leaves the trash only once the cluster is stored with it.
+    pub fn restore(&mut self, item: usize, list: &mut TodoList) -> Result<(), String> {
+        let trashed = match self.items.get(item) {
+            Some(trashed) if trashed.cluster == list.cluster_name() => trashed,
+            _ => return Err("not a task of this cluster".to_string()),
+        };
+        list.reinsert(trashed.parent_id.as_deref(), trashed.index, trashed.todo.clone())?;
+        self.items.remove(item);
+        // Left in the trash, the task could only be restored twice
+        self.save().ok();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Priority;
+
+    #[test]
+    fn test_encrypted_cluster_trash_is_sealed() {
+        let cluster = "trash-crypto-test";
+        crypto::remember(cluster, Key::generate("secret").unwrap());
+        let mut list = TodoList::load(cluster);
+        list.todos.push(Todo::new("Hidden task".to_string(), None, Priority::None));
+        Trash::delete_at_path(&mut list, &[0]).unwrap();
+
+        let stored = fs::read_to_string(Trash::path()).unwrap();
+        assert!(!stored.contains("Hidden task"));
+        assert!(Trash::load().items.iter().any(|item| item.todo.text == "Hidden task"));
+
+        // Locked again, the task stays in the trash but out of sight
+        let key = crypto::key_for(cluster).unwrap();
+        crypto::forget(cluster);
+        let trash = Trash::load();
+        assert!(!trash.items.iter().any(|item| item.cluster == cluster));
+        trash.save().unwrap();
+        crypto::remember(cluster, key);
+        let mut trash = Trash::load();
+        let item = trash.items.iter().position(|item| item.cluster == cluster).unwrap();
+        trash.restore(item, &mut list).unwrap();
+        assert_eq!(list.todos[0].text, "Hidden task");
+    }
+
+    #[test]
+    fn test_restore_keeps_task_until_stored() {
+        let cluster = "trash-restore-test";
+        let mut list = TodoList::load(cluster);
+        list.todos.push(Todo::new("Keep me".to_string(), None, Priority::None));
+        list.save();
+        Trash::delete_at_path(&mut list, &[0]).unwrap();
+
+        // The cluster changed on disk, so it isn't saved over
+        let mut other = TodoList::load(cluster);
+        other.todos.push(Todo::new("Elsewhere".to_string(), None, Priority::None));
+        other.save();
+        let mut trash = Trash::load();
+        let item = trash.items.iter().position(|item| item.todo.text == "Keep me").unwrap();
+        assert!(trash.restore(item, &mut list).is_err());
+        assert!(list.todos.is_empty());
+        assert!(Trash::load().items.iter().any(|item| item.todo.text == "Keep me"));
+
+        let mut list = TodoList::load(cluster);
+        let mut trash = Trash::load();
+        let item = trash.items.iter().position(|item| item.todo.text == "Keep me").unwrap();
+        trash.restore(item, &mut list).unwrap();
+        assert_eq!(TodoList::load(cluster).todos.len(), 2);
+        assert!(!Trash::load().items.iter().any(|item| item.todo.text == "Keep me"));
+    }
+}
@@ -0,0 +1,343 @@
+use chrono::{Datelike, Local};
+use gtk4::prelude::*;
+use gtk4::{Box as GtkBox, Frame, Label, Orientation, ScrolledWindow};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::settings::Settings;
+use crate::todo::{Priority, Status, Todo, TodoList};
+
+/// What the board columns represent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoardGrouping {
+    Status,
+    Priority,
+}
+
+/// A single board column
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Column {
+    Status(Status),
+    Priority(Priority),
+}
+
+impl BoardGrouping {
+    fn columns(&self) -> Vec<Column> {
+        match self {
+            BoardGrouping::Status => vec![
+                Column::Status(Status::Todo),
+                Column::Status(Status::InProgress),
+                Column::Status(Status::Blocked),
+                Column::Status(Status::Done),
+                Column::Status(Status::Abandoned),
+            ],
+            BoardGrouping::Priority => vec![
+                Column::Priority(Priority::Max),
+                Column::Priority(Priority::High),
+                Column::Priority(Priority::Medium),
+                Column::Priority(Priority::Low),
+                Column::Priority(Priority::None),
+            ],
+        }
+    }
+}
+
+impl Column {
+    fn title(&self) -> &'static str {
+        match self {
+            Column::Status(status) => status.label(),
+            Column::Priority(Priority::Max) => "Max",
+            Column::Priority(Priority::High) => "High",
+            Column::Priority(Priority::Medium) => "Medium",
+            Column::Priority(Priority::Low) => "Low",
+            Column::Priority(Priority::None) => "None",
+        }
+    }
+
+    fn contains(&self, todo: &Todo) -> bool {
+        match self {
+            Column::Status(status) => todo.status == *status,
+            Column::Priority(p) => todo.priority == *p,
+        }
+    }
+
+    /// Update the task so that it belongs to this column
+    fn apply(&self, todos: &mut TodoList, path: &[usize]) {
+        match self {
+            Column::Status(status) => {
+                todos.set_status_at_path(path, *status, Settings::current().move_done_to_bottom);
+            }
+            Column::Priority(p) => todos.set_priority_at_path(path, *p),
+        }
+    }
+}
+
+struct Card {
+    path: Vec<usize>,
+    todo: Todo,
+}
+
+/// Board view state
+pub struct BoardState {
+    pub grouping: BoardGrouping,
+    selected_column: usize,
+    selected_card: usize,
+    columns_box: GtkBox,
+    cards: Vec<Vec<Card>>,
+}
+
+/// Create and populate the board view for a tab
+pub fn create_board_view(
+    scrolled_board: &ScrolledWindow,
+    board_state: &Rc<RefCell<Option<BoardState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    grouping: BoardGrouping,
+) {
+    let columns_box = GtkBox::new(Orientation::Horizontal, 8);
+    columns_box.set_homogeneous(true);
+    columns_box.set_margin_start(8);
+    columns_box.set_margin_end(8);
+    columns_box.set_margin_top(8);
+    columns_box.set_margin_bottom(8);
+
+    scrolled_board.set_child(Some(&columns_box));
+
+    let state = BoardState {
+        grouping,
+        selected_column: 0,
+        selected_card: 0,
+        columns_box,
+        cards: Vec::new(),
+    };
+    *board_state.borrow_mut() = Some(state);
+
+    refresh_board_view(board_state, todos);
+}
+
+/// Change what the columns represent, keeping the rest of the state
+pub fn set_board_grouping(
+    board_state: &Rc<RefCell<Option<BoardState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    grouping: BoardGrouping,
+) {
+    {
+        let mut state_ref = board_state.borrow_mut();
+        if let Some(state) = state_ref.as_mut() {
+            if state.grouping != grouping {
+                state.grouping = grouping;
+                state.selected_column = 0;
+                state.selected_card = 0;
+            }
+        }
+    }
+    refresh_board_view(board_state, todos);
+}
+
+/// Collect the cards for the board: top-level tasks, and tasks directly under
+/// top-level sections (sections themselves are not cards)
+fn collect_cards(todos: &TodoList) -> Vec<Card> {
+    let mut cards = Vec::new();
+    for (i, todo) in todos.todos.iter().enumerate() {
+        if todo.is_section {
+            for (j, subtask) in todo.subtasks.iter().enumerate() {
+                if !subtask.is_section {
+                    cards.push(Card { path: vec![i, j], todo: subtask.clone() });
+                }
+            }
+        } else {
+            cards.push(Card { path: vec![i], todo: todo.clone() });
+        }
+    }
+    cards
+}
+
+/// Refresh the board view from the tab's cluster
+pub fn refresh_board_view(
+    board_state: &Rc<RefCell<Option<BoardState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+) {
+    let mut state_ref = board_state.borrow_mut();
+    let state = match state_ref.as_mut() {
+        Some(s) => s,
+        None => return,
+    };
+
+    while let Some(child) = state.columns_box.first_child() {
+        state.columns_box.remove(&child);
+    }
+
+    let columns = state.grouping.columns();
+    let mut cards: Vec<Vec<Card>> = columns.iter().map(|_| Vec::new()).collect();
+    for card in collect_cards(&todos.borrow()) {
+        if let Some(index) = columns.iter().position(|c| c.contains(&card.todo)) {
+            cards[index].push(card);
+        }
+    }
+
+    // Keep the selection inside the board
+    if state.selected_column >= columns.len() {
+        state.selected_column = columns.len() - 1;
+    }
+    let count = cards[state.selected_column].len();
+    if state.selected_card >= count {
+        state.selected_card = count.saturating_sub(1);
+    }
+
+    let current_year = Local::now().year();
+    for (col_index, column) in columns.iter().enumerate() {
+        let column_box = GtkBox::new(Orientation::Vertical, 4);
+        column_box.add_css_class("board-column");
+        if col_index == state.selected_column {
+            column_box.add_css_class("board-column-selected");
+        }
+
+        let header = Label::new(Some(&format!("{} ({})", column.title(), cards[col_index].len())));
+        header.set_halign(gtk4::Align::Start);
+        header.add_css_class("board-column-header");
+        column_box.append(&header);
+
+        for (card_index, card) in cards[col_index].iter().enumerate() {
+            let frame = Frame::new(None);
+            frame.add_css_class("board-card");
+            if col_index == state.selected_column && card_index == state.selected_card {
+                frame.add_css_class("board-card-selected");
+            }
+
+            let card_box = GtkBox::new(Orientation::Vertical, 2);
+            card_box.set_margin_start(6);
+            card_box.set_margin_end(6);
+            card_box.set_margin_top(6);
+            card_box.set_margin_bottom(6);
+
+            let text_label = Label::new(Some(&card.todo.text));
+            text_label.set_xalign(0.0);
+            text_label.set_wrap(true);
+            text_label.add_css_class("board-card-text");
+            if card.todo.is_done() {
+                text_label.add_css_class("completed");
+            }
+            if card.todo.is_abandoned() {
+                text_label.add_css_class("abandoned-text");
+            }
+            card_box.append(&text_label);
+
+            // Priority, due date and subtask count
+            let meta_box = GtkBox::new(Orientation::Horizontal, 6);
+            let priority_label = Label::new(Some("●"));
+            match card.todo.priority {
+                Priority::Max => priority_label.add_css_class("priority-max"),
+                Priority::High => priority_label.add_css_class("priority-high"),
+                Priority::Medium => priority_label.add_css_class("priority-medium"),
+                Priority::Low => priority_label.add_css_class("priority-low"),
+                Priority::None => priority_label.add_css_class("priority-none"),
+            }
+            meta_box.append(&priority_label);
+            if let Some(due) = card.todo.due_date {
+                let date_str = if due.year() != current_year {
+                    due.format("%b %d, %Y").to_string()
+                } else {
+                    due.format("%b %d").to_string()
+                };
+                let date_label = Label::new(Some(&format!("→ {}", date_str)));
+                date_label.add_css_class("due-date");
+                meta_box.append(&date_label);
+            }
+            if card.todo.has_subtasks() {
+                let done = card.todo.subtasks.iter().filter(|t| t.is_done()).count();
+                let sub_label = Label::new(Some(&format!("{}/{}", done, card.todo.subtasks.len())));
+                sub_label.add_css_class("subtask-indicator");
+                meta_box.append(&sub_label);
+            }
+            card_box.append(&meta_box);
+
+            frame.set_child(Some(&card_box));
+            column_box.append(&frame);
+        }
+
+        state.columns_box.append(&column_box);
+    }
+
+    state.cards = cards;
+}
+
+/// Move the selection by columns and/or cards
+pub fn navigate_board(
+    board_state: &Rc<RefCell<Option<BoardState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta_columns: i32,
+    delta_cards: i32,
+) {
+    {
+        let mut state_ref = board_state.borrow_mut();
+        let state = match state_ref.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        let column = state.selected_column as i32 + delta_columns;
+        if column < 0 || column >= state.cards.len() as i32 {
+            return;
+        }
+        let count = state.cards[column as usize].len() as i32;
+        let card = if delta_columns != 0 {
+            // Keep roughly the same row when changing columns
+            (state.selected_card as i32).min(count - 1).max(0)
+        } else {
+            state.selected_card as i32 + delta_cards
+        };
+        if card < 0 || (count > 0 && card >= count) {
+            return;
+        }
+        state.selected_column = column as usize;
+        state.selected_card = card as usize;
+    }
+    refresh_board_view(board_state, todos);
+}
+
+/// Move the selected card to the neighbouring column, updating the task
+pub fn move_board_card(
+    board_state: &Rc<RefCell<Option<BoardState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta: i32,
+) {
+    let (target, path, task_id) = {
+        let state_ref = board_state.borrow();
+        let state = match state_ref.as_ref() {
+            Some(s) => s,
+            None => return,
+        };
+        let target = state.selected_column as i32 + delta;
+        if target < 0 || target >= state.cards.len() as i32 {
+            return;
+        }
+        let card = match state.cards[state.selected_column].get(state.selected_card) {
+            Some(c) => c,
+            None => return,
+        };
+        (target as usize, card.path.clone(), card.todo.id.clone())
+    };
+
+    let column = match board_state.borrow().as_ref() {
+        Some(state) => state.grouping.columns()[target],
+        None => return,
+    };
+    column.apply(&mut todos.borrow_mut(), &path);
+
+    // Follow the card to its new column
+    {
+        let mut state_ref = board_state.borrow_mut();
+        if let Some(state) = state_ref.as_mut() {
+            state.selected_column = target;
+        }
+    }
+    refresh_board_view(board_state, todos);
+
+    let index = board_state.borrow().as_ref().and_then(|state| {
+        state.cards[target].iter().position(|card| card.todo.id == task_id)
+    });
+    if let Some(index) = index {
+        if let Some(state) = board_state.borrow_mut().as_mut() {
+            state.selected_card = index;
+        }
+        refresh_board_view(board_state, todos);
+    }
+}
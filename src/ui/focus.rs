@@ -0,0 +1,147 @@
+use chrono::Utc;
+use gtk4::prelude::*;
+use gtk4::{gio, glib, Application, Label};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::focus::FocusConfig;
+use crate::todo::TodoList;
+
+/// Which part of the pomodoro cycle is running
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Work,
+    Break,
+}
+
+/// A running focus session on one task
+pub struct FocusSession {
+    pub task_id: String,
+    task_text: String,
+    todos: Rc<RefCell<TodoList>>,
+    config: FocusConfig,
+    phase: Phase,
+    phase_end: i64,
+    completed: u32,  // Work intervals finished so far
+    source: Option<glib::SourceId>,
+}
+
+/// Start a focus session on a task: a work interval with the timer running,
+/// then alternating breaks and work intervals until stopped
+pub fn start_focus(
+    focus: &Rc<RefCell<Option<FocusSession>>>,
+    app: Option<Application>,
+    focus_label: &Label,
+    todos: &Rc<RefCell<TodoList>>,
+    task_id: &str,
+    config: FocusConfig,
+) {
+    stop_focus(focus, focus_label);
+
+    let task_text = match todos.borrow().find_by_id(task_id) {
+        Some(todo) => todo.text.clone(),
+        None => return,
+    };
+    todos.borrow_mut().start_timer(task_id);
+
+    let phase_end = Utc::now().timestamp() + config.work_minutes as i64 * 60;
+    *focus.borrow_mut() = Some(FocusSession {
+        task_id: task_id.to_string(),
+        task_text,
+        todos: todos.clone(),
+        config,
+        phase: Phase::Work,
+        phase_end,
+        completed: 0,
+        source: None,
+    });
+    update_focus_label(focus, focus_label);
+    focus_label.set_visible(true);
+
+    let focus_for_tick = focus.clone();
+    let label_for_tick = focus_label.clone();
+    let source = glib::timeout_add_seconds_local(1, move || {
+        tick(&focus_for_tick, &label_for_tick, app.as_ref());
+        glib::ControlFlow::Continue
+    });
+    if let Some(session) = focus.borrow_mut().as_mut() {
+        session.source = Some(source);
+    }
+}
+
+/// Stop the focus session, logging the running work interval
+pub fn stop_focus(focus: &Rc<RefCell<Option<FocusSession>>>, focus_label: &Label) {
+    if let Some(mut session) = focus.borrow_mut().take() {
+        if let Some(source) = session.source.take() {
+            source.remove();
+        }
+        if session.phase == Phase::Work {
+            session.todos.borrow_mut().stop_timer();
+        }
+    }
+    focus_label.set_visible(false);
+}
+
+/// Id of the task being focused on, if any
+pub fn focused_task(focus: &Rc<RefCell<Option<FocusSession>>>) -> Option<String> {
+    focus.borrow().as_ref().map(|session| session.task_id.clone())
+}
+
+fn tick(focus: &Rc<RefCell<Option<FocusSession>>>, focus_label: &Label, app: Option<&Application>) {
+    let message = {
+        let mut focus_ref = focus.borrow_mut();
+        let session = match focus_ref.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        let now = Utc::now().timestamp();
+        if now < session.phase_end {
+            None
+        } else {
+            // Switch phase: log work against the task, pause it during breaks
+            match session.phase {
+                Phase::Work => {
+                    session.todos.borrow_mut().stop_timer();
+                    session.completed += 1;
+                    let minutes = session.config.break_after(session.completed);
+                    session.phase = Phase::Break;
+                    session.phase_end = now + minutes as i64 * 60;
+                    Some(format!("Take a {} minute break", minutes))
+                }
+                Phase::Break => {
+                    session.todos.borrow_mut().start_timer(&session.task_id);
+                    session.phase = Phase::Work;
+                    session.phase_end = now + session.config.work_minutes as i64 * 60;
+                    Some(format!("Back to '{}'", session.task_text))
+                }
+            }
+        }
+    };
+
+    if let (Some(body), Some(app)) = (message, app) {
+        let notification = gio::Notification::new("Zap focus");
+        notification.set_body(Some(&body));
+        app.send_notification(Some("zap-focus"), &notification);
+    }
+    update_focus_label(focus, focus_label);
+}
+
+fn update_focus_label(focus: &Rc<RefCell<Option<FocusSession>>>, focus_label: &Label) {
+    let focus_ref = focus.borrow();
+    let session = match focus_ref.as_ref() {
+        Some(s) => s,
+        None => return,
+    };
+    let remaining = (session.phase_end - Utc::now().timestamp()).max(0);
+    let countdown = format!("{:02}:{:02}", remaining / 60, remaining % 60);
+    match session.phase {
+        Phase::Work => {
+            focus_label.set_text(&format!("● {} {}", countdown, session.task_text));
+            focus_label.remove_css_class("focus-break");
+        }
+        Phase::Break => {
+            focus_label.set_text(&format!("☕ {} break", countdown));
+            focus_label.add_css_class("focus-break");
+        }
+    }
+}
@@ -1,3 +1,7 @@
+mod board;
+mod focus;
+mod session;
+mod trash;
 mod window;
 
 pub use window::ZapWindow;
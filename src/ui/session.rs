@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use super::window::DisplaySettings;
+use crate::todo::TodoList;
+
+/// Name of the session saved when the window closes and restored on launch
+pub const LAST_SESSION: &str = "last";
+
+/// Which view a tab was showing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionView {
+    #[default]
+    List,
+    Calendar,
+    Week,
+    Board,
+    BoardPriority,
+    Trash,
+}
+
+/// Saved state of one tab
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabSession {
+    /// Empty for a tab with no cluster loaded
+    pub cluster: String,
+    #[serde(default)]
+    pub view: SessionView,
+    /// Year and month shown in the calendar, if it was opened
+    #[serde(default)]
+    pub calendar_month: Option<(i32, u32)>,
+    #[serde(default)]
+    pub selected_id: Option<String>,
+    #[serde(default)]
+    pub display: DisplaySettings,
+}
+
+/// Open tabs of a window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<TabSession>,
+    /// Index of the tab that was in front
+    #[serde(default)]
+    pub current: usize,
+}
+
+impl Session {
+    fn sessions_dir() -> PathBuf {
+        let dir = TodoList::data_dir().join("sessions");
+        fs::create_dir_all(&dir).ok();
+        dir
+    }
+
+    /// Session names become file names, so keep them to a single path component
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+    }
+
+    pub fn load(name: &str) -> Option<Self> {
+        let path = Self::sessions_dir().join(format!("{}.json", name));
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, name: &str) {
+        let path = Self::sessions_dir().join(format!("{}.json", name));
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            fs::write(path, json).ok();
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use chrono::Utc;
+use gtk4::prelude::*;
+use gtk4::{Box as GtkBox, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow, SelectionMode};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::todo::Todo;
+use crate::trash::{Trash, TrashedTask};
+
+/// Trash view state
+pub struct TrashState {
+    list_box: ListBox,
+    /// Trash item index shown in each row, most recently deleted first
+    rows: Vec<usize>,
+}
+
+/// Create and populate the trash view for a tab
+pub fn create_trash_view(scrolled_trash: &ScrolledWindow, trash_state: &Rc<RefCell<Option<TrashState>>>) {
+    let list_box = ListBox::new();
+    list_box.set_selection_mode(SelectionMode::Single);
+    list_box.add_css_class("todo-list");
+    scrolled_trash.set_child(Some(&list_box));
+
+    *trash_state.borrow_mut() = Some(TrashState {
+        list_box,
+        rows: Vec::new(),
+    });
+    refresh_trash_view(trash_state);
+}
+
+/// Reload the trash and rebuild the rows, keeping the selected position
+pub fn refresh_trash_view(trash_state: &Rc<RefCell<Option<TrashState>>>) {
+    let mut state_ref = trash_state.borrow_mut();
+    let state = match state_ref.as_mut() {
+        Some(s) => s,
+        None => return,
+    };
+    let selected = state.list_box.selected_row().map(|row| row.index()).unwrap_or(0);
+    while let Some(child) = state.list_box.first_child() {
+        state.list_box.remove(&child);
+    }
+
+    let trash = Trash::load();
+    let now = Utc::now().timestamp();
+    state.rows = (0..trash.items.len()).rev().collect();
+    if state.rows.is_empty() {
+        let row = ListBoxRow::new();
+        row.set_selectable(false);
+        // A trash.json that couldn't be read shows why instead
+        let label = Label::new(Some(trash.load_error().unwrap_or("Trash is empty")));
+        label.add_css_class("trash-empty");
+        label.set_margin_top(16);
+        row.set_child(Some(&label));
+        state.list_box.append(&row);
+        return;
+    }
+    for &item in &state.rows {
+        state.list_box.append(&create_trash_row(&trash.items[item], now));
+    }
+
+    let index = selected.min(state.rows.len() as i32 - 1);
+    if let Some(row) = state.list_box.row_at_index(index) {
+        state.list_box.select_row(Some(&row));
+    }
+    state.list_box.grab_focus();
+}
+
+fn create_trash_row(item: &TrashedTask, now: i64) -> ListBoxRow {
+    let row = ListBoxRow::new();
+    row.add_css_class("todo-row");
+
+    let hbox = GtkBox::new(Orientation::Horizontal, 8);
+    hbox.set_margin_start(8);
+    hbox.set_margin_end(8);
+    hbox.set_margin_top(8);
+    hbox.set_margin_bottom(8);
+
+    // Where the task was: cluster/Parent/...
+    let mut origin = vec![item.cluster.clone()];
+    origin.extend(item.parent_path.iter().cloned());
+    let origin_label = Label::new(Some(&format!("{}/", origin.join("/"))));
+    origin_label.add_css_class("hierarchy-path");
+    hbox.append(&origin_label);
+
+    let mut text = item.todo.text.clone();
+    let subtasks = count_subtasks(&item.todo.subtasks);
+    if subtasks > 0 {
+        text.push_str(&format!(" (+{})", subtasks));
+    }
+    let text_label = Label::new(Some(&text));
+    text_label.set_hexpand(true);
+    text_label.set_halign(gtk4::Align::Start);
+    hbox.append(&text_label);
+
+    let age_label = Label::new(Some(&format!("deleted {}", format_age(now - item.deleted_at))));
+    age_label.add_css_class("trash-age");
+    hbox.append(&age_label);
+
+    row.set_child(Some(&hbox));
+    row
+}
+
+fn count_subtasks(todos: &[Todo]) -> usize {
+    todos.iter().map(|t| 1 + count_subtasks(&t.subtasks)).sum()
+}
+
+/// Rough age such as "5m ago" or "3d ago"
+fn format_age(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
+}
+
+/// Move the selection up or down
+pub fn navigate_trash(trash_state: &Rc<RefCell<Option<TrashState>>>, delta: i32) {
+    let state_ref = trash_state.borrow();
+    let state = match state_ref.as_ref() {
+        Some(s) => s,
+        None => return,
+    };
+    let current = state.list_box.selected_row().map(|row| row.index()).unwrap_or(0);
+    let next = (current + delta).clamp(0, (state.rows.len() as i32 - 1).max(0));
+    if let Some(row) = state.list_box.row_at_index(next) {
+        state.list_box.select_row(Some(&row));
+    }
+}
+
+/// Trash item index of the selected row
+pub fn selected_trash_item(trash_state: &Rc<RefCell<Option<TrashState>>>) -> Option<usize> {
+    let state_ref = trash_state.borrow();
+    let state = state_ref.as_ref()?;
+    let row = state.list_box.selected_row()?;
+    state.rows.get(row.index() as usize).copied()
+}
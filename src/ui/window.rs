@@ -1,18 +1,36 @@
-use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
 use gtk4::prelude::*;
 use gtk4::{
-    gdk, Application, ApplicationWindow, Box as GtkBox, Button, Entry, EventControllerKey, Frame,
+    gdk, gio, Application, ApplicationWindow, Box as GtkBox, Button, Entry, EventControllerKey, Frame,
     Grid, Label, ListBox, ListBoxRow, Notebook, Orientation, ScrolledWindow, SelectionMode, Stack,
     StackTransitionType,
 };
-use std::cell::RefCell;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::rc::Rc;
 
+use super::board::{
+    create_board_view, move_board_card, navigate_board, refresh_board_view, set_board_grouping,
+    BoardGrouping, BoardState,
+};
+use super::focus::{focused_task, start_focus, stop_focus, FocusSession};
+use super::session::{Session, SessionView, TabSession, LAST_SESSION};
+use super::trash::{create_trash_view, navigate_trash, refresh_trash_view, selected_trash_item, TrashState};
+use crate::archive::ArchiveConfig;
 use crate::colors::ColorConfig;
-use crate::date_parser::{parse_date, parse_priority};
-use crate::keybindings::{Action, Keybindings};
-use crate::todo::{FlatTodo, Priority, Todo, TodoList};
+use crate::crypto;
+use crate::focus::FocusConfig;
+use crate::history::{GitConfig, Repo};
+use crate::date_parser::{parse_date, parse_dependencies, parse_estimate, parse_priority, try_parse_date};
+use crate::keybindings::{is_modifier_key, show_keys, Action, Chord, Keybindings, Lookup, Mode};
+use crate::merge::{find_conflict_files, merge, SYNC_CONFLICT_MARKER};
+use crate::paths;
+use crate::settings::{Settings, OPTIONS};
+use crate::storage::{current_backend, switch_backend, with_storage, Backend, StorageConfig};
+use crate::todo::{format_minutes, FlatTodo, Priority, Status, Todo, TodoList, ARCHIVE_SUFFIX};
+use crate::trash::{Trash, TrashConfig};
 
 #[derive(Clone, Debug, PartialEq)]
 enum InputMode {
@@ -22,12 +40,113 @@ enum InputMode {
     Edit(Vec<usize>),            // Editing task at path
     Command,                     // Command mode (started with :)
     CalendarInsert(NaiveDate),   // Inserting a task on a specific calendar date
+    SetDate(Vec<usize>),         // Entering a date expression for task at path
+    Passphrase(PassphrasePrompt), // Entering a passphrase (hidden)
+}
+
+/// What a passphrase typed into the command bar is for
+#[derive(Clone, Debug, PartialEq)]
+enum PassphrasePrompt {
+    /// Open an encrypted cluster in the current tab
+    Unlock(String),
+    /// Encrypt the current cluster; asks again to confirm
+    Encrypt,
+    ConfirmEncrypt(String),
+}
+
+/// How a tab orders its rows (without changing the cluster itself)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortMode {
+    #[default]
+    Manual,    // Stored order; flattened views sort by status, priority, due date
+    Priority,
+    Due,
+    Alpha,
+    Created,   // Newest first
+}
+
+impl SortMode {
+    fn parse(s: &str) -> Option<SortMode> {
+        match s {
+            "manual" | "default" => Some(SortMode::Manual),
+            "priority" | "p" => Some(SortMode::Priority),
+            "due" | "date" | "d" => Some(SortMode::Due),
+            "alpha" | "name" | "a" => Some(SortMode::Alpha),
+            "created" | "new" => Some(SortMode::Created),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortMode::Manual => "manual",
+            SortMode::Priority => "priority",
+            SortMode::Due => "due",
+            SortMode::Alpha => "alpha",
+            SortMode::Created => "created",
+        }
+    }
 }
 
-#[derive(Clone, Debug, Default)]
-struct DisplaySettings {
+/// How a tab renders its cluster. Each tab has its own, so two tabs can show
+/// the same cluster differently.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct DisplaySettings {
     show_start_date: bool,
     flattened: bool,
+    next_actions: bool,  // Only actionable tasks: open, unblocked, no open subtasks
+    filter: Option<String>,  // Only tasks whose text contains this (lowercase)
+    sort_mode: SortMode,
+    show_completed: bool,  // When false, done tasks collapse into "Completed (N)"
+    show_abandoned: bool,
+    auto_hide_days: Option<u32>,  // Collapse tasks closed more than this many days ago
+    expanded_groups: HashSet<String>,  // "Completed (N)" rows that are open
+    #[serde(skip)]
+    focused_id: Option<String>,  // Task in focus mode; other rows are dimmed
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            show_start_date: false,
+            flattened: false,
+            next_actions: false,
+            filter: None,
+            sort_mode: SortMode::Manual,
+            show_completed: true,
+            show_abandoned: true,
+            auto_hide_days: None,
+            expanded_groups: HashSet::new(),
+            focused_id: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Whether rows are shown without hierarchy (indentation and chevrons)
+    fn is_flat(&self) -> bool {
+        self.flattened || self.next_actions
+    }
+
+    /// Whether a closed task goes into its level's "Completed (N)" row
+    fn collapses(&self, todo: &Todo, now: i64) -> bool {
+        let shown = match todo.status {
+            Status::Done => self.show_completed,
+            Status::Abandoned => self.show_abandoned,
+            _ => return false,
+        };
+        // Tasks closed before completion times were recorded count as old
+        let is_old = self.auto_hide_days.is_some_and(|days| {
+            todo.completed_at.is_none_or(|t| now - t > days as i64 * 86400)
+        });
+        !todo.is_section && (!shown || is_old)
+    }
+
+    fn hides_closed(&self) -> bool {
+        !self.show_completed || !self.show_abandoned || self.auto_hide_days.is_some()
+    }
 }
 
 /// View type for a tab
@@ -35,6 +154,93 @@ struct DisplaySettings {
 enum ViewType {
     List,
     Calendar,
+    Week,
+    Board,
+    Trash,
+}
+
+/// Keys typed so far of a binding longer than one key
+#[derive(Default)]
+struct PendingKeys {
+    keys: Vec<Chord>,
+    /// Runs the binding of `keys` if no key follows in time
+    timeout: Option<gtk4::glib::SourceId>,
+}
+
+impl PendingKeys {
+    fn cancel_timeout(&mut self) {
+        if let Some(source) = self.timeout.take() {
+            source.remove();
+        }
+    }
+}
+
+/// Which history a text field steps through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HistoryKind {
+    Command,
+    Task,
+    Date,
+}
+
+/// Text entered in this run, oldest first
+#[derive(Default)]
+struct EntryHistory {
+    entries: Vec<String>,
+    /// Index of the entry in the field while stepping through
+    shown: Option<usize>,
+    /// What was typed before stepping back, put back after the newest entry
+    draft: String,
+}
+
+thread_local! {
+    /// Never holds passphrases
+    static HISTORY: RefCell<HashMap<HistoryKind, EntryHistory>> = RefCell::new(HashMap::new());
+}
+
+fn record_history(kind: HistoryKind, text: &str) {
+    let text = text.trim();
+    if text.is_empty() || text == ":" {
+        return;
+    }
+    HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        let history = history.entry(kind).or_default();
+        history.entries.retain(|entry| entry != text);
+        history.entries.push(text.to_string());
+        history.shown = None;
+    });
+}
+
+/// Put an older (`delta` -1) or newer (1) entry of the history in the field
+fn step_history(kind: HistoryKind, entry: &Entry, delta: i32) {
+    HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        let history = history.entry(kind).or_default();
+        let current = entry.text().to_string();
+        // Editing a recalled entry starts over from the newest
+        if history.shown.is_some_and(|i| history.entries[i] != current) {
+            history.shown = None;
+        }
+        let newest = history.entries.len() as i32;
+        let from = history.shown.map_or(newest, |i| i as i32);
+        let to = (from + delta).clamp(0, newest);
+        if to == from {
+            return;
+        }
+        if history.shown.is_none() {
+            history.draft = current;
+        }
+        let text = if to == newest {
+            history.shown = None;
+            history.draft.clone()
+        } else {
+            history.shown = Some(to as usize);
+            history.entries[to as usize].clone()
+        };
+        entry.set_text(&text);
+        entry.set_position(-1);
+    });
 }
 
 /// Calendar state
@@ -42,12 +248,25 @@ struct CalendarState {
     year: i32,
     month: u32,
     selected_day: u32,
+    selected_task: usize,      // Index of the selected task within the selected day
     grid: Grid,
-    day_frames: HashMap<u32, Frame>,
     month_label: Label,
+    day_names: Vec<Label>,       // Column headers, starting at the configured week start
+    day_tasks: HashMap<u32, Vec<FlatTodo>>,
+}
+
+/// Week view state
+struct WeekState {
+    start: NaiveDate,          // First day of the displayed week
+    selected: NaiveDate,
+    selected_task: usize,      // Index of the selected task within the selected day
+    columns: GtkBox,
+    range_label: Label,
+    day_tasks: HashMap<NaiveDate, Vec<FlatTodo>>,
 }
 
 /// Per-tab content state
+#[derive(Clone)]
 struct TabContent {
     todos: Rc<RefCell<TodoList>>,
     list_box: ListBox,
@@ -56,10 +275,17 @@ struct TabContent {
     cluster_name: String,
     view_type: Rc<RefCell<ViewType>>,
     calendar_state: Rc<RefCell<Option<CalendarState>>>,
+    week_state: Rc<RefCell<Option<WeekState>>>,
+    board_state: Rc<RefCell<Option<BoardState>>>,
+    trash_state: Rc<RefCell<Option<TrashState>>>,
+    display_settings: Rc<RefCell<DisplaySettings>>,
     content_stack: gtk4::Stack,
     #[allow(dead_code)]
     scrolled_list: ScrolledWindow,
     scrolled_calendar: ScrolledWindow,
+    scrolled_week: ScrolledWindow,
+    scrolled_board: ScrolledWindow,
+    scrolled_trash: ScrolledWindow,
 }
 
 pub struct ZapWindow {
@@ -70,19 +296,54 @@ pub struct ZapWindow {
     mode_label: Label,
     notification_label: Label,
     input_mode: Rc<RefCell<InputMode>>,
-    pending_key: Rc<RefCell<Option<String>>>,  // For key sequences like gg, dd, za
-    display_settings: Rc<RefCell<DisplaySettings>>,
-    keybindings: Rc<Keybindings>,
-    color_config: Rc<ColorConfig>,
+    pending_keys: Rc<RefCell<PendingKeys>>,  // For key sequences like gg, dd, za
+    showcmd_label: Label,  // The pending keys, as vim's showcmd
+    keybindings: Rc<RefCell<Keybindings>>,
+    css_provider: gtk4::CssProvider,
+    focus: Rc<RefCell<Option<FocusSession>>>,
+    focus_label: Label,  // Pomodoro countdown in the header
+    breadcrumb_label: Label,  // Path to the hoisted task, if any
+    file_monitor: Option<gio::FileMonitor>,  // Watches the data dir for outside changes
+    config_monitor: Option<gio::FileMonitor>,  // Watches the config dir when watch_config is on
+    git_config: Rc<GitConfig>,
+    repo: Option<Rc<Repo>>,  // The data dir as a git repository, when git history is on
+    git_busy: Rc<Cell<bool>>,  // Set while git runs in the background
 }
 
 impl ZapWindow {
-    pub fn new(app: &Application) -> Self {
+    /// Create the window, reopening the tabs of the last session if `restore` is set
+    pub fn new(app: &Application, restore: bool) -> Self {
         let input_mode = Rc::new(RefCell::new(InputMode::Normal));
-        let pending_key: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
-        let display_settings = Rc::new(RefCell::new(DisplaySettings::default()));
-        let keybindings = Rc::new(Keybindings::load());
-        let color_config = Rc::new(ColorConfig::load());
+        let pending_keys = Rc::new(RefCell::new(PendingKeys::default()));
+        // Config files that don't parse are reported instead of being replaced
+        let mut config_errors = Vec::new();
+        let keybindings = Keybindings::try_load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            Keybindings::default()
+        });
+        config_errors.extend_from_slice(keybindings.problems());
+        let keybindings = Rc::new(RefCell::new(keybindings));
+        let color_config = ColorConfig::try_load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            ColorConfig::default()
+        });
+        let css_provider = gtk4::CssProvider::new();
+        css_provider.load_from_data(&color_config.generate_css());
+        if let Err(err) = Settings::reload() {
+            config_errors.push(err);
+        }
+        if let Err(err) = ArchiveConfig::try_load() {
+            config_errors.push(err);
+        }
+        if let Err(err) = TrashConfig::try_load() {
+            config_errors.push(err);
+        }
+        if let Err(err) = FocusConfig::try_load() {
+            config_errors.push(err);
+        }
+        if let Err(err) = StorageConfig::try_load() {
+            config_errors.push(err);
+        }
         let tabs: Rc<RefCell<Vec<TabContent>>> = Rc::new(RefCell::new(Vec::new()));
 
         // Create window
@@ -104,12 +365,33 @@ impl ZapWindow {
         header_box.set_margin_top(8);
         header_box.set_margin_bottom(4);
 
+        // Focus countdown (hidden unless a focus session is running)
+        let focus_label = Label::new(None);
+        focus_label.add_css_class("focus-indicator");
+        focus_label.set_halign(gtk4::Align::Start);
+        focus_label.set_visible(false);
+
+        // Breadcrumb of the hoisted task (hidden unless hoisted)
+        let breadcrumb_label = Label::new(None);
+        breadcrumb_label.add_css_class("hoist-breadcrumb");
+        breadcrumb_label.set_halign(gtk4::Align::Start);
+        breadcrumb_label.set_ellipsize(gtk4::pango::EllipsizeMode::Start);
+        breadcrumb_label.set_visible(false);
+
+        // Keys typed so far of a longer binding
+        let showcmd_label = Label::new(None);
+        showcmd_label.add_css_class("showcmd");
+        showcmd_label.set_halign(gtk4::Align::End);
+        showcmd_label.set_hexpand(true);
+
         // Mode indicator
         let mode_label = Label::new(Some("NORMAL"));
         mode_label.add_css_class("mode-indicator");
         mode_label.set_halign(gtk4::Align::End);
-        mode_label.set_hexpand(true);
 
+        header_box.append(&breadcrumb_label);
+        header_box.append(&focus_label);
+        header_box.append(&showcmd_label);
         header_box.append(&mode_label);
 
         // Notification label (hidden by default)
@@ -147,7 +429,14 @@ impl ZapWindow {
 
         window.set_child(Some(&main_box));
 
-        let zap = Self {
+        // History stays off while git.json can't be read
+        let git_config = GitConfig::try_load().unwrap_or_else(|err| {
+            config_errors.push(err);
+            GitConfig::default()
+        });
+        let repo = Repo::open_data_dir(&git_config).map(Rc::new);
+
+        let mut zap = Self {
             window,
             notebook,
             tabs,
@@ -155,29 +444,63 @@ impl ZapWindow {
             mode_label,
             notification_label,
             input_mode,
-            pending_key,
-            display_settings,
+            pending_keys,
+            showcmd_label,
             keybindings,
-            color_config,
+            css_provider,
+            focus: Rc::new(RefCell::new(None)),
+            focus_label,
+            breadcrumb_label,
+            file_monitor: None,
+            config_monitor: None,
+            git_config: Rc::new(git_config),
+            repo,
+            git_busy: Rc::new(Cell::new(false)),
         };
 
-        // Create initial tab with "main" cluster
-        zap.add_tab("main");
+        // Reopen the previous session, or start with the startup cluster
+        let restored = restore
+            && Session::load(LAST_SESSION).is_some_and(|session| {
+                restore_session(&session, &zap.tabs, &zap.notebook, &zap.breadcrumb_label, &zap.notification_label)
+            });
+        if !restored {
+            zap.add_tab(&Settings::current().startup_cluster);
+        }
         zap.setup_keybindings();
         zap.setup_entry_handler();
-        zap.setup_entry_autocomplete();
+        zap.setup_tab_switch();
+        zap.setup_session_save();
+        zap.file_monitor = zap.watch_data_dir();
+        zap.config_monitor = zap.watch_config_dir();
+        zap.setup_history_commits();
         zap.apply_css();
 
+        // Clusters that couldn't be read are open empty and read-only
+        let mut errors = config_errors;
+        errors.extend(
+            zap.tabs
+                .borrow()
+                .iter()
+                .filter_map(|tab| tab.todos.borrow().load_error().map(str::to_string)),
+        );
+        if !errors.is_empty() {
+            show_notification(&zap.notification_label, &errors.join("\n"), true);
+        }
+
         zap
     }
 
     /// Create a new tab with the given cluster name
     fn add_tab(&self, cluster_name: &str) {
-        let todos = Rc::new(RefCell::new(TodoList::load(cluster_name)));
+        let todos = Rc::new(RefCell::new(load_cluster(cluster_name, &self.notification_label)));
         let flat_todos = Rc::new(RefCell::new(Vec::new()));
         let inline_entry_row: Rc<RefCell<Option<ListBoxRow>>> = Rc::new(RefCell::new(None));
         let view_type = Rc::new(RefCell::new(ViewType::List));
         let calendar_state: Rc<RefCell<Option<CalendarState>>> = Rc::new(RefCell::new(None));
+        let week_state: Rc<RefCell<Option<WeekState>>> = Rc::new(RefCell::new(None));
+        let board_state: Rc<RefCell<Option<BoardState>>> = Rc::new(RefCell::new(None));
+        let trash_state: Rc<RefCell<Option<TrashState>>> = Rc::new(RefCell::new(None));
+        let display_settings = Rc::new(RefCell::new(DisplaySettings::default()));
 
         // Create stack for switching between list and calendar views
         let content_stack = Stack::new();
@@ -206,6 +529,33 @@ impl ZapWindow {
         scrolled_calendar.set_margin_bottom(8);
 
         content_stack.add_named(&scrolled_calendar, Some("calendar"));
+
+        // Create week view container (will be populated when switched to)
+        let scrolled_week = ScrolledWindow::new();
+        scrolled_week.set_vexpand(true);
+        scrolled_week.set_margin_start(12);
+        scrolled_week.set_margin_end(12);
+        scrolled_week.set_margin_bottom(8);
+
+        content_stack.add_named(&scrolled_week, Some("week"));
+
+        // Create board view container (will be populated when switched to)
+        let scrolled_board = ScrolledWindow::new();
+        scrolled_board.set_vexpand(true);
+        scrolled_board.set_margin_start(12);
+        scrolled_board.set_margin_end(12);
+        scrolled_board.set_margin_bottom(8);
+
+        content_stack.add_named(&scrolled_board, Some("board"));
+
+        // Create trash view container (will be populated when switched to)
+        let scrolled_trash = ScrolledWindow::new();
+        scrolled_trash.set_vexpand(true);
+        scrolled_trash.set_margin_start(12);
+        scrolled_trash.set_margin_end(12);
+        scrolled_trash.set_margin_bottom(8);
+
+        content_stack.add_named(&scrolled_trash, Some("trash"));
         content_stack.set_visible_child_name("list");
 
         // Tab label
@@ -225,9 +575,16 @@ impl ZapWindow {
             cluster_name: cluster_name.to_string(),
             view_type,
             calendar_state,
+            week_state,
+            board_state,
+            trash_state,
+            display_settings,
             content_stack,
             scrolled_list,
             scrolled_calendar,
+            scrolled_week,
+            scrolled_board,
+            scrolled_trash,
         };
         self.tabs.borrow_mut().push(tab_content);
 
@@ -245,20 +602,8 @@ impl ZapWindow {
     fn refresh_tab(&self, tab_index: usize) {
         let tabs = self.tabs.borrow();
         if let Some(tab) = tabs.get(tab_index) {
-            while let Some(child) = tab.list_box.first_child() {
-                tab.list_box.remove(&child);
-            }
-
-            let todos = tab.todos.borrow();
-            let flat = todos.flatten();
-            let settings = self.display_settings.borrow();
-
-            for flat_todo in &flat {
-                let row = create_todo_row(flat_todo, &settings);
-                tab.list_box.append(&row);
-            }
-
-            *tab.flat_todos.borrow_mut() = flat;
+            // Same rows as every other refresh, with hidden and grouped closed tasks
+            refresh_list_with_settings(&tab.todos, &tab.list_box, &tab.flat_todos, &tab.display_settings);
 
             if let Some(first_row) = tab.list_box.row_at_index(0) {
                 tab.list_box.select_row(Some(&first_row));
@@ -266,31 +611,156 @@ impl ZapWindow {
         }
     }
 
-    fn setup_entry_autocomplete(&self) {
-        let command_entry = self.command_entry.clone();
-        let input_mode = self.input_mode.clone();
-
-        let key_controller = EventControllerKey::new();
-        key_controller.connect_key_pressed(move |_, key, _, _| {
-            if key != gdk::Key::Tab {
-                return gdk::glib::Propagation::Proceed;
+    /// Save the open tabs when the window closes
+    fn setup_session_save(&self) {
+        let tabs = self.tabs.clone();
+        let notebook = self.notebook.clone();
+        let repo = self.repo.clone();
+        let focus = self.focus.clone();
+        let focus_label = self.focus_label.clone();
+        let git_busy = self.git_busy.clone();
+        let notification_label = self.notification_label.clone();
+        // Set once the last saves are committed, or failed to be
+        let committed = Rc::new(Cell::new(false));
+        self.window.connect_close_request(move |window| {
+            // Running timers would otherwise count the time zap is closed
+            stop_focus(&focus, &focus_label);
+            for tab in tabs.borrow().iter() {
+                tab.todos.borrow_mut().stop_timer();
             }
-
-            let mode = input_mode.borrow().clone();
-            if mode != InputMode::Command {
-                return gdk::glib::Propagation::Proceed;
+            capture_session(&tabs, &notebook).save(LAST_SESSION);
+            if let Some(repo) = repo.as_ref().filter(|_| !committed.get()) {
+                if git_busy.get() {
+                    // Close again once the sync or commit running in the background is done
+                    show_notification(&notification_label, "Waiting for git to finish...", false);
+                    let window = window.clone();
+                    let git_busy = git_busy.clone();
+                    gtk4::glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
+                        if git_busy.get() {
+                            return gtk4::glib::ControlFlow::Continue;
+                        }
+                        window.close();
+                        gtk4::glib::ControlFlow::Break
+                    });
+                    return gdk::glib::Propagation::Stop;
+                }
+                committed.set(true);
+                if let Err(err) = repo.commit_pending() {
+                    let message = format!("Could not commit to git history: {}; close again to quit", err);
+                    show_notification(&notification_label, &message, true);
+                    return gdk::glib::Propagation::Stop;
+                }
             }
+            gdk::glib::Propagation::Proceed
+        });
+    }
+
+    /// With git history on, commit the saves of the last interval together
+    fn setup_history_commits(&self) {
+        let repo = match &self.repo {
+            Some(repo) => repo.clone(),
+            None => return,
+        };
+        let busy = self.git_busy.clone();
+        let interval = self.git_config.commit_interval_seconds.max(1);
+        gtk4::glib::timeout_add_seconds_local(interval, move || {
+            // Skipped while a sync runs, which commits too
+            run_git(&repo, &busy, |repo| repo.commit_pending().ok(), |_| {});
+            gtk4::glib::ControlFlow::Continue
+        });
+    }
 
-            let text = command_entry.text().to_string();
-            if let Some(completed) = autocomplete_command(&text) {
-                command_entry.set_text(&completed);
-                command_entry.set_position(-1);
+    /// Reload clusters that another program or zap instance writes to
+    fn watch_data_dir(&self) -> Option<gio::FileMonitor> {
+        let dir = gio::File::for_path(TodoList::data_dir());
+        let monitor = dir
+            .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            .ok()?;
+        let tabs = self.tabs.clone();
+        let notification_label = self.notification_label.clone();
+        monitor.connect_changed(move |_, file, other_file, event| {
+            // Wait for writes to finish; sync tools often replace the file with a rename
+            let file = match event {
+                gio::FileMonitorEvent::ChangesDoneHint
+                | gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::MovedIn => file,
+                gio::FileMonitorEvent::Renamed => match other_file {
+                    Some(f) => f,
+                    None => return,
+                },
+                _ => return,
+            };
+            let path = match file.path() {
+                Some(p) => p,
+                None => return,
+            };
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) {
+                    match name.split_once(SYNC_CONFLICT_MARKER) {
+                        Some((cluster, _)) => {
+                            let message = format!("Sync conflict in '{}'; :resolve merges it", cluster);
+                            show_notification(&notification_label, &message, true);
+                        }
+                        None => reload_changed_cluster(&tabs, &name, &notification_label),
+                    }
+                }
             }
+        });
+        Some(monitor)
+    }
 
-            gdk::glib::Propagation::Stop
+    /// Pick up edits to keybindings.json, colors.json and settings.json while
+    /// the watch_config setting is on
+    fn watch_config_dir(&self) -> Option<gio::FileMonitor> {
+        let dir = gio::File::for_path(paths::config_dir());
+        let monitor = dir
+            .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            .ok()?;
+        let tabs = self.tabs.clone();
+        let keybindings = self.keybindings.clone();
+        let css_provider = self.css_provider.clone();
+        let notification_label = self.notification_label.clone();
+        monitor.connect_changed(move |_, file, other_file, event| {
+            // Editors often save by writing a temporary file and renaming it
+            let file = match event {
+                gio::FileMonitorEvent::ChangesDoneHint
+                | gio::FileMonitorEvent::Created
+                | gio::FileMonitorEvent::MovedIn => file,
+                gio::FileMonitorEvent::Renamed => match other_file {
+                    Some(f) => f,
+                    None => return,
+                },
+                _ => return,
+            };
+            if !Settings::current().watch_config {
+                return;
+            }
+            let name = match file.basename() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return,
+            };
+            // Only problems are worth interrupting for
+            if let Some(problems) = reload_config_file(&name, &keybindings, &css_provider, &tabs) {
+                if !problems.is_empty() {
+                    show_notification(&notification_label, &problems.join("\n"), true);
+                }
+            }
         });
+        Some(monitor)
+    }
 
-        self.command_entry.add_controller(key_controller);
+    /// Keep the header in sync with the current tab
+    fn setup_tab_switch(&self) {
+        let tabs = self.tabs.clone();
+        let breadcrumb_label = self.breadcrumb_label.clone();
+        self.notebook.connect_switch_page(move |_, _, page| {
+            // Tabs are borrowed while a new tab is being added
+            if let Ok(tabs_ref) = tabs.try_borrow() {
+                if let Some(tab) = tabs_ref.get(page as usize) {
+                    update_breadcrumb(&breadcrumb_label, &tab.todos);
+                }
+            }
+        });
     }
 
     fn setup_keybindings(&self) {
@@ -301,14 +771,15 @@ impl ZapWindow {
         let command_entry = self.command_entry.clone();
         let mode_label = self.mode_label.clone();
         let input_mode = self.input_mode.clone();
-        let pending_key = self.pending_key.clone();
-        let display_settings = self.display_settings.clone();
+        let pending_keys = self.pending_keys.clone();
+        let showcmd_label = self.showcmd_label.clone();
         let keybindings = self.keybindings.clone();
+        let breadcrumb_label = self.breadcrumb_label.clone();
+        let notification_label = self.notification_label.clone();
 
         // Clone self references for tab operations
         let tabs_for_new = tabs.clone();
         let notebook_for_new = notebook.clone();
-        let display_settings_for_new = display_settings.clone();
 
         key_controller.connect_key_pressed(move |_, key, _, modifier| {
             let mode = input_mode.borrow().clone();
@@ -320,7 +791,7 @@ impl ZapWindow {
             if ctrl && !shift && !alt {
                 if key == gdk::Key::t {
                     // Open new tab
-                    open_new_tab(&tabs_for_new, &notebook_for_new, &display_settings_for_new);
+                    open_new_tab(&tabs_for_new, &notebook_for_new);
                     return gdk::glib::Propagation::Stop;
                 }
                 if key == gdk::Key::w {
@@ -354,17 +825,46 @@ impl ZapWindow {
                 None => return gdk::glib::Propagation::Proceed,
             };
 
-            let todos = tab.todos.clone();
+            let tab = tab.clone();
+            drop(tabs_ref);
             let list_box = tab.list_box.clone();
-            let flat_todos = tab.flat_todos.clone();
             let inline_entry_row = tab.inline_entry_row.clone();
             let view_type = tab.view_type.clone();
-            let calendar_state = tab.calendar_state.clone();
-            drop(tabs_ref);
 
-            // Handle non-normal modes - only Escape works
+            // Outside normal mode only single keys are bound; the rest type text
             if mode != InputMode::Normal {
-                if let Some(Action::Cancel) = keybindings.get_action(&key, shift, ctrl, alt) {
+                let (scope, history) = match &mode {
+                    InputMode::Command => (Mode::Command, Some(HistoryKind::Command)),
+                    InputMode::Passphrase(_) => (Mode::Command, None),
+                    InputMode::SetDate(_) => (Mode::Insert, Some(HistoryKind::Date)),
+                    _ => (Mode::Insert, Some(HistoryKind::Task)),
+                };
+                let action = keybindings.borrow().action(scope, &[Chord::from_key(&key, shift, ctrl, alt)]);
+                if let Some(Action::HistoryPrev | Action::HistoryNext) = action {
+                    // New tasks in the list view are typed into their row, everything else into the command bar
+                    let entry = match mode {
+                        InputMode::Insert | InputMode::InsertSubtask(_) => {
+                            inline_entry_row.borrow().as_ref().and_then(get_entry_from_row)
+                        }
+                        _ => Some(command_entry.clone()),
+                    };
+                    if let (Some(kind), Some(entry)) = (history, entry) {
+                        let delta = if action == Some(Action::HistoryPrev) { -1 } else { 1 };
+                        step_history(kind, &entry, delta);
+                    }
+                    return gdk::glib::Propagation::Stop;
+                }
+                if action == Some(Action::Complete) {
+                    if mode == InputMode::Command {
+                        let text = command_entry.text().to_string();
+                        if let Some(completed) = autocomplete_command(&text) {
+                            command_entry.set_text(&completed);
+                            command_entry.set_position(-1);
+                        }
+                    }
+                    return gdk::glib::Propagation::Stop;
+                }
+                if action == Some(Action::Cancel) {
                     *input_mode.borrow_mut() = InputMode::Normal;
                     mode_label.set_text("NORMAL");
                     if let Some(row) = inline_entry_row.borrow_mut().take() {
@@ -372,6 +872,7 @@ impl ZapWindow {
                     }
                     command_entry.set_sensitive(false);
                     command_entry.set_text("");
+                    command_entry.set_visibility(true);
                     if *view_type.borrow() == ViewType::List {
                         list_box.grab_focus();
                     }
@@ -380,134 +881,87 @@ impl ZapWindow {
                 return gdk::glib::Propagation::Proceed;
             }
 
-            // Check if we're in calendar view
-            if *view_type.borrow() == ViewType::Calendar {
-                // Calendar-specific keybindings
-                // Arrow key detection
-                let is_left = key == gdk::Key::Left;
-                let is_right = key == gdk::Key::Right;
-                let is_up = key == gdk::Key::Up;
-                let is_down = key == gdk::Key::Down;
-
-                // </> or Ctrl+Left/Right for month navigation
-                if key == gdk::Key::less {
-                    change_calendar_month(&calendar_state, -1);
-                    return gdk::glib::Propagation::Stop;
-                }
-                if key == gdk::Key::greater {
-                    change_calendar_month(&calendar_state, 1);
-                    return gdk::glib::Propagation::Stop;
-                }
-                if ctrl && !shift && !alt {
-                    if is_left {
-                        change_calendar_month(&calendar_state, -1);
-                        return gdk::glib::Propagation::Stop;
-                    }
-                    if is_right {
-                        change_calendar_month(&calendar_state, 1);
-                        return gdk::glib::Propagation::Stop;
-                    }
-                }
-
-                // h/l/j/k or arrow keys for day navigation
-                if key == gdk::Key::h || (is_left && !ctrl) {
-                    navigate_calendar(&calendar_state, -1, 0);
-                    return gdk::glib::Propagation::Stop;
-                }
-                if key == gdk::Key::l || (is_right && !ctrl) {
-                    navigate_calendar(&calendar_state, 1, 0);
-                    return gdk::glib::Propagation::Stop;
-                }
-                if key == gdk::Key::k || is_up {
-                    navigate_calendar(&calendar_state, 0, -1);
-                    return gdk::glib::Propagation::Stop;
-                }
-                if key == gdk::Key::j || is_down {
-                    navigate_calendar(&calendar_state, 0, 1);
-                    return gdk::glib::Propagation::Stop;
-                }
-
-                match key {
-                    k if k == gdk::Key::i => {
-                        // Insert task on selected date
-                        if let Some(date) = get_selected_calendar_date(&calendar_state) {
-                            *input_mode.borrow_mut() = InputMode::CalendarInsert(date);
-                            mode_label.set_text("INSERT (calendar)");
-                            command_entry.set_placeholder_text(Some(&format!("Task for {}...", date.format("%b %d"))));
-                            command_entry.set_text("");
-                            command_entry.set_sensitive(true);
-                            command_entry.grab_focus();
+            // Bound keys, where a key starting a longer binding waits for the
+            // next one
+            let scope = match *view_type.borrow() {
+                ViewType::List => Mode::List,
+                ViewType::Calendar => Mode::Calendar,
+                ViewType::Week => Mode::Week,
+                ViewType::Board => Mode::Board,
+                ViewType::Trash => Mode::Trash,
+            };
+            let action = if is_modifier_key(&key) {
+                None
+            } else {
+                let mut pending = pending_keys.borrow_mut();
+                pending.cancel_timeout();
+                let lookup = keybindings.borrow().feed(scope, &mut pending.keys, Chord::from_key(&key, shift, ctrl, alt));
+                showcmd_label.set_text(&show_keys(&pending.keys));
+                match lookup {
+                    Lookup::Prefix => {
+                        if let Some(timeout) = keybindings.borrow().timeout() {
+                            // Give up waiting and run what the keys so far are bound to, if anything
+                            let tabs = tabs.clone();
+                            let notebook = notebook.clone();
+                            let command_entry = command_entry.clone();
+                            let mode_label = mode_label.clone();
+                            let input_mode = input_mode.clone();
+                            let breadcrumb_label = breadcrumb_label.clone();
+                            let notification_label = notification_label.clone();
+                            let keybindings = keybindings.clone();
+                            let pending_keys = pending_keys.clone();
+                            let showcmd_label = showcmd_label.clone();
+                            let source = gtk4::glib::timeout_add_local_once(timeout, move || {
+                                let keys = {
+                                    let mut pending = pending_keys.borrow_mut();
+                                    pending.timeout = None;
+                                    std::mem::take(&mut pending.keys)
+                                };
+                                showcmd_label.set_text("");
+                                let action = keybindings.borrow().action(scope, &keys);
+                                let tab = notebook.current_page().and_then(|page| tabs.borrow().get(page as usize).cloned());
+                                if let (Some(action), Some(tab)) = (action, tab) {
+                                    run_bound_action(action, &tabs, &tab, &command_entry, &mode_label, &input_mode, &breadcrumb_label, &notification_label);
+                                }
+                            });
+                            pending.timeout = Some(source);
                         }
                         return gdk::glib::Propagation::Stop;
                     }
-                    k if k == gdk::Key::colon && shift => {
-                        // Command mode
-                        *input_mode.borrow_mut() = InputMode::Command;
-                        mode_label.set_text("COMMAND");
-                        command_entry.set_placeholder_text(Some(""));
-                        command_entry.set_text(":");
-                        command_entry.set_sensitive(true);
-                        command_entry.grab_focus();
-                        command_entry.set_position(-1);
-                        return gdk::glib::Propagation::Stop;
-                    }
-                    _ => {}
+                    Lookup::Action(action) => Some(action),
+                    Lookup::Unbound => None,
                 }
-                return gdk::glib::Propagation::Proceed;
-            }
-
-            // List view keybindings
-            // Check for sequence completion first
-            let pending = pending_key.borrow().clone();
-            if let Some(ref pending_str) = pending {
-                if let Some(action) = keybindings.get_sequence_action(pending_str, &key) {
-                    *pending_key.borrow_mut() = None;
-                    return execute_action(
-                        action, &todos, &list_box, &command_entry, &mode_label,
-                        &input_mode, &flat_todos, &todos, &list_box,
-                        &flat_todos, &display_settings, &inline_entry_row,
-                    );
+            };
+            if let Some(action) = action {
+                if let Some(propagation) = run_bound_action(
+                    action, &tabs, &tab, &command_entry, &mode_label, &input_mode, &breadcrumb_label, &notification_label,
+                ) {
+                    return propagation;
                 }
-                // Invalid sequence, clear pending
-                *pending_key.borrow_mut() = None;
-            }
-
-            // Check if this key starts a sequence
-            if let Some(seq_start) = keybindings.is_sequence_start(&key) {
-                *pending_key.borrow_mut() = Some(seq_start);
-                return gdk::glib::Propagation::Stop;
-            }
-
-            // Check for single key action
-            if let Some(action) = keybindings.get_action(&key, shift, ctrl, alt) {
-                *pending_key.borrow_mut() = None;
-                return execute_action(
-                    action, &todos, &list_box, &command_entry, &mode_label,
-                    &input_mode, &flat_todos, &todos, &list_box,
-                    &flat_todos, &display_settings, &inline_entry_row,
-                );
             }
 
-            *pending_key.borrow_mut() = None;
             gdk::glib::Propagation::Proceed
         });
 
+        // Ahead of the text fields, so that keys bound in insert and command
+        // mode are seen before the field takes them
+        key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
         self.window.add_controller(key_controller);
     }
 }
 
 /// Open a new blank tab
-fn open_new_tab(
-    tabs: &Rc<RefCell<Vec<TabContent>>>,
-    notebook: &Notebook,
-    _display_settings: &Rc<RefCell<DisplaySettings>>,
-) {
+fn open_new_tab(tabs: &Rc<RefCell<Vec<TabContent>>>, notebook: &Notebook) {
     // Create an empty tab with no cluster loaded
     let todos = Rc::new(RefCell::new(TodoList::default()));
     let flat_todos = Rc::new(RefCell::new(Vec::new()));
     let inline_entry_row: Rc<RefCell<Option<ListBoxRow>>> = Rc::new(RefCell::new(None));
     let view_type = Rc::new(RefCell::new(ViewType::List));
     let calendar_state: Rc<RefCell<Option<CalendarState>>> = Rc::new(RefCell::new(None));
+    let week_state: Rc<RefCell<Option<WeekState>>> = Rc::new(RefCell::new(None));
+    let board_state: Rc<RefCell<Option<BoardState>>> = Rc::new(RefCell::new(None));
+    let trash_state: Rc<RefCell<Option<TrashState>>> = Rc::new(RefCell::new(None));
+    let display_settings = Rc::new(RefCell::new(DisplaySettings::default()));
 
     // Create stack for switching between list and calendar views
     let content_stack = Stack::new();
@@ -534,6 +988,30 @@ fn open_new_tab(
     scrolled_calendar.set_margin_bottom(8);
 
     content_stack.add_named(&scrolled_calendar, Some("calendar"));
+
+    let scrolled_week = ScrolledWindow::new();
+    scrolled_week.set_vexpand(true);
+    scrolled_week.set_margin_start(12);
+    scrolled_week.set_margin_end(12);
+    scrolled_week.set_margin_bottom(8);
+
+    content_stack.add_named(&scrolled_week, Some("week"));
+
+    let scrolled_board = ScrolledWindow::new();
+    scrolled_board.set_vexpand(true);
+    scrolled_board.set_margin_start(12);
+    scrolled_board.set_margin_end(12);
+    scrolled_board.set_margin_bottom(8);
+
+    content_stack.add_named(&scrolled_board, Some("board"));
+
+    let scrolled_trash = ScrolledWindow::new();
+    scrolled_trash.set_vexpand(true);
+    scrolled_trash.set_margin_start(12);
+    scrolled_trash.set_margin_end(12);
+    scrolled_trash.set_margin_bottom(8);
+
+    content_stack.add_named(&scrolled_trash, Some("trash"));
     content_stack.set_visible_child_name("list");
 
     // Tab label - empty/new tab
@@ -551,9 +1029,16 @@ fn open_new_tab(
         cluster_name: String::new(),
         view_type,
         calendar_state,
+        week_state,
+        board_state,
+        trash_state,
+        display_settings,
         content_stack,
         scrolled_list,
         scrolled_calendar,
+        scrolled_week,
+        scrolled_board,
+        scrolled_trash,
     };
     tabs.borrow_mut().push(tab_content);
 
@@ -562,6 +1047,272 @@ fn open_new_tab(
     list_box.grab_focus();
 }
 
+/// Snapshot the open tabs for saving
+fn capture_session(tabs: &Rc<RefCell<Vec<TabContent>>>, notebook: &Notebook) -> Session {
+    let tabs_ref = tabs.borrow();
+    let tab_sessions = tabs_ref
+        .iter()
+        .map(|tab| {
+            let view = match *tab.view_type.borrow() {
+                ViewType::List => SessionView::List,
+                ViewType::Calendar => SessionView::Calendar,
+                ViewType::Week => SessionView::Week,
+                ViewType::Board => match tab.board_state.borrow().as_ref().map(|s| s.grouping) {
+                    Some(BoardGrouping::Priority) => SessionView::BoardPriority,
+                    _ => SessionView::Board,
+                },
+                ViewType::Trash => SessionView::Trash,
+            };
+            TabSession {
+                cluster: tab.cluster_name.clone(),
+                view,
+                calendar_month: tab.calendar_state.borrow().as_ref().map(|s| (s.year, s.month)),
+                selected_id: selected_flat_todo(&tab.list_box, &tab.flat_todos).map(|ft| ft.todo.id),
+                display: tab.display_settings.borrow().clone(),
+            }
+        })
+        .collect();
+    Session {
+        tabs: tab_sessions,
+        current: notebook.current_page().unwrap_or(0) as usize,
+    }
+}
+
+/// Replace the open tabs with those of a saved session. Tabs whose cluster no
+/// longer exists are skipped. Returns false (leaving the tabs alone) if none remain.
+fn restore_session(
+    session: &Session,
+    tabs: &Rc<RefCell<Vec<TabContent>>>,
+    notebook: &Notebook,
+    breadcrumb_label: &Label,
+    notification_label: &Label,
+) -> bool {
+    let tab_sessions: Vec<&TabSession> = session
+        .tabs
+        .iter()
+        .filter(|t| t.cluster.is_empty() || TodoList::exists(&t.cluster))
+        .collect();
+    if tab_sessions.is_empty() {
+        return false;
+    }
+
+    while notebook.n_pages() > 0 {
+        notebook.remove_page(Some(0));
+    }
+    tabs.borrow_mut().clear();
+
+    for tab_session in &tab_sessions {
+        open_new_tab(tabs, notebook);
+        let page = tabs.borrow().len() - 1;
+        tabs.borrow_mut()[page].cluster_name = tab_session.cluster.clone();
+
+        let tabs_ref = tabs.borrow();
+        let tab = &tabs_ref[page];
+        *tab.display_settings.borrow_mut() = tab_session.display.clone();
+        if !tab_session.cluster.is_empty() {
+            *tab.todos.borrow_mut() = load_cluster(&tab_session.cluster, notification_label);
+            if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+                notebook.set_tab_label_text(&page_widget, &tab_session.cluster);
+            }
+        }
+
+        refresh_list_with_settings(&tab.todos, &tab.list_box, &tab.flat_todos, &tab.display_settings);
+        match tab_session.selected_id {
+            Some(ref id) => select_row_by_id(&tab.list_box, &tab.flat_todos, id),
+            None => {
+                if let Some(first) = tab.list_box.row_at_index(0) {
+                    tab.list_box.select_row(Some(&first));
+                }
+            }
+        }
+
+        if let Some((year, month)) = tab_session.calendar_month {
+            create_calendar_view(&tab.scrolled_calendar, &tab.calendar_state, &tab.todos);
+            if let Some(state) = tab.calendar_state.borrow_mut().as_mut() {
+                state.year = year;
+                state.month = month;
+                state.selected_day = 1;
+                state.selected_task = 0;
+            }
+            refresh_calendar_view(&tab.calendar_state, &tab.todos);
+        }
+        match tab_session.view {
+            SessionView::List => {}
+            SessionView::Calendar => show_calendar_view(tab, notebook, page),
+            SessionView::Week => show_week_view(tab, notebook, page),
+            SessionView::Board => show_board_view(tab, notebook, page, BoardGrouping::Status),
+            SessionView::BoardPriority => show_board_view(tab, notebook, page, BoardGrouping::Priority),
+            SessionView::Trash => show_trash_view(tab, notebook, page),
+        }
+    }
+
+    let current = session.current.min(tab_sessions.len() - 1);
+    notebook.set_current_page(Some(current as u32));
+    let tabs_ref = tabs.borrow();
+    if let Some(tab) = tabs_ref.get(current) {
+        update_breadcrumb(breadcrumb_label, &tab.todos);
+        if *tab.view_type.borrow() == ViewType::List {
+            tab.list_box.grab_focus();
+        }
+    }
+    true
+}
+
+/// Run a bound action in the tab's view, None if the view has no use for it.
+/// `tabs` are for restoring from the trash into the tabs showing the cluster.
+#[allow(clippy::too_many_arguments)]
+fn run_bound_action(
+    action: Action,
+    tabs: &Rc<RefCell<Vec<TabContent>>>,
+    tab: &TabContent,
+    command_entry: &Entry,
+    mode_label: &Label,
+    input_mode: &Rc<RefCell<InputMode>>,
+    breadcrumb_label: &Label,
+    notification_label: &Label,
+) -> Option<gdk::glib::Propagation> {
+    let view = tab.view_type.borrow().clone();
+    // A "Completed (N)" row isn't a task: Enter opens or closes it like za
+    let on_group = view == ViewType::List
+        && selected_flat_todo(&tab.list_box, &tab.flat_todos).is_some_and(|ft| ft.hidden_count > 0);
+    let action = match action {
+        Action::ToggleComplete if on_group => Action::ToggleFold,
+        _ if on_group && action.acts_on_task() => {
+            show_notification(notification_label, "Not a task: Enter or za opens and closes the group", true);
+            return Some(gdk::glib::Propagation::Stop);
+        }
+        _ => action,
+    };
+    // Archives can be browsed but not edited, in any view
+    if action.modifies_tasks() && refuse_read_only(&tab.todos, notification_label) {
+        return Some(gdk::glib::Propagation::Stop);
+    }
+    match view {
+        // Scheduling keys are shared with the list view
+        ViewType::Calendar | ViewType::Week if action.is_scheduling() => {
+            let selected = match view {
+                ViewType::Calendar => get_selected_calendar_task(&tab.calendar_state),
+                _ => get_selected_week_task(&tab.week_state),
+            };
+            if let Some((path, task_id)) = selected {
+                match action {
+                    Action::SetDate => start_date_prompt(path, input_mode, mode_label, command_entry),
+                    _ => {
+                        let new_date = reschedule_task(action, &tab.todos, &path);
+                        match view {
+                            ViewType::Calendar => select_calendar_task_by_id(&tab.calendar_state, &tab.todos, new_date, &task_id),
+                            _ => select_week_task_by_id(&tab.week_state, &tab.todos, new_date, &task_id),
+                        }
+                    }
+                }
+            }
+            Some(gdk::glib::Propagation::Stop)
+        }
+        ViewType::Calendar => {
+            let (state, todos) = (&tab.calendar_state, &tab.todos);
+            match action {
+                Action::PrevDay => navigate_calendar(state, todos, -1, 0),
+                Action::NextDay => navigate_calendar(state, todos, 1, 0),
+                Action::PrevWeek => navigate_calendar(state, todos, 0, -1),
+                Action::NextWeek => navigate_calendar(state, todos, 0, 1),
+                Action::PrevMonth => change_calendar_month(state, todos, -1),
+                Action::NextMonth => change_calendar_month(state, todos, 1),
+                Action::NextDayTask => select_calendar_task(state, todos, 1),
+                Action::PrevDayTask => select_calendar_task(state, todos, -1),
+                Action::Insert => {
+                    if let Some(date) = get_selected_calendar_date(state) {
+                        start_date_insert(date, "INSERT (calendar)", input_mode, mode_label, command_entry);
+                    }
+                }
+                Action::CommandMode => start_command_mode(input_mode, mode_label, command_entry),
+                _ => return None,
+            }
+            Some(gdk::glib::Propagation::Stop)
+        }
+        ViewType::Week => {
+            let (state, todos) = (&tab.week_state, &tab.todos);
+            match action {
+                Action::PrevDay => navigate_week(state, todos, -1),
+                Action::NextDay => navigate_week(state, todos, 1),
+                Action::PrevWeek => navigate_week(state, todos, -7),
+                Action::NextWeek => navigate_week(state, todos, 7),
+                Action::NextDayTask => select_week_task(state, todos, 1),
+                Action::PrevDayTask => select_week_task(state, todos, -1),
+                Action::Insert => {
+                    if let Some(date) = state.borrow().as_ref().map(|s| s.selected) {
+                        start_date_insert(date, "INSERT (week)", input_mode, mode_label, command_entry);
+                    }
+                }
+                Action::CommandMode => start_command_mode(input_mode, mode_label, command_entry),
+                _ => return None,
+            }
+            Some(gdk::glib::Propagation::Stop)
+        }
+        ViewType::Board => {
+            let (state, todos) = (&tab.board_state, &tab.todos);
+            match action {
+                Action::PrevColumn => navigate_board(state, todos, -1, 0),
+                Action::NextColumn => navigate_board(state, todos, 1, 0),
+                Action::MoveUp => navigate_board(state, todos, 0, -1),
+                Action::MoveDown => navigate_board(state, todos, 0, 1),
+                Action::MoveCardPrev => move_board_card(state, todos, -1),
+                Action::MoveCardNext => move_board_card(state, todos, 1),
+                Action::CommandMode => start_command_mode(input_mode, mode_label, command_entry),
+                _ => return None,
+            }
+            Some(gdk::glib::Propagation::Stop)
+        }
+        ViewType::Trash => {
+            match action {
+                Action::MoveUp => navigate_trash(&tab.trash_state, -1),
+                Action::MoveDown => navigate_trash(&tab.trash_state, 1),
+                Action::Restore => {
+                    match restore_from_trash(tabs, &tab.trash_state) {
+                        Ok(message) => show_notification(notification_label, &message, false),
+                        Err(message) => show_notification(notification_label, &message, true),
+                    }
+                }
+                Action::CommandMode => start_command_mode(input_mode, mode_label, command_entry),
+                _ => return None,
+            }
+            Some(gdk::glib::Propagation::Stop)
+        }
+        ViewType::List => Some(execute_action(
+            action, &tab.todos, &tab.list_box, command_entry, mode_label,
+            input_mode, &tab.flat_todos, &tab.todos, &tab.list_box,
+            &tab.flat_todos, &tab.display_settings, &tab.inline_entry_row, breadcrumb_label,
+            notification_label,
+        )),
+    }
+}
+
+/// Open the command bar to type a task due on `date`
+fn start_date_insert(
+    date: NaiveDate,
+    label: &str,
+    input_mode: &Rc<RefCell<InputMode>>,
+    mode_label: &Label,
+    command_entry: &Entry,
+) {
+    *input_mode.borrow_mut() = InputMode::CalendarInsert(date);
+    mode_label.set_text(label);
+    command_entry.set_placeholder_text(Some(&format!("Task for {}...", date.format("%b %d"))));
+    command_entry.set_text("");
+    command_entry.set_sensitive(true);
+    command_entry.grab_focus();
+}
+
+/// Open the command bar with `:` typed
+fn start_command_mode(input_mode: &Rc<RefCell<InputMode>>, mode_label: &Label, command_entry: &Entry) {
+    *input_mode.borrow_mut() = InputMode::Command;
+    mode_label.set_text("COMMAND");
+    command_entry.set_placeholder_text(Some(""));
+    command_entry.set_text(":");
+    command_entry.set_sensitive(true);
+    command_entry.grab_focus();
+    command_entry.set_position(-1);
+}
+
 /// Execute an action from keybindings
 fn execute_action(
     action: Action,
@@ -576,6 +1327,8 @@ fn execute_action(
     refresh_flat_todos: &Rc<RefCell<Vec<FlatTodo>>>,
     refresh_display_settings: &Rc<RefCell<DisplaySettings>>,
     inline_entry_row: &Rc<RefCell<Option<ListBoxRow>>>,
+    breadcrumb_label: &Label,
+    notification_label: &Label,
 ) -> gdk::glib::Propagation {
     match action {
         Action::MoveDown => {
@@ -605,7 +1358,7 @@ fn execute_action(
                     let path = flat_todo.path.clone();
                     let task_id = flat_todo.todo.id.clone();
                     drop(flat);
-                    todos.borrow_mut().toggle_at_path(&path);
+                    todos.borrow_mut().toggle_at_path(&path, Settings::current().move_done_to_bottom);
                     refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
                     // Find the task by ID after refresh (it may have moved)
                     let new_flat = refresh_flat_todos.borrow();
@@ -617,7 +1370,7 @@ fn execute_action(
                 }
             }
         }
-        Action::Abandon => {
+        Action::CycleStatus => {
             if let Some(row) = list_box.selected_row() {
                 let index = row.index() as usize;
                 let flat = flat_todos.borrow();
@@ -625,7 +1378,7 @@ fn execute_action(
                     let path = flat_todo.path.clone();
                     let task_id = flat_todo.todo.id.clone();
                     drop(flat);
-                    todos.borrow_mut().abandon_at_path(&path);
+                    todos.borrow_mut().cycle_status_at_path(&path, Settings::current().move_done_to_bottom);
                     refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
                     // Find the task by ID after refresh (it may have moved)
                     let new_flat = refresh_flat_todos.borrow();
@@ -637,14 +1390,38 @@ fn execute_action(
                 }
             }
         }
-        Action::Delete => {
+        Action::Abandon => {
             if let Some(row) = list_box.selected_row() {
                 let index = row.index() as usize;
                 let flat = flat_todos.borrow();
                 if let Some(flat_todo) = flat.get(index) {
                     let path = flat_todo.path.clone();
+                    let task_id = flat_todo.todo.id.clone();
                     drop(flat);
-                    todos.borrow_mut().remove_at_path(&path);
+                    todos.borrow_mut().abandon_at_path(&path);
+                    refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
+                    // Find the task by ID after refresh (it may have moved)
+                    let new_flat = refresh_flat_todos.borrow();
+                    let new_index = new_flat.iter().position(|ft| ft.todo.id == task_id).unwrap_or(index);
+                    drop(new_flat);
+                    if let Some(new_row) = refresh_list_box.row_at_index(new_index as i32) {
+                        refresh_list_box.select_row(Some(&new_row));
+                    }
+                }
+            }
+        }
+        Action::Delete => {
+            if let Some(row) = list_box.selected_row() {
+                let index = row.index() as usize;
+                let flat = flat_todos.borrow();
+                if let Some(flat_todo) = flat.get(index) {
+                    let path = flat_todo.path.clone();
+                    drop(flat);
+                    let deleted = Trash::delete_at_path(&mut todos.borrow_mut(), &path);
+                    if let Err(err) = deleted {
+                        show_notification(notification_label, &format!("Could not delete task: {}", err), true);
+                        return gdk::glib::Propagation::Stop;
+                    }
                     refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
                     let new_count = refresh_flat_todos.borrow().len() as i32;
                     if new_count > 0 {
@@ -720,8 +1497,17 @@ fn execute_action(
                 let flat = flat_todos.borrow();
                 if let Some(flat_todo) = flat.get(index) {
                     let id = flat_todo.todo.id.clone();
+                    let is_group = flat_todo.hidden_count > 0;
                     drop(flat);
-                    todos.borrow_mut().toggle_fold(&id);
+                    if is_group {
+                        // Open or close a "Completed (N)" row
+                        let mut settings = refresh_display_settings.borrow_mut();
+                        if !settings.expanded_groups.remove(&id) {
+                            settings.expanded_groups.insert(id);
+                        }
+                    } else {
+                        todos.borrow_mut().toggle_fold(&id);
+                    }
                     refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
                     if let Some(new_row) = refresh_list_box.row_at_index(index as i32) {
                         refresh_list_box.select_row(Some(&new_row));
@@ -729,6 +1515,66 @@ fn execute_action(
                 }
             }
         }
+        Action::HoistIn => {
+            if let Some(flat_todo) = selected_flat_todo(list_box, flat_todos) {
+                todos.borrow_mut().hoist(&flat_todo.todo.id);
+                update_breadcrumb(breadcrumb_label, todos);
+                refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
+                if let Some(first) = refresh_list_box.row_at_index(0) {
+                    refresh_list_box.select_row(Some(&first));
+                }
+            }
+        }
+        Action::HoistOut => {
+            let previous = todos.borrow_mut().unhoist();
+            if let Some(previous) = previous {
+                update_breadcrumb(breadcrumb_label, todos);
+                refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
+                // Keep the task we came out of selected
+                select_row_by_id(refresh_list_box, refresh_flat_todos, &previous);
+            }
+        }
+        Action::ToggleTimer => {
+            if let Some(row) = list_box.selected_row() {
+                let index = row.index();
+                let flat = flat_todos.borrow();
+                if let Some(flat_todo) = flat.get(index as usize) {
+                    let path = flat_todo.path.clone();
+                    drop(flat);
+                    todos.borrow_mut().toggle_timer_at_path(&path);
+                    refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
+                    if let Some(new_row) = refresh_list_box.row_at_index(index) {
+                        refresh_list_box.select_row(Some(&new_row));
+                    }
+                }
+            }
+        }
+        Action::DateForward | Action::DateBack | Action::ClearDate => {
+            if let Some(row) = list_box.selected_row() {
+                let index = row.index();
+                let flat = flat_todos.borrow();
+                if let Some(flat_todo) = flat.get(index as usize) {
+                    let path = flat_todo.path.clone();
+                    drop(flat);
+                    reschedule_task(action, todos, &path);
+                    refresh_list_with_settings(refresh_todos, refresh_list_box, refresh_flat_todos, refresh_display_settings);
+                    if let Some(new_row) = refresh_list_box.row_at_index(index) {
+                        refresh_list_box.select_row(Some(&new_row));
+                    }
+                }
+            }
+        }
+        Action::SetDate => {
+            if let Some(row) = list_box.selected_row() {
+                let index = row.index() as usize;
+                let flat = flat_todos.borrow();
+                if let Some(flat_todo) = flat.get(index) {
+                    let path = flat_todo.path.clone();
+                    drop(flat);
+                    start_date_prompt(path, input_mode, mode_label, command_entry);
+                }
+            }
+        }
         Action::Insert => {
             *input_mode.borrow_mut() = InputMode::Insert;
             mode_label.set_text("INSERT");
@@ -745,6 +1591,7 @@ fn execute_action(
                 let input_mode_c = input_mode.clone();
                 let mode_label_c = mode_label.clone();
                 let inline_entry_row_c = inline_entry_row.clone();
+                let notification_label_c = notification_label.clone();
 
                 entry.connect_activate(move |e| {
                     let text = e.text().to_string();
@@ -756,9 +1603,13 @@ fn execute_action(
                             }
                         } else {
                             let (text_after_priority, priority) = parse_priority(&text);
-                            let (task_text, due_date) = parse_date(&text_after_priority);
+                            let (text_after_date, due_date) = parse_date(&text_after_priority);
+                            let (text_after_estimate, estimate) = parse_estimate(&text_after_date);
+                            let (task_text, dep_prefixes) = parse_dependencies(&text_after_estimate);
                             if !task_text.trim().is_empty() {
-                                let todo = Todo::new(task_text, due_date, priority);
+                                let mut todo = Todo::new(task_text, due_date, priority);
+                                todo.estimate = estimate;
+                                todo.depends_on = resolve_dependencies(&todos_c.borrow(), &dep_prefixes, &notification_label_c);
                                 todos_c.borrow_mut().add(todo);
                             }
                         }
@@ -785,6 +1636,11 @@ fn execute_action(
                 let index = row.index() as usize;
                 let flat = flat_todos.borrow();
                 if let Some(flat_todo) = flat.get(index) {
+                    // The "Completed (N)" row isn't a task that can hold subtasks
+                    if flat_todo.hidden_count > 0 {
+                        show_notification(notification_label, "Select a task to add a subtask to", true);
+                        return gdk::glib::Propagation::Stop;
+                    }
                     let path = flat_todo.path.clone();
                     let depth = flat_todo.depth + 1;
                     drop(flat);
@@ -805,15 +1661,20 @@ fn execute_action(
                         let input_mode_c = input_mode.clone();
                         let mode_label_c = mode_label.clone();
                         let inline_entry_row_c = inline_entry_row.clone();
+                        let notification_label_c = notification_label.clone();
                         let path_c = path.clone();
 
                         entry.connect_activate(move |e| {
                             let text = e.text().to_string();
                             if !text.trim().is_empty() {
                                 let (text_after_priority, priority) = parse_priority(&text);
-                                let (task_text, due_date) = parse_date(&text_after_priority);
+                                let (text_after_date, due_date) = parse_date(&text_after_priority);
+                                let (text_after_estimate, estimate) = parse_estimate(&text_after_date);
+                                let (task_text, dep_prefixes) = parse_dependencies(&text_after_estimate);
                                 if !task_text.trim().is_empty() {
-                                    let todo = Todo::new(task_text, due_date, priority);
+                                    let mut todo = Todo::new(task_text, due_date, priority);
+                                    todo.estimate = estimate;
+                                    todo.depends_on = resolve_dependencies(&todos_c.borrow(), &dep_prefixes, &notification_label_c);
                                     todos_c.borrow_mut().add_subtask(&path_c, todo);
                                 }
                             }
@@ -854,17 +1715,26 @@ fn execute_action(
             }
         }
         Action::CommandMode => {
-            *input_mode.borrow_mut() = InputMode::Command;
-            mode_label.set_text("COMMAND");
-            command_entry.set_placeholder_text(Some(""));
-            command_entry.set_text(":");
-            command_entry.set_sensitive(true);
-            command_entry.grab_focus();
-            command_entry.set_position(-1);
-        }
-        Action::Cancel => {
+            start_command_mode(input_mode, mode_label, command_entry);
+        }
+        Action::Cancel | Action::HistoryPrev | Action::HistoryNext | Action::Complete => {
             // Handled in the main key handler
         }
+        Action::PrevDay
+        | Action::NextDay
+        | Action::PrevWeek
+        | Action::NextWeek
+        | Action::PrevMonth
+        | Action::NextMonth
+        | Action::NextDayTask
+        | Action::PrevDayTask
+        | Action::PrevColumn
+        | Action::NextColumn
+        | Action::MoveCardPrev
+        | Action::MoveCardNext
+        | Action::Restore => {
+            // Other views only
+        }
     }
     gdk::glib::Propagation::Stop
 }
@@ -878,11 +1748,25 @@ impl ZapWindow {
         let mode_label = self.mode_label.clone();
         let notification_label = self.notification_label.clone();
         let input_mode = self.input_mode.clone();
-        let display_settings = self.display_settings.clone();
+        let focus = self.focus.clone();
+        let focus_label = self.focus_label.clone();
+        let window = self.window.clone();
+        let breadcrumb_label = self.breadcrumb_label.clone();
+        let repo = self.repo.clone();
+        let git_config = self.git_config.clone();
+        let git_busy = self.git_busy.clone();
+        let keybindings = self.keybindings.clone();
+        let css_provider = self.css_provider.clone();
 
         self.command_entry.connect_activate(move |e| {
             let text = e.text().to_string();
             let mode = input_mode.borrow().clone();
+            match mode {
+                InputMode::Command => record_history(HistoryKind::Command, &text),
+                InputMode::Edit(_) | InputMode::CalendarInsert(_) => record_history(HistoryKind::Task, &text),
+                InputMode::SetDate(_) => record_history(HistoryKind::Date, &text),
+                _ => {}
+            }
 
             // Hide any previous notification
             notification_label.set_visible(false);
@@ -913,6 +1797,7 @@ impl ZapWindow {
             let todos = tab.todos.clone();
             let list_box = tab.list_box.clone();
             let flat_todos = tab.flat_todos.clone();
+            let display_settings = tab.display_settings.clone();
             drop(tabs_ref);
 
             match mode {
@@ -936,27 +1821,20 @@ impl ZapWindow {
                         notification_label.set_visible(true);
                     } else if cmd == ":e calendar" || cmd == ":e cal" {
                         // Switch to calendar view
-                        let mut tabs_mut = tabs.borrow_mut();
-                        let tab = &mut tabs_mut[current_page];
-                        *tab.view_type.borrow_mut() = ViewType::Calendar;
-
-                        // Create calendar if not exists
-                        if tab.calendar_state.borrow().is_none() {
-                            create_calendar_view(&tab.scrolled_calendar, &tab.calendar_state);
-                        } else {
-                            refresh_calendar_view(&tab.calendar_state);
-                        }
-
-                        tab.content_stack.set_visible_child_name("calendar");
-                        // Update tab label
-                        if let Some(page_widget) = notebook.nth_page(Some(current_page as u32)) {
-                            let label = if tab.cluster_name.is_empty() {
-                                "[calendar]".to_string()
-                            } else {
-                                format!("{} [cal]", tab.cluster_name)
-                            };
-                            notebook.set_tab_label_text(&page_widget, &label);
-                        }
+                        let tabs_ref = tabs.borrow();
+                        show_calendar_view(&tabs_ref[current_page], &notebook, current_page);
+                    } else if cmd == ":e week" {
+                        // Switch to week view
+                        let tabs_ref = tabs.borrow();
+                        show_week_view(&tabs_ref[current_page], &notebook, current_page);
+                    } else if cmd == ":e board" || cmd == ":e board status" {
+                        // Switch to board view grouped by status
+                        let tabs_ref = tabs.borrow();
+                        show_board_view(&tabs_ref[current_page], &notebook, current_page, BoardGrouping::Status);
+                    } else if cmd == ":e board priority" {
+                        // Switch to board view grouped by priority
+                        let tabs_ref = tabs.borrow();
+                        show_board_view(&tabs_ref[current_page], &notebook, current_page, BoardGrouping::Priority);
                     } else if cmd == ":e list" {
                         // Switch back to list view
                         let mut tabs_mut = tabs.borrow_mut();
@@ -978,23 +1856,11 @@ impl ZapWindow {
                         let cluster_name = cluster_name.trim();
                         // Handle calendar/list as special cases (fallback)
                         if cluster_name == "calendar" || cluster_name == "cal" {
-                            let mut tabs_mut = tabs.borrow_mut();
-                            let tab = &mut tabs_mut[current_page];
-                            *tab.view_type.borrow_mut() = ViewType::Calendar;
-                            if tab.calendar_state.borrow().is_none() {
-                                create_calendar_view(&tab.scrolled_calendar, &tab.calendar_state);
-                            } else {
-                                refresh_calendar_view(&tab.calendar_state);
-                            }
-                            tab.content_stack.set_visible_child_name("calendar");
-                            if let Some(page_widget) = notebook.nth_page(Some(current_page as u32)) {
-                                let label = if tab.cluster_name.is_empty() {
-                                    "[calendar]".to_string()
-                                } else {
-                                    format!("{} [cal]", tab.cluster_name)
-                                };
-                                notebook.set_tab_label_text(&page_widget, &label);
-                            }
+                            let tabs_ref = tabs.borrow();
+                            show_calendar_view(&tabs_ref[current_page], &notebook, current_page);
+                        } else if cluster_name == "week" {
+                            let tabs_ref = tabs.borrow();
+                            show_week_view(&tabs_ref[current_page], &notebook, current_page);
                         } else if cluster_name == "list" {
                             let mut tabs_mut = tabs.borrow_mut();
                             let tab = &mut tabs_mut[current_page];
@@ -1010,19 +1876,11 @@ impl ZapWindow {
                             }
                             tab.list_box.grab_focus();
                         } else if !cluster_name.is_empty() {
-                            let path = TodoList::cluster_path(cluster_name);
-                            if path.exists() {
-                                *todos.borrow_mut() = TodoList::load(cluster_name);
-                                // Update the tab label
-                                if let Some(page_widget) = notebook.nth_page(Some(current_page as u32)) {
-                                    notebook.set_tab_label_text(&page_widget, cluster_name);
-                                }
-                                // Update stored cluster name
-                                tabs.borrow_mut()[current_page].cluster_name = cluster_name.to_string();
-                                // Switch to list view
-                                tabs.borrow_mut()[current_page].content_stack.set_visible_child_name("list");
-                                *tabs.borrow_mut()[current_page].view_type.borrow_mut() = ViewType::List;
-                                refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                            if TodoList::is_encrypted(cluster_name) && crypto::key_for(cluster_name).is_none() {
+                                let prompt = PassphrasePrompt::Unlock(cluster_name.to_string());
+                                start_passphrase_prompt(prompt, &input_mode, &mode_label, e);
+                            } else if TodoList::exists(cluster_name) {
+                                open_cluster_in_tab(&tabs, current_page, cluster_name, &notebook, &notification_label, &breadcrumb_label);
                             } else {
                                 notification_label.set_text(&format!("Cluster '{}' does not exist. Use :n to create.", cluster_name));
                                 notification_label.add_css_class("notification-error");
@@ -1036,29 +1894,282 @@ impl ZapWindow {
                             let new_list = TodoList::load(cluster_name);
                             new_list.save(); // Create the file
                             *todos.borrow_mut() = new_list;
+                            update_breadcrumb(&breadcrumb_label, &todos);
                             // Update the tab label
                             if let Some(page_widget) = notebook.nth_page(Some(current_page as u32)) {
                                 notebook.set_tab_label_text(&page_widget, cluster_name);
                             }
                             // Update stored cluster name
                             tabs.borrow_mut()[current_page].cluster_name = cluster_name.to_string();
-                            notification_label.set_text(&format!("Created cluster '{}'", cluster_name));
-                            notification_label.remove_css_class("notification-error");
-                            notification_label.set_visible(true);
+                            match todos.borrow().load_error() {
+                                Some(err) => show_notification(&notification_label, err, true),
+                                None => {
+                                    notification_label.set_text(&format!("Created cluster '{}'", cluster_name));
+                                    notification_label.remove_css_class("notification-error");
+                                    notification_label.set_visible(true);
+                                }
+                            }
+                            refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                        }
+                    } else if cmd == ":archive-done" || cmd.starts_with(":archive-done ") {
+                        // Move closed tasks (optionally only those closed N days ago) to the archive
+                        let arg = cmd[":archive-done".len()..].trim();
+                        let days = if arg.is_empty() { Ok(None) } else { arg.parse::<u32>().map(Some) };
+                        if todos.borrow().is_read_only() {
+                            show_notification(&notification_label, "Already in an archive", true);
+                        } else if let Ok(days) = days {
+                            let cutoff = days.map(|d| Utc::now().timestamp() - d as i64 * 86400);
+                            let archived = todos.borrow_mut().archive_done(cutoff);
                             refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                            match archived {
+                                Ok(count) => {
+                                    let message = format!("Archived {} task{}", count, if count == 1 { "" } else { "s" });
+                                    show_notification(&notification_label, &message, false);
+                                }
+                                Err(err) => show_notification(&notification_label, &format!("Could not archive: {}", err), true),
+                            }
+                        } else {
+                            show_notification(&notification_label, "Usage: :archive-done [DAYS]", true);
+                        }
+                    } else if cmd == ":trash" {
+                        // Show deleted tasks of all clusters
+                        let tabs_ref = tabs.borrow();
+                        show_trash_view(&tabs_ref[current_page], &notebook, current_page);
+                    } else if cmd == ":restore" && *tabs.borrow()[current_page].view_type.borrow() == ViewType::Trash {
+                        // Put the selected deleted task back where it was
+                        let trash_state = tabs.borrow()[current_page].trash_state.clone();
+                        match restore_from_trash(&tabs, &trash_state) {
+                            Ok(message) => show_notification(&notification_label, &message, false),
+                            Err(message) => show_notification(&notification_label, &message, true),
+                        }
+                    } else if cmd == ":restore" {
+                        // Move the selected archived task back into its cluster
+                        let archive_name = todos.borrow().cluster_name().to_string();
+                        let cluster = archive_name.strip_suffix(ARCHIVE_SUFFIX).map(str::to_string);
+                        let selected = selected_flat_todo(&list_box, &flat_todos).filter(|ft| !ft.path.is_empty());
+                        match (cluster, selected) {
+                            (Some(cluster), Some(flat_todo)) => {
+                                let restored = with_cluster(&tabs, &cluster, |list| {
+                                    list.restore_archived(&mut todos.borrow_mut(), &flat_todo.todo.id)
+                                });
+                                refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                match restored {
+                                    Ok(()) => show_notification(&notification_label, &format!("Restored to '{}'", cluster), false),
+                                    Err(err) => show_notification(&notification_label, &format!("Could not restore task: {}", err), true),
+                                }
+                            }
+                            (None, _) => show_notification(&notification_label, "Not viewing an archive", true),
+                            (_, None) => show_notification(&notification_label, "No task selected", true),
+                        }
+                    } else if cmd == ":log" || cmd.starts_with(":revert") || cmd == ":sync" {
+                        // Git history of the data dir
+                        let cluster = todos.borrow().cluster_name().to_string();
+                        match &repo {
+                            None => show_notification(&notification_label, "Git history is off (set \"enabled\" in git.json)", true),
+                            Some(_) if git_busy.get() => show_notification(&notification_label, "Git is busy; try again", true),
+                            Some(repo) if cmd == ":log" => {
+                                // Commits the latest saves first, off the main loop like the interval commits
+                                let name = cluster.clone();
+                                let label = notification_label.clone();
+                                run_git(repo, &git_busy, move |repo| repo.commit_pending().and_then(|_| repo.log(&name, 10)), move |result| match result {
+                                    Ok(entries) if entries.is_empty() => {
+                                        show_notification(&label, &format!("No history for '{}'", cluster), false);
+                                    }
+                                    Ok(entries) => {
+                                        // Stays up like :ls; numbers are what :revert takes
+                                        let lines: Vec<String> = entries
+                                            .iter()
+                                            .enumerate()
+                                            .map(|(i, entry)| format!("{:>2}  {}  {:<16} {}", i + 1, entry.hash, entry.when, entry.message))
+                                            .collect();
+                                        label.set_text(&lines.join("\n"));
+                                        label.remove_css_class("notification-error");
+                                        label.set_visible(true);
+                                    }
+                                    Err(err) => show_notification(&label, &err, true),
+                                });
+                            }
+                            Some(repo) if cmd == ":sync" => {
+                                // Pulling and pushing can take a while on a slow network
+                                let branch = git_config.branch.clone();
+                                let tabs = tabs.clone();
+                                let label = notification_label.clone();
+                                run_git(repo, &git_busy, move |repo| repo.sync(&branch), move |result| match result {
+                                    Ok(()) => {
+                                        // Pick up pulled changes right away
+                                        let clusters: Vec<String> = tabs.borrow().iter().map(|t| t.cluster_name.clone()).collect();
+                                        for cluster in clusters {
+                                            reload_changed_cluster(&tabs, &cluster, &label);
+                                        }
+                                        show_notification(&label, "Synced", false);
+                                    }
+                                    Err(err) => show_notification(&label, &format!("Sync failed: {}", err), true),
+                                });
+                                show_notification(&notification_label, "Syncing...", false);
+                            }
+                            Some(repo) => {
+                                // :revert N restores the cluster as of entry N of :log
+                                let n = cmd[":revert".len()..].trim().parse::<usize>().unwrap_or(0);
+                                let entry = repo.log(&cluster, n).ok().and_then(|entries| entries.into_iter().nth(n.wrapping_sub(1)));
+                                match entry {
+                                    Some(entry) => match repo.revert(&cluster, &entry.hash) {
+                                        Ok(()) => {
+                                            todos.borrow_mut().reload();
+                                            refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                            let message = format!("Reverted '{}' to {} ({})", cluster, entry.hash, entry.when);
+                                            show_notification(&notification_label, &message, false);
+                                        }
+                                        Err(err) => show_notification(&notification_label, &err, true),
+                                    },
+                                    None => show_notification(&notification_label, "Usage: :revert N (N from :log)", true),
+                                }
+                            }
+                        }
+                    } else if cmd == ":resolve" {
+                        // Merge sync conflict copies back into their clusters
+                        let files = find_conflict_files();
+                        let mut conflicts = Vec::new();
+                        let mut merged = 0;
+                        // Copies that couldn't be merged stay where they are
+                        let mut left = Vec::new();
+                        for (cluster, path) in &files {
+                            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                            let remote = match TodoList::read(path) {
+                                Some(remote) => remote,
+                                None => {
+                                    left.push(format!("{} (can't be read)", name));
+                                    continue;
+                                }
+                            };
+                            let base = TodoList::load_merge_base(cluster).map(|b| b.todos).unwrap_or_default();
+                            let result = with_cluster(&tabs, cluster, |list| {
+                                if list.is_read_only() {
+                                    return Err(format!("'{}' is read-only", cluster));
+                                }
+                                let result = merge(&base, &list.todos, &remote.todos);
+                                let local = std::mem::replace(&mut list.todos, result.todos);
+                                if let Err(err) = list.try_save() {
+                                    list.todos = local;
+                                    return Err(err);
+                                }
+                                list.save_merge_base();
+                                Ok(result.conflicts)
+                            });
+                            match result {
+                                Ok(cluster_conflicts) => {
+                                    fs::remove_file(path).ok();
+                                    merged += 1;
+                                    conflicts.extend(cluster_conflicts);
+                                }
+                                Err(err) => left.push(format!("{} ({})", name, err)),
+                            }
+                        }
+                        if files.is_empty() {
+                            show_notification(&notification_label, "No sync conflicts", false);
+                        } else {
+                            let mut message = format!("Merged {} conflict file(s)", merged);
+                            if !conflicts.is_empty() {
+                                let details: Vec<String> = conflicts
+                                    .iter()
+                                    .map(|c| format!("'{}' ({})", c.text, c.field))
+                                    .collect();
+                                message.push_str(&format!("; kept local version of {}", details.join(", ")));
+                            }
+                            if !left.is_empty() {
+                                message.push_str(&format!("; left {}", left.join(", ")));
+                            }
+                            show_notification(&notification_label, &message, !conflicts.is_empty() || !left.is_empty());
+                        }
+                    } else if let Some(target) = cmd.strip_prefix(":migrate") {
+                        // Copy all clusters to another storage backend and switch to it
+                        match Backend::parse(target.trim()) {
+                            Some(target) if target == current_backend() => {
+                                show_notification(&notification_label, &format!("Already using {}", target.name()), false);
+                            }
+                            Some(target) => {
+                                for tab in tabs.borrow().iter() {
+                                    tab.todos.borrow().save();
+                                }
+                                match switch_backend(target) {
+                                    Ok(count) => {
+                                        let message = format!("Migrated {} cluster(s) to {}", count, target.name());
+                                        show_notification(&notification_label, &message, false);
+                                    }
+                                    Err(err) => show_notification(&notification_label, &format!("Migration failed: {}", err), true),
+                                }
+                            }
+                            None => show_notification(&notification_label, "Usage: :migrate json|sqlite", true),
+                        }
+                    } else if cmd == ":encrypt" {
+                        let cluster = todos.borrow().cluster_name().to_string();
+                        if todos.borrow().is_read_only() {
+                            show_notification(&notification_label, "Can't encrypt a read-only cluster", true);
+                        } else if TodoList::is_encrypted(&cluster) {
+                            show_notification(&notification_label, &format!("'{}' is already encrypted", cluster), false);
+                        } else {
+                            start_passphrase_prompt(PassphrasePrompt::Encrypt, &input_mode, &mode_label, e);
+                        }
+                    } else if cmd == ":decrypt" {
+                        let cluster = todos.borrow().cluster_name().to_string();
+                        match todos.borrow().decrypt() {
+                            Ok(()) => show_notification(&notification_label, &format!("'{}' is stored in plain text", cluster), false),
+                            Err(err) => show_notification(&notification_label, &err, true),
+                        }
+                    } else if cmd == ":set" || cmd.starts_with(":set ") {
+                        // :set shows all options, :set option? one, :set option=value changes it
+                        let arg = cmd[":set".len()..].trim();
+                        let settings = Settings::current();
+                        if arg.is_empty() {
+                            let values: Vec<String> = OPTIONS
+                                .iter()
+                                .filter_map(|option| settings.get(option).map(|value| format!("{}={}", option, value)))
+                                .collect();
+                            show_notification(&notification_label, &values.join("  "), false);
+                        } else if let Some((option, value)) = arg.split_once('=') {
+                            let option = option.trim();
+                            match Settings::update(|settings| settings.set(option, value.trim())) {
+                                Ok(()) => {
+                                    if option == "week_start" {
+                                        refresh_date_views(&tabs);
+                                    }
+                                    let value = Settings::current().get(option).unwrap_or_default();
+                                    show_notification(&notification_label, &format!("{}={}", option, value), false);
+                                }
+                                Err(err) => show_notification(&notification_label, &err, true),
+                            }
+                        } else {
+                            let option = arg.trim_end_matches('?');
+                            match settings.get(option) {
+                                Some(value) => show_notification(&notification_label, &format!("{}={}", option, value), false),
+                                None => show_notification(&notification_label, &format!("Unknown option: {}", option), true),
+                            }
+                        }
+                    } else if cmd == ":reload" {
+                        // Files that don't parse are reported and keep their current values
+                        let problems: Vec<String> = [Keybindings::FILE, ColorConfig::FILE, Settings::FILE]
+                            .iter()
+                            .filter_map(|name| reload_config_file(name, &keybindings, &css_provider, &tabs))
+                            .flatten()
+                            .collect();
+                        if problems.is_empty() {
+                            show_notification(&notification_label, "Reloaded keybindings, colors and settings", false);
+                        } else {
+                            show_notification(&notification_label, &problems.join("\n"), true);
+                        }
+                    } else if cmd == ":checkbindings" {
+                        let problems = Keybindings::check();
+                        if problems.is_empty() {
+                            show_notification(&notification_label, "No problems in keybindings.json", false);
+                        } else {
+                            show_notification(&notification_label, &problems.join("\n"), true);
                         }
                     } else if cmd == ":sort" {
                         // Sort tasks by priority, date, then alphabetically
-                        todos.borrow_mut().sort();
-                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
-                        notification_label.set_text("Tasks sorted");
-                        notification_label.remove_css_class("notification-error");
-                        notification_label.set_visible(true);
-                        let notification_label = notification_label.clone();
-                        gtk4::glib::timeout_add_local(std::time::Duration::from_secs(3), move || {
-                            notification_label.set_visible(false);
-                            gtk4::glib::ControlFlow::Break
-                        });
+                        if !refuse_read_only(&todos, &notification_label) {
+                            todos.borrow_mut().sort();
+                            refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                            show_notification(&notification_label, "Tasks sorted", false);
+                        }
                     } else if cmd == ":flatten" {
                         // Toggle flattened view
                         let mut settings = display_settings.borrow_mut();
@@ -1066,23 +2177,229 @@ impl ZapWindow {
                         let is_flat = settings.flattened;
                         drop(settings);
                         refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
-                        notification_label.set_text(if is_flat { "Flattened view" } else { "Hierarchical view" });
-                        notification_label.remove_css_class("notification-error");
-                        notification_label.set_visible(true);
-                        let notification_label = notification_label.clone();
-                        gtk4::glib::timeout_add_local(std::time::Duration::from_secs(2), move || {
-                            notification_label.set_visible(false);
-                            gtk4::glib::ControlFlow::Break
-                        });
+                        show_notification(&notification_label, if is_flat { "Flattened view" } else { "Hierarchical view" }, false);
+                    } else if let Some(name) = cmd.strip_prefix(":mksession ") {
+                        // Save the open tabs under a name
+                        let name = name.trim();
+                        if Session::is_valid_name(name) {
+                            capture_session(&tabs, &notebook).save(name);
+                            show_notification(&notification_label, &format!("Saved session '{}'", name), false);
+                        } else {
+                            show_notification(&notification_label, &format!("Invalid session name '{}'", name), true);
+                        }
+                    } else if let Some(name) = cmd.strip_prefix(":source ") {
+                        // Replace the open tabs with a saved session
+                        let name = name.trim();
+                        match Session::load(name).filter(|_| Session::is_valid_name(name)) {
+                            Some(session) => {
+                                e.set_text("");
+                                e.set_sensitive(false);
+                                *input_mode.borrow_mut() = InputMode::Normal;
+                                mode_label.set_text("NORMAL");
+                                if !restore_session(&session, &tabs, &notebook, &breadcrumb_label, &notification_label) {
+                                    show_notification(&notification_label, &format!("Session '{}' has no tabs", name), true);
+                                }
+                                return;
+                            }
+                            None => show_notification(&notification_label, &format!("No session '{}'", name), true),
+                        }
+                    } else if cmd == ":focus stop" || cmd == ":unfocus" {
+                        stop_focus(&focus, &focus_label);
+                        for tab in tabs.borrow().iter() {
+                            if tab.display_settings.borrow_mut().focused_id.take().is_some() {
+                                refresh_list_with_settings(&tab.todos, &tab.list_box, &tab.flat_todos, &tab.display_settings);
+                            }
+                        }
+                    } else if cmd == ":focus" || cmd.starts_with(":focus ") {
+                        // Start a pomodoro cycle on the selected task, with optional
+                        // work and break minutes overriding the config
+                        let mut config = FocusConfig::load();
+                        let args: Vec<&str> = cmd[":focus".len()..].split_whitespace().collect();
+                        let minutes: Vec<u32> = args.iter().filter_map(|a| a.parse().ok()).collect();
+                        if minutes.len() != args.len() || minutes.contains(&0) {
+                            show_notification(&notification_label, "Usage: :focus [work minutes] [break minutes]", true);
+                        } else if let Some(flat_todo) = selected_flat_todo(&list_box, &flat_todos) {
+                            if let Some(&work) = minutes.first() {
+                                config.work_minutes = work;
+                            }
+                            if let Some(&rest) = minutes.get(1) {
+                                config.break_minutes = rest;
+                                config.long_break_minutes = config.long_break_minutes.max(rest);
+                            }
+                            start_focus(&focus, window.application(), &focus_label, &todos, &flat_todo.todo.id, config);
+                            // Only one task is focused at a time
+                            for tab in tabs.borrow().iter() {
+                                tab.display_settings.borrow_mut().focused_id = None;
+                            }
+                            display_settings.borrow_mut().focused_id = focused_task(&focus);
+                            refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                            select_row_by_id(&list_box, &flat_todos, &flat_todo.todo.id);
+                        }
+                    } else if cmd == ":timer" {
+                        // Show which task the timer is running on
+                        let todos_ref = todos.borrow();
+                        let text = match todos_ref.running_timer() {
+                            Some(todo) => {
+                                let started = todo.sessions.last().map_or(0, |s| s.start);
+                                let minutes = (Utc::now().timestamp() - started) / 60;
+                                format!("Timing '{}' for {}", todo.text, format_minutes(minutes))
+                            }
+                            None => "No timer running".to_string(),
+                        };
+                        show_notification(&notification_label, &text, false);
+                    } else if cmd == ":report time" || cmd.starts_with(":report time ") {
+                        // Summarize tracked time per cluster
+                        let range = cmd[":report time".len()..].trim().to_lowercase();
+                        match parse_report_range(&range, Local::now().date_naive()) {
+                            Some((from, to)) => show_notification(&notification_label, &time_report(from, to), false),
+                            None => show_notification(&notification_label, &format!("Could not parse range '{}'", range), true),
+                        }
+                    } else if cmd == ":filter" || cmd.starts_with(":filter ") {
+                        // Only show tasks containing the text (and their parents); no text clears
+                        let text = cmd[":filter".len()..].trim().to_lowercase();
+                        let message = if text.is_empty() { "Filter cleared".to_string() } else { format!("Filter: {}", text) };
+                        display_settings.borrow_mut().filter = if text.is_empty() { None } else { Some(text) };
+                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                        show_notification(&notification_label, &message, false);
+                    } else if cmd == ":sortby" || cmd.starts_with(":sortby ") {
+                        // Change this tab's row order without touching the cluster
+                        let arg = cmd[":sortby".len()..].trim().to_lowercase();
+                        let mode = if arg.is_empty() { Some(SortMode::Manual) } else { SortMode::parse(&arg) };
+                        match mode {
+                            Some(mode) => {
+                                display_settings.borrow_mut().sort_mode = mode;
+                                refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                show_notification(&notification_label, &format!("Sorted by {}", mode.name()), false);
+                            }
+                            None => show_notification(&notification_label, "Sort by: manual, priority, due, alpha, created", true),
+                        }
+                    } else if matches!(cmd, ":show done" | ":show abandoned" | ":hide done" | ":hide abandoned") {
+                        // :show lists closed tasks in place, :hide toggles collapsing them
+                        // into a "Completed (N)" row at the bottom of each level
+                        let mut settings = display_settings.borrow_mut();
+                        let (shown, what) = match cmd {
+                            ":show done" => {
+                                settings.show_completed = true;
+                                (true, "Completed")
+                            }
+                            ":show abandoned" => {
+                                settings.show_abandoned = true;
+                                (true, "Abandoned")
+                            }
+                            ":hide done" => {
+                                settings.show_completed = !settings.show_completed;
+                                (settings.show_completed, "Completed")
+                            }
+                            _ => {
+                                settings.show_abandoned = !settings.show_abandoned;
+                                (settings.show_abandoned, "Abandoned")
+                            }
+                        };
+                        drop(settings);
+                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                        let message = format!("{} tasks {}", what, if shown { "shown" } else { "hidden" });
+                        show_notification(&notification_label, &message, false);
+                    } else if cmd == ":autohide" || cmd.starts_with(":autohide ") {
+                        // Collapse tasks closed more than N days ago; "off" disables
+                        let arg = cmd[":autohide".len()..].trim();
+                        let days = match arg {
+                            "" | "off" => Ok(None),
+                            n => n.parse::<u32>().map(Some),
+                        };
+                        match days {
+                            Ok(days) => {
+                                display_settings.borrow_mut().auto_hide_days = days;
+                                refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                let message = match days {
+                                    Some(n) => format!("Hiding tasks closed more than {} days ago", n),
+                                    None => "Auto-hide off".to_string(),
+                                };
+                                show_notification(&notification_label, &message, false);
+                            }
+                            Err(_) => show_notification(&notification_label, "Usage: :autohide DAYS|off", true),
+                        }
+                    } else if cmd == ":next" {
+                        // Toggle the next-actions filter
+                        let mut settings = display_settings.borrow_mut();
+                        settings.next_actions = !settings.next_actions;
+                        let is_next = settings.next_actions;
+                        drop(settings);
+                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                        show_notification(&notification_label, if is_next { "Next actions" } else { "All tasks" }, false);
+                    } else if cmd == ":id" {
+                        // Show the selected task's id, for use with [after:...] and :dep
+                        if let Some(flat_todo) = selected_flat_todo(&list_box, &flat_todos) {
+                            show_notification(&notification_label, &format!("Id: {}", short_id(&flat_todo.todo.id)), false);
+                        }
+                    } else if cmd == ":dep" {
+                        // Show what the selected task depends on
+                        if let Some(flat_todo) = selected_flat_todo(&list_box, &flat_todos) {
+                            let todos_ref = todos.borrow();
+                            let deps: Vec<String> = flat_todo.todo.depends_on.iter()
+                                .map(|id| match todos_ref.find_by_id(id) {
+                                    Some(dep) => {
+                                        let mark = if dep.status.is_closed() { "✓" } else { "…" };
+                                        format!("{} {} ({})", mark, dep.text, short_id(id))
+                                    }
+                                    None => format!("? {}", short_id(id)),
+                                })
+                                .collect();
+                            let text = if deps.is_empty() {
+                                "No dependencies".to_string()
+                            } else {
+                                format!("After: {}", deps.join(", "))
+                            };
+                            show_notification(&notification_label, &text, false);
+                        }
+                    } else if let Some(prefix) = cmd.strip_prefix(":dep ") {
+                        // Make the selected task depend on another task
+                        if let Some(flat_todo) = selected_flat_todo(&list_box, &flat_todos) {
+                            if !refuse_read_only(&todos, &notification_label) {
+                                let result = todos.borrow_mut().add_dependency_at_path(&flat_todo.path, prefix);
+                                match result {
+                                    Ok(dep_id) => {
+                                        let text = todos.borrow().find_by_id(&dep_id).map(|t| t.text.clone()).unwrap_or_default();
+                                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                        select_row_by_id(&list_box, &flat_todos, &flat_todo.todo.id);
+                                        show_notification(&notification_label, &format!("Now after '{}'", text), false);
+                                    }
+                                    Err(err) => show_notification(&notification_label, &err.to_string(), true),
+                                }
+                            }
+                        }
+                    } else if cmd == ":undep" {
+                        // Remove all dependencies of the selected task
+                        if let Some(flat_todo) = selected_flat_todo(&list_box, &flat_todos) {
+                            if !refuse_read_only(&todos, &notification_label) {
+                                todos.borrow_mut().clear_dependencies_at_path(&flat_todo.path);
+                                refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                select_row_by_id(&list_box, &flat_todos, &flat_todo.todo.id);
+                            }
+                        }
                     }
                     // Unknown commands are silently ignored
                 }
+                // Archives can be browsed but not edited
+                InputMode::Edit(_) | InputMode::CalendarInsert(_) | InputMode::SetDate(_)
+                    if refuse_read_only(&todos, &notification_label) => {}
                 InputMode::Edit(ref path) => {
                     if !text.trim().is_empty() {
                         let (text_after_priority, priority) = parse_priority(&text);
-                        let (task_text, due_date) = parse_date(&text_after_priority);
+                        let (text_after_date, due_date) = parse_date(&text_after_priority);
+                        let (text_after_estimate, estimate) = parse_estimate(&text_after_date);
+                        let (task_text, dep_prefixes) = parse_dependencies(&text_after_estimate);
                         if !task_text.trim().is_empty() {
                             todos.borrow_mut().update_at_path(path, task_text, due_date, priority);
+                            // Keep the estimate unless a new one was given
+                            if estimate.is_some() {
+                                todos.borrow_mut().set_estimate_at_path(path, estimate);
+                            }
+                            for prefix in &dep_prefixes {
+                                let result = todos.borrow_mut().add_dependency_at_path(path, prefix);
+                                if let Err(err) = result {
+                                    show_notification(&notification_label, &err.to_string(), true);
+                                }
+                            }
                             refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
                         }
                     }
@@ -1098,18 +2415,80 @@ impl ZapWindow {
                             // Refresh calendar view
                             let tabs_ref = tabs.borrow();
                             if let Some(tab) = tabs_ref.get(current_page) {
-                                if *tab.view_type.borrow() == ViewType::Calendar {
-                                    refresh_calendar_view(&tab.calendar_state);
+                                match *tab.view_type.borrow() {
+                                    ViewType::Calendar => refresh_calendar_view(&tab.calendar_state, &todos),
+                                    ViewType::Week => refresh_week_view(&tab.week_state, &todos),
+                                    ViewType::Board => refresh_board_view(&tab.board_state, &todos),
+                                    ViewType::List | ViewType::Trash => {}
+                                }
+                            }
+                        }
+                    }
+                }
+                InputMode::SetDate(ref path) => {
+                    let expr = text.trim().to_lowercase();
+                    let today = Local::now().date_naive();
+                    match try_parse_date(&expr, today) {
+                        Some(date) => {
+                            let task_id = todos.borrow().get_at_path(path).map(|t| t.id.clone());
+                            todos.borrow_mut().set_due_date_at_path(path, Some(date));
+                            let tabs_ref = tabs.borrow();
+                            if let (Some(tab), Some(task_id)) = (tabs_ref.get(current_page), task_id) {
+                                match *tab.view_type.borrow() {
+                                    ViewType::Calendar => select_calendar_task_by_id(&tab.calendar_state, &todos, date, &task_id),
+                                    ViewType::Week => select_week_task_by_id(&tab.week_state, &todos, date, &task_id),
+                                    ViewType::Board => refresh_board_view(&tab.board_state, &todos),
+                                    ViewType::Trash => {}
+                                    ViewType::List => {
+                                        refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+                                        let index = flat_todos.borrow().iter().position(|ft| ft.todo.id == task_id);
+                                        if let Some(row) = index.and_then(|i| list_box.row_at_index(i as i32)) {
+                                            list_box.select_row(Some(&row));
+                                        }
+                                    }
                                 }
                             }
                         }
+                        None if !expr.is_empty() => {
+                            notification_label.set_text(&format!("Could not parse date '{}'", text.trim()));
+                            notification_label.add_css_class("notification-error");
+                            notification_label.set_visible(true);
+                        }
+                        None => {}
                     }
                 }
+                InputMode::Passphrase(ref prompt) => match prompt {
+                    PassphrasePrompt::Unlock(cluster) => match TodoList::unlock(cluster, &text) {
+                        Ok(()) => open_cluster_in_tab(&tabs, current_page, cluster, &notebook, &notification_label, &breadcrumb_label),
+                        Err(err) => show_notification(&notification_label, &format!("Can't open '{}': {}", cluster, err), true),
+                    },
+                    PassphrasePrompt::Encrypt if text.is_empty() => {
+                        show_notification(&notification_label, "Passphrase can't be empty", true);
+                    }
+                    PassphrasePrompt::Encrypt => {
+                        start_passphrase_prompt(PassphrasePrompt::ConfirmEncrypt(text.clone()), &input_mode, &mode_label, e);
+                    }
+                    PassphrasePrompt::ConfirmEncrypt(first) if *first != text => {
+                        show_notification(&notification_label, "Passphrases don't match; nothing encrypted", true);
+                    }
+                    PassphrasePrompt::ConfirmEncrypt(_) => {
+                        let cluster = todos.borrow().cluster_name().to_string();
+                        match todos.borrow().encrypt(&text) {
+                            Ok(()) => show_notification(&notification_label, &format!("'{}' is encrypted", cluster), false),
+                            Err(err) => show_notification(&notification_label, &err, true),
+                        }
+                    }
+                },
                 // Insert modes are handled by inline entries, not this handler
                 InputMode::Insert | InputMode::InsertSubtask(_) | InputMode::Normal => {}
             }
 
+            // A command that opened another prompt keeps the command bar
+            if *input_mode.borrow() != mode {
+                return;
+            }
             e.set_text("");
+            e.set_visibility(true);
             e.set_sensitive(false);
             *input_mode.borrow_mut() = InputMode::Normal;
             mode_label.set_text("NORMAL");
@@ -1118,14 +2497,10 @@ impl ZapWindow {
     }
 
     fn apply_css(&self) {
-        let css = self.color_config.generate_css();
-
-        let provider = gtk4::CssProvider::new();
-        provider.load_from_data(&css);
-
+        // The provider stays installed; :reload replaces its contents
         gtk4::style_context_add_provider_for_display(
             &gtk4::prelude::WidgetExt::display(&self.window),
-            &provider,
+            &self.css_provider,
             gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
         );
     }
@@ -1137,18 +2512,36 @@ fn create_todo_row(flat_todo: &FlatTodo, settings: &DisplaySettings) -> ListBoxR
 
     let hbox = GtkBox::new(Orientation::Horizontal, 8);
     // No indentation in flattened mode
-    let indent = if settings.flattened { 0 } else { flat_todo.depth as i32 * 20 };
+    let indent = if settings.is_flat() { 0 } else { flat_todo.depth as i32 * 20 };
     hbox.set_margin_start(8 + indent);
     hbox.set_margin_end(8);
     hbox.set_margin_top(8);
     hbox.set_margin_bottom(8);
 
+    // "Completed (N)" row standing in for hidden closed tasks, opened with the fold key
+    if flat_todo.hidden_count > 0 {
+        row.add_css_class("completed-group-row");
+        let chevron = if flat_todo.is_folded { "▶" } else { "▼" };
+        let chevron_label = Label::new(Some(chevron));
+        chevron_label.add_css_class("fold-chevron");
+        hbox.append(&chevron_label);
+
+        let text_label = Label::new(Some(&flat_todo.todo.text));
+        text_label.set_hexpand(true);
+        text_label.set_halign(gtk4::Align::Start);
+        text_label.add_css_class("completed-group-text");
+        hbox.append(&text_label);
+
+        row.set_child(Some(&hbox));
+        return row;
+    }
+
     // Section rendering - different from regular tasks
     if flat_todo.todo.is_section {
         row.add_css_class("section-row");
 
         // Fold chevron for sections with subtasks (not shown in flattened mode)
-        if flat_todo.has_subtasks && !settings.flattened {
+        if flat_todo.has_subtasks && !settings.is_flat() {
             let chevron = if flat_todo.is_folded { "▶" } else { "▼" };
             let chevron_label = Label::new(Some(chevron));
             chevron_label.add_css_class("fold-chevron");
@@ -1161,7 +2554,7 @@ fn create_todo_row(flat_todo: &FlatTodo, settings: &DisplaySettings) -> ListBoxR
         hbox.append(&marker);
 
         // Hierarchy path for sections (in flattened mode)
-        if settings.flattened && !flat_todo.hierarchy_path.is_empty() {
+        if settings.is_flat() && !flat_todo.hierarchy_path.is_empty() {
             let path_text = format!("{}/", flat_todo.hierarchy_path.join("/"));
             let path_label = Label::new(Some(&path_text));
             path_label.add_css_class("hierarchy-path");
@@ -1181,7 +2574,7 @@ fn create_todo_row(flat_todo: &FlatTodo, settings: &DisplaySettings) -> ListBoxR
 
     // Regular task rendering
     // Fold chevron or subtask indicator (not in flattened mode)
-    if !settings.flattened {
+    if !settings.is_flat() {
         if flat_todo.has_subtasks {
             let chevron = if flat_todo.is_folded { "▶" } else { "▼" };
             let chevron_label = Label::new(Some(chevron));
@@ -1215,21 +2608,34 @@ fn create_todo_row(flat_todo: &FlatTodo, settings: &DisplaySettings) -> ListBoxR
     }
     hbox.append(&priority_label);
 
-    // Checkbox indicator or abandoned marker
-    let check_label = if flat_todo.todo.abandoned {
-        let label = Label::new(Some(" ! "));
-        label.add_css_class("abandoned-marker");
-        row.add_css_class("abandoned-row");
-        label
-    } else {
-        let check = if flat_todo.todo.completed { "[x]" } else { "[ ]" };
-        let label = Label::new(Some(check));
-        label.add_css_class("todo-check");
-        label
+    // Status marker: checkbox, in-progress/blocked markers or abandoned marker
+    let check_label = match flat_todo.todo.status {
+        Status::Abandoned => {
+            let label = Label::new(Some(" ! "));
+            label.add_css_class("abandoned-marker");
+            row.add_css_class("abandoned-row");
+            label
+        }
+        Status::InProgress => {
+            let label = Label::new(Some("[~]"));
+            label.add_css_class("in-progress-marker");
+            label
+        }
+        Status::Blocked => {
+            let label = Label::new(Some("[#]"));
+            label.add_css_class("blocked-marker");
+            label
+        }
+        Status::Done | Status::Todo => {
+            let check = if flat_todo.todo.is_done() { "[x]" } else { "[ ]" };
+            let label = Label::new(Some(check));
+            label.add_css_class("todo-check");
+            label
+        }
     };
 
     // Hierarchy path (shown in flattened mode, styled differently)
-    if settings.flattened && !flat_todo.hierarchy_path.is_empty() {
+    if settings.is_flat() && !flat_todo.hierarchy_path.is_empty() {
         let path_text = format!("{}/", flat_todo.hierarchy_path.join("/"));
         let path_label = Label::new(Some(&path_text));
         path_label.add_css_class("hierarchy-path");
@@ -1240,16 +2646,45 @@ fn create_todo_row(flat_todo: &FlatTodo, settings: &DisplaySettings) -> ListBoxR
     let text_label = Label::new(Some(&flat_todo.todo.text));
     text_label.set_hexpand(true);
     text_label.set_halign(gtk4::Align::Start);
-    if flat_todo.todo.completed {
-        text_label.add_css_class("completed");
-    }
-    if flat_todo.todo.abandoned {
-        text_label.add_css_class("abandoned-text");
+    match flat_todo.todo.status {
+        Status::Done => text_label.add_css_class("completed"),
+        Status::Abandoned => text_label.add_css_class("abandoned-text"),
+        Status::Blocked => text_label.add_css_class("blocked-text"),
+        Status::InProgress | Status::Todo => {}
     }
 
     hbox.append(&check_label);
     hbox.append(&text_label);
 
+    // Waiting on an open dependency
+    if flat_todo.is_blocked {
+        row.add_css_class("blocked-row");
+        let dep_label = Label::new(Some("⧗"));
+        dep_label.add_css_class("dependency-indicator");
+        hbox.append(&dep_label);
+    }
+
+    // Tracked time against estimate, rolled up from subtasks
+    let tracked_minutes = flat_todo.todo.total_tracked() / 60;
+    let estimate = flat_todo.todo.total_estimate();
+    if tracked_minutes > 0 || estimate.is_some() || flat_todo.todo.is_timing() {
+        let mut time_str = format_minutes(tracked_minutes);
+        if let Some(est) = estimate {
+            time_str = format!("{}/{}", time_str, format_minutes(est as i64));
+        }
+        if flat_todo.todo.is_timing() {
+            time_str = format!("⏱ {}", time_str);
+        }
+        let time_label = Label::new(Some(&time_str));
+        time_label.add_css_class("time-tracked");
+        if flat_todo.todo.is_timing() {
+            time_label.add_css_class("time-running");
+        } else if estimate.is_some_and(|est| tracked_minutes > est as i64) {
+            time_label.add_css_class("time-over");
+        }
+        hbox.append(&time_label);
+    }
+
     // Start date (if enabled)
     if settings.show_start_date {
         let created: DateTime<Utc> = DateTime::from_timestamp(flat_todo.todo.created_at, 0)
@@ -1308,21 +2743,25 @@ fn refresh_list_with_settings(
     }
 
     let todos_ref = todos.borrow();
-    let flat = todos_ref.flatten();
     let settings = display_settings.borrow();
+    let flat = filter_flat(todos_ref.flatten(), &settings);
 
     // In flattened mode, filter out section headers and sort to remove section clustering
-    let display_flat: Vec<FlatTodo> = if settings.flattened {
-        let mut filtered: Vec<FlatTodo> = flat.into_iter().filter(|ft| !ft.todo.is_section).collect();
-        // Sort by: abandoned, completed status, priority, due date, then alphabetically
+    let display_flat: Vec<FlatTodo> = if settings.is_flat() {
+        let mut filtered: Vec<FlatTodo> = flat.into_iter()
+            .filter(|ft| !ft.todo.is_section)
+            .filter(|ft| !settings.next_actions || is_next_action(ft))
+            .collect();
+        // Sort by the tab's sort mode, then status, priority, due date, alphabetically
         filtered.sort_by(|a, b| {
-            // Abandoned tasks last (at the very bottom)
-            if a.todo.abandoned != b.todo.abandoned {
-                return a.todo.abandoned.cmp(&b.todo.abandoned);
+            let mode_cmp = compare_by_sort_mode(settings.sort_mode, &a.todo, &b.todo);
+            if mode_cmp != std::cmp::Ordering::Equal {
+                return mode_cmp;
             }
-            // Completed tasks after incomplete
-            if a.todo.completed != b.todo.completed {
-                return a.todo.completed.cmp(&b.todo.completed);
+            // In progress first, then open, blocked, completed, abandoned at the very bottom
+            let status_cmp = a.todo.status.sort_order().cmp(&b.todo.status.sort_order());
+            if status_cmp != std::cmp::Ordering::Equal {
+                return status_cmp;
             }
             // Priority (higher priority first)
             let priority_order = |p: &Priority| match p {
@@ -1349,40 +2788,415 @@ fn refresh_list_with_settings(
             // Alphabetical
             a.todo.text.to_lowercase().cmp(&b.todo.text.to_lowercase())
         });
-        filtered
+        if settings.hides_closed() {
+            group_closed(filtered, &settings, &todos_ref, Utc::now().timestamp(), true)
+        } else {
+            filtered
+        }
     } else {
-        // In hierarchical mode, move abandoned tasks to the bottom while preserving structure
-        let mut non_abandoned: Vec<FlatTodo> = Vec::new();
-        let mut abandoned: Vec<FlatTodo> = Vec::new();
-        for ft in flat {
-            if ft.todo.abandoned {
-                abandoned.push(ft);
-            } else {
-                non_abandoned.push(ft);
+        // Sort siblings at every level if the tab has a sort mode
+        let flat = if settings.sort_mode == SortMode::Manual {
+            flat
+        } else {
+            sort_flat_tree(flat, settings.sort_mode)
+        };
+        if settings.hides_closed() {
+            // Closed tasks sink into the "Completed (N)" rows instead
+            group_closed(flat, &settings, &todos_ref, Utc::now().timestamp(), false)
+        } else {
+            // In hierarchical mode, move abandoned tasks to the bottom while preserving structure
+            let mut non_abandoned: Vec<FlatTodo> = Vec::new();
+            let mut abandoned: Vec<FlatTodo> = Vec::new();
+            for ft in flat {
+                if ft.todo.is_abandoned() {
+                    abandoned.push(ft);
+                } else {
+                    non_abandoned.push(ft);
+                }
             }
+            non_abandoned.extend(abandoned);
+            non_abandoned
         }
-        non_abandoned.extend(abandoned);
-        non_abandoned
     };
 
+    // In focus mode, dim everything outside the focused task's subtree
+    let focus_path = settings.focused_id.as_ref().and_then(|id| todos_ref.path_of(id));
+
     for flat_todo in &display_flat {
         let row = create_todo_row(flat_todo, &settings);
+        if let Some(ref path) = focus_path {
+            if !flat_todo.path.starts_with(path) {
+                row.add_css_class("focus-dimmed");
+            }
+        }
         list_box.append(&row);
     }
 
     *flat_todos.borrow_mut() = display_flat;
 }
 
-/// Autocomplete command input
-fn autocomplete_command(input: &str) -> Option<String> {
-    let commands = [":e ", ":e calendar", ":e list", ":n ", ":ls", ":sort", ":flatten", ":display_start"];
+/// Apply a tab's text filter, keeping the parents of matching tasks
+fn filter_flat(flat: Vec<FlatTodo>, settings: &DisplaySettings) -> Vec<FlatTodo> {
+    let matches: Option<Vec<Vec<usize>>> = settings.filter.as_ref().map(|filter| {
+        flat.iter()
+            .filter(|ft| ft.todo.text.to_lowercase().contains(filter.as_str()))
+            .map(|ft| ft.path.clone())
+            .collect()
+    });
 
-    // Check for command completion
-    for cmd in &commands {
-        if cmd.starts_with(input) && *cmd != input {
-            return Some(cmd.to_string());
-        }
-    }
+    flat.into_iter()
+        .filter(|ft| match matches {
+            Some(ref matches) => matches.iter().any(|path| path.starts_with(&ft.path)),
+            None => true,
+        })
+        .collect()
+}
+
+/// Ordering of two tasks under a sort mode (Equal for manual)
+fn compare_by_sort_mode(mode: SortMode, a: &Todo, b: &Todo) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match mode {
+        SortMode::Manual => Ordering::Equal,
+        SortMode::Priority => a.priority.sort_order().cmp(&b.priority.sort_order()),
+        SortMode::Due => match (&a.due_date, &b.due_date) {
+            (Some(da), Some(db)) => da.cmp(db),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        SortMode::Alpha => a.text.to_lowercase().cmp(&b.text.to_lowercase()),
+        SortMode::Created => b.created_at.cmp(&a.created_at),
+    }
+}
+
+/// Sort siblings at every level of a flattened tree, keeping each task's
+/// subtree directly below it
+fn sort_flat_tree(flat: Vec<FlatTodo>, mode: SortMode) -> Vec<FlatTodo> {
+    let mut blocks = split_blocks(flat);
+    blocks.sort_by(|a, b| compare_by_sort_mode(mode, &a[0].todo, &b[0].todo));
+
+    let mut result = Vec::new();
+    for mut block in blocks {
+        let children = block.split_off(1);
+        result.append(&mut block);
+        result.extend(sort_flat_tree(children, mode));
+    }
+    result
+}
+
+/// Split a flattened tree into blocks: a task followed by its (deeper) descendants
+fn split_blocks(flat: Vec<FlatTodo>) -> Vec<Vec<FlatTodo>> {
+    let mut blocks: Vec<Vec<FlatTodo>> = Vec::new();
+    for ft in flat {
+        match blocks.last_mut() {
+            Some(block) if ft.depth > block[0].depth => block.push(ft),
+            _ => blocks.push(vec![ft]),
+        }
+    }
+    blocks
+}
+
+/// Move closed tasks that the tab hides into a "Completed (N)" row at the end
+/// of their level. In flat mode there is a single row at the bottom.
+fn group_closed(
+    flat: Vec<FlatTodo>,
+    settings: &DisplaySettings,
+    todos: &TodoList,
+    now: i64,
+    flat_mode: bool,
+) -> Vec<FlatTodo> {
+    let blocks = if flat_mode {
+        flat.into_iter().map(|ft| vec![ft]).collect()
+    } else {
+        split_blocks(flat)
+    };
+    let (hidden, visible): (Vec<Vec<FlatTodo>>, Vec<Vec<FlatTodo>>) =
+        blocks.into_iter().partition(|block| settings.collapses(&block[0].todo, now));
+
+    let mut result = Vec::new();
+    let emit = |result: &mut Vec<FlatTodo>, mut block: Vec<FlatTodo>| {
+        let children = block.split_off(1);
+        result.append(&mut block);
+        result.extend(group_closed(children, settings, todos, now, flat_mode));
+    };
+    for block in visible {
+        emit(&mut result, block);
+    }
+
+    if let Some(first) = hidden.first().map(|block| &block[0]) {
+        // Keyed by the parent so the open state survives refreshes
+        let parent_path = &first.path[..first.path.len().saturating_sub(1)];
+        let parent_id = if flat_mode {
+            String::new()
+        } else {
+            todos.get_at_path(parent_path).map(|t| t.id.clone()).unwrap_or_default()
+        };
+        let group_id = format!("completed:{}", parent_id);
+        let expanded = settings.expanded_groups.contains(&group_id);
+
+        let mut placeholder = Todo::new_section(format!("Completed ({})", hidden.len()));
+        placeholder.id = group_id;
+        result.push(FlatTodo {
+            todo: placeholder,
+            depth: if flat_mode { 0 } else { first.depth },
+            path: Vec::new(),  // Not a real task: run_bound_action keeps task actions off it
+            has_subtasks: true,
+            is_folded: !expanded,
+            hierarchy_path: Vec::new(),
+            is_blocked: false,
+            hidden_count: hidden.len(),
+        });
+        if expanded {
+            for block in hidden {
+                emit(&mut result, block);
+            }
+        }
+    }
+    result
+}
+
+/// Whether a task can be worked on right now: open, not blocked by status or
+/// dependencies, and without open subtasks of its own
+fn is_next_action(flat_todo: &FlatTodo) -> bool {
+    let todo = &flat_todo.todo;
+    !todo.status.is_closed()
+        && todo.status != Status::Blocked
+        && !flat_todo.is_blocked
+        && todo.subtasks.iter().all(|t| t.status.is_closed() || t.is_section)
+}
+
+/// Resolve [after:...] id prefixes, reporting the ones that don't match a
+/// single task
+fn resolve_dependencies(todos: &TodoList, prefixes: &[String], notification_label: &Label) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut errors = Vec::new();
+    for prefix in prefixes {
+        match todos.resolve_id_prefix(prefix) {
+            Ok(id) if !ids.contains(&id) => ids.push(id),
+            Ok(_) => {}
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        show_notification(notification_label, &errors.join("; "), true);
+    }
+    ids
+}
+
+/// First 8 characters of a task id, enough to reference it with [after:...]
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
+
+/// Get the flattened todo for the selected list row
+fn selected_flat_todo(list_box: &ListBox, flat_todos: &Rc<RefCell<Vec<FlatTodo>>>) -> Option<FlatTodo> {
+    let row = list_box.selected_row()?;
+    flat_todos.borrow().get(row.index() as usize).cloned()
+}
+
+/// Select the list row showing the task with the given id
+fn select_row_by_id(list_box: &ListBox, flat_todos: &Rc<RefCell<Vec<FlatTodo>>>, id: &str) {
+    let index = flat_todos.borrow().iter().position(|ft| ft.todo.id == id);
+    if let Some(row) = index.and_then(|i| list_box.row_at_index(i as i32)) {
+        list_box.select_row(Some(&row));
+    }
+}
+
+/// Parse the range for `:report time`: empty for the current week,
+/// `today`, a single date expression, or `FROM..TO`
+fn parse_report_range(range: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    match range {
+        "" | "week" => Some((week_start(today), today)),
+        "month" => Some((today.with_day(1)?, today)),
+        _ => match range.split_once("..") {
+            Some((from, to)) => {
+                let from = try_parse_date(from.trim(), today)?;
+                let to = try_parse_date(to.trim(), today)?;
+                Some((from.min(to), from.max(to)))
+            }
+            None => try_parse_date(range, today).map(|date| (date, date)),
+        },
+    }
+}
+
+/// Load a cluster, first archiving closed tasks per the automatic archive policy.
+/// What was read becomes the base for merging sync conflicts.
+fn load_cluster(name: &str, notification_label: &Label) -> TodoList {
+    let mut list = TodoList::load(name);
+    list.save_merge_base();
+    let cutoff = ArchiveConfig::load().cutoff(Utc::now().timestamp());
+    if let Some(cutoff) = cutoff.filter(|_| !list.is_read_only()) {
+        if let Err(err) = list.archive_done(Some(cutoff)) {
+            show_notification(notification_label, &format!("Could not archive closed tasks: {}", err), true);
+        }
+    }
+    list
+}
+
+/// Load a cluster into a tab and show its list
+fn open_cluster_in_tab(
+    tabs: &Rc<RefCell<Vec<TabContent>>>,
+    page: usize,
+    cluster_name: &str,
+    notebook: &Notebook,
+    notification_label: &Label,
+    breadcrumb_label: &Label,
+) {
+    let (todos, list_box, flat_todos, display_settings) = {
+        let mut tabs_mut = tabs.borrow_mut();
+        let tab = match tabs_mut.get_mut(page) {
+            Some(tab) => tab,
+            None => return,
+        };
+        tab.cluster_name = cluster_name.to_string();
+        tab.content_stack.set_visible_child_name("list");
+        *tab.view_type.borrow_mut() = ViewType::List;
+        (tab.todos.clone(), tab.list_box.clone(), tab.flat_todos.clone(), tab.display_settings.clone())
+    };
+    *todos.borrow_mut() = load_cluster(cluster_name, notification_label);
+    if let Some(err) = todos.borrow().load_error() {
+        show_notification(notification_label, err, true);
+    } else if TodoList::is_archive(cluster_name) {
+        show_notification(notification_label, "Archive is read-only; :restore moves a task back", false);
+    }
+    update_breadcrumb(breadcrumb_label, &todos);
+    if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+        notebook.set_tab_label_text(&page_widget, cluster_name);
+    }
+    refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+}
+
+/// Tracked time per cluster between two dates (inclusive)
+fn time_report(from: NaiveDate, to: NaiveDate) -> String {
+    let day_start = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(Local).earliest())
+            .map_or(0, |dt| dt.timestamp())
+    };
+    let (start, end) = (day_start(from), day_start(to + Duration::days(1)));
+
+    // Only read: clusters that can't be read are listed, not set aside
+    let mut unreadable = Vec::new();
+    let mut tracked = |name: &str| match with_storage(|storage| storage.load(name)) {
+        Ok(todos) => todos.unwrap_or_default().iter().map(|t| t.tracked_between(start, end)).sum(),
+        Err(_) => {
+            unreadable.push(name.to_string());
+            0
+        }
+    };
+    let mut total = 0;
+    let mut parts = Vec::new();
+    for cluster in TodoList::list_clusters() {
+        // Archived tasks still count towards the cluster's time
+        let archived = tracked(&format!("{}{}", cluster, ARCHIVE_SUFFIX));
+        let minutes = (tracked(&cluster) + archived) / 60;
+        if minutes > 0 {
+            parts.push(format!("{} {}", cluster, format_minutes(minutes)));
+            total += minutes;
+        }
+    }
+
+    let range = if from == to {
+        from.format("%b %d").to_string()
+    } else {
+        format!("{} – {}", from.format("%b %d"), to.format("%b %d"))
+    };
+    let mut report = if parts.is_empty() {
+        format!("{}: no time tracked", range)
+    } else {
+        format!("{}: {} (total {})", range, parts.join(", "), format_minutes(total))
+    };
+    if !unreadable.is_empty() {
+        report.push_str(&format!("; can't read {}", unreadable.join(", ")));
+    }
+    report
+}
+
+/// Show the path to the hoisted task in the header, or hide it
+fn update_breadcrumb(breadcrumb_label: &Label, todos: &Rc<RefCell<TodoList>>) {
+    let todos_ref = todos.borrow();
+    let crumbs = todos_ref.hoist_breadcrumb();
+    if crumbs.is_empty() {
+        breadcrumb_label.set_visible(false);
+    } else {
+        breadcrumb_label.set_text(&format!("{} › {}", todos_ref.cluster_name(), crumbs.join(" › ")));
+        breadcrumb_label.set_visible(true);
+    }
+}
+
+/// Whether the tab's cluster can't be changed, telling the user so
+fn refuse_read_only(todos: &Rc<RefCell<TodoList>>, notification_label: &Label) -> bool {
+    if !todos.borrow().is_read_only() {
+        return false;
+    }
+    let message = if TodoList::is_archive(todos.borrow().cluster_name()) {
+        "Archive is read-only"
+    } else {
+        "Cluster is read-only"
+    };
+    show_notification(notification_label, message, true);
+    true
+}
+
+/// Run git on a worker thread, so that the window doesn't freeze, and pass
+/// the result to `done` back on the main loop. Git locks the repository, so
+/// nothing is run while `busy`.
+fn run_git<T: Send + 'static>(
+    repo: &Repo,
+    busy: &Rc<Cell<bool>>,
+    work: impl FnOnce(&Repo) -> T + Send + 'static,
+    done: impl FnOnce(T) + 'static,
+) {
+    if busy.replace(true) {
+        return;
+    }
+    let repo = repo.clone();
+    let handle = gio::spawn_blocking(move || work(&repo));
+    let busy = busy.clone();
+    gtk4::glib::spawn_future_local(async move {
+        let result = handle.await;
+        busy.set(false);
+        if let Ok(result) = result {
+            done(result);
+        }
+    });
+}
+
+/// Show a message in the notification bar
+fn show_notification(notification_label: &Label, text: &str, is_error: bool) {
+    notification_label.set_text(text);
+    if is_error {
+        notification_label.add_css_class("notification-error");
+    } else {
+        notification_label.remove_css_class("notification-error");
+    }
+    notification_label.set_visible(true);
+    let notification_label = notification_label.clone();
+    let timeout = std::time::Duration::from_millis(Settings::current().notification_ms as u64);
+    gtk4::glib::timeout_add_local(timeout, move || {
+        notification_label.set_visible(false);
+        gtk4::glib::ControlFlow::Break
+    });
+}
+
+/// Autocomplete command input
+fn autocomplete_command(input: &str) -> Option<String> {
+    let commands = [
+        ":e ", ":e calendar", ":e week", ":e board", ":e board priority", ":e list", ":n ", ":ls",
+        ":sort", ":flatten", ":display_start", ":next", ":id", ":dep", ":undep",
+        ":filter ", ":sortby ", ":show done", ":show abandoned", ":hide done", ":hide abandoned", ":autohide ",
+        ":timer", ":focus", ":focus stop", ":report time", ":mksession ", ":source ",
+        ":archive-done", ":restore", ":trash", ":resolve", ":log", ":revert ", ":sync",
+        ":migrate json", ":migrate sqlite", ":encrypt", ":decrypt",
+        ":set ", ":reload", ":checkbindings",
+    ];
+
+    // Check for command completion
+    for cmd in &commands {
+        if cmd.starts_with(input) && *cmd != input {
+            return Some(cmd.to_string());
+        }
+    }
 
     // Check for cluster name completion after :e or :n
     if let Some(partial) = input.strip_prefix(":e ") {
@@ -1426,6 +3240,8 @@ fn create_inline_entry_row(depth: usize, placeholder: &str) -> ListBoxRow {
     entry.set_placeholder_text(Some(placeholder));
     entry.add_css_class("inline-entry");
     entry.set_hexpand(true);
+    // Connected ahead of the handler adding the task, which clears the text
+    entry.connect_activate(|e| record_history(HistoryKind::Task, &e.text()));
     hbox.append(&entry);
 
     row.set_child(Some(&hbox));
@@ -1441,10 +3257,14 @@ fn get_entry_from_row(row: &ListBoxRow) -> Option<Entry> {
     child?.downcast::<Entry>().ok()
 }
 
+/// Number of tasks shown in a calendar day cell before "+N more"
+const CALENDAR_VISIBLE_TASKS: usize = 3;
+
 /// Create and populate the calendar view for a tab
 fn create_calendar_view(
     scrolled_calendar: &ScrolledWindow,
     calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
 ) {
     let today = Local::now().date_naive();
     let year = today.year();
@@ -1483,11 +3303,14 @@ fn create_calendar_view(
     // Day names header
     let day_names_box = GtkBox::new(Orientation::Horizontal, 0);
     day_names_box.set_homogeneous(true);
-    for day_name in &["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"] {
-        let label = Label::new(Some(day_name));
-        label.add_css_class("calendar-day-header");
-        day_names_box.append(&label);
-    }
+    let day_names: Vec<Label> = (0..7)
+        .map(|_| {
+            let label = Label::new(None);
+            label.add_css_class("calendar-day-header");
+            day_names_box.append(&label);
+            label
+        })
+        .collect();
     main_box.append(&day_names_box);
 
     // Calendar grid
@@ -1506,30 +3329,35 @@ fn create_calendar_view(
         year,
         month,
         selected_day,
+        selected_task: 0,
         grid,
-        day_frames: HashMap::new(),
         month_label,
+        day_names,
+        day_tasks: HashMap::new(),
     };
     *calendar_state.borrow_mut() = Some(state);
 
     // Connect button click handlers
     let calendar_state_left = calendar_state.clone();
+    let todos_left = todos.clone();
     left_btn.connect_clicked(move |_| {
-        change_calendar_month(&calendar_state_left, -1);
+        change_calendar_month(&calendar_state_left, &todos_left, -1);
     });
 
     let calendar_state_right = calendar_state.clone();
+    let todos_right = todos.clone();
     right_btn.connect_clicked(move |_| {
-        change_calendar_month(&calendar_state_right, 1);
+        change_calendar_month(&calendar_state_right, &todos_right, 1);
     });
 
-    // Populate the calendar with tasks from all clusters
-    refresh_calendar_view(calendar_state);
+    // Populate the calendar with tasks from the tab's cluster
+    refresh_calendar_view(calendar_state, todos);
 }
 
-/// Refresh the calendar view with tasks from ALL clusters
+/// Refresh the calendar view with tasks from the tab's cluster
 fn refresh_calendar_view(
     calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
 ) {
     let mut state_ref = calendar_state.borrow_mut();
     let state = match state_ref.as_mut() {
@@ -1559,24 +3387,27 @@ fn refresh_calendar_view(
     for c in children {
         state.grid.remove(&c);
     }
-    state.day_frames.clear();
 
     // Get first day of month and number of days
     let first_day = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
     let days_in_month = days_in_month(year, month);
-    let first_weekday = first_day.weekday().num_days_from_sunday();
+    let week_start = Settings::current().week_start.weekday();
+    let first_weekday = first_day.weekday().days_since(week_start);
+    let mut weekday = week_start;
+    for label in &state.day_names {
+        label.set_text(&weekday.to_string());
+        weekday = weekday.succ();
+    }
 
-    // Load tasks from main cluster only
     let today = Local::now().date_naive();
-    let todo_list = TodoList::load("main");
-    let flat_todos = todo_list.flatten();
+    let flat_todos = todos.borrow().flatten();
 
     // Group tasks by day, skipping completed tasks from previous days
     let mut tasks_by_day: HashMap<u32, Vec<FlatTodo>> = HashMap::new();
     for flat_todo in flat_todos {
         let date = flat_todo.todo.due_date.unwrap_or(today);
         // Skip completed tasks from previous days
-        if flat_todo.todo.completed && date < today {
+        if flat_todo.todo.is_done() && date < today {
             continue;
         }
         if date.year() == year && date.month() == month {
@@ -1584,6 +3415,13 @@ fn refresh_calendar_view(
         }
     }
 
+    // Keep the task selection within the selected day
+    let selected_count = tasks_by_day.get(&selected_day).map_or(0, |t| t.len());
+    if state.selected_task >= selected_count {
+        state.selected_task = selected_count.saturating_sub(1);
+    }
+    let selected_task = state.selected_task;
+
     // Create day cells
     for day in 1..=days_in_month {
         let col = ((first_weekday + day - 1) % 7) as i32;
@@ -1616,12 +3454,17 @@ fn refresh_calendar_view(
 
         day_box.append(&day_label);
 
-        // Add tasks for this day
+        // Add tasks for this day, scrolled so the selected task stays visible
         if let Some(day_tasks) = tasks_by_day.get(&day) {
-            for (i, flat_todo) in day_tasks.iter().enumerate() {
-                if i >= 3 {
+            let first = if day == selected_day {
+                selected_task.saturating_sub(CALENDAR_VISIBLE_TASKS - 1)
+            } else {
+                0
+            };
+            for (i, flat_todo) in day_tasks.iter().enumerate().skip(first) {
+                if i >= first + CALENDAR_VISIBLE_TASKS {
                     // Show "+N more" if too many tasks
-                    let more_label = Label::new(Some(&format!("+{} more", day_tasks.len() - 3)));
+                    let more_label = Label::new(Some(&format!("+{} more", day_tasks.len() - CALENDAR_VISIBLE_TASKS)));
                     more_label.add_css_class("calendar-task-more");
                     more_label.set_halign(gtk4::Align::Start);
                     day_box.append(&more_label);
@@ -1630,7 +3473,7 @@ fn refresh_calendar_view(
                 let task_label = Label::new(Some(&truncate_text(&flat_todo.todo.text, 15)));
                 task_label.set_halign(gtk4::Align::Start);
                 task_label.add_css_class("calendar-task");
-                if flat_todo.todo.completed {
+                if flat_todo.todo.is_done() {
                     task_label.add_css_class("calendar-task-completed");
                 }
                 // Priority coloring
@@ -1640,49 +3483,111 @@ fn refresh_calendar_view(
                     Priority::Medium => task_label.add_css_class("calendar-task-medium"),
                     _ => {}
                 }
+                if day == selected_day && i == selected_task {
+                    task_label.add_css_class("calendar-task-selected");
+                }
                 day_box.append(&task_label);
             }
         }
 
         frame.set_child(Some(&day_box));
         state.grid.attach(&frame, col, row, 1, 1);
-        state.day_frames.insert(day, frame);
     }
+
+    state.day_tasks = tasks_by_day;
 }
 
-/// Update calendar selection highlighting
-fn update_calendar_selection(calendar_state: &Rc<RefCell<Option<CalendarState>>>, new_day: u32) {
-    let mut state_ref = calendar_state.borrow_mut();
-    if let Some(state) = state_ref.as_mut() {
-        // Remove selection from old day
-        if let Some(old_frame) = state.day_frames.get(&state.selected_day) {
-            old_frame.remove_css_class("calendar-selected");
+/// Navigate calendar selection
+fn navigate_calendar(
+    calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta_days: i32,
+    delta_weeks: i32,
+) {
+    {
+        let mut state_ref = calendar_state.borrow_mut();
+        let state = match state_ref.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        let days_in_month = days_in_month(state.year, state.month);
+        let new_day = state.selected_day as i32 + delta_days + (delta_weeks * 7);
+        if new_day < 1 || new_day > days_in_month as i32 {
+            return;
         }
-        // Add selection to new day
-        if let Some(new_frame) = state.day_frames.get(&new_day) {
-            new_frame.add_css_class("calendar-selected");
-            state.selected_day = new_day;
+        state.selected_day = new_day as u32;
+        state.selected_task = 0;
+    }
+    refresh_calendar_view(calendar_state, todos);
+}
+
+/// Move the task selection within the selected calendar day
+fn select_calendar_task(
+    calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta: i32,
+) {
+    {
+        let mut state_ref = calendar_state.borrow_mut();
+        let state = match state_ref.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        let count = state.day_tasks.get(&state.selected_day).map_or(0, |t| t.len()) as i32;
+        let new_index = state.selected_task as i32 + delta;
+        if new_index < 0 || new_index >= count {
+            return;
         }
+        state.selected_task = new_index as usize;
     }
+    refresh_calendar_view(calendar_state, todos);
 }
 
-/// Navigate calendar selection
-fn navigate_calendar(calendar_state: &Rc<RefCell<Option<CalendarState>>>, delta_days: i32, delta_weeks: i32) {
+/// Get the path and id of the task selected in the calendar
+fn get_selected_calendar_task(calendar_state: &Rc<RefCell<Option<CalendarState>>>) -> Option<(Vec<usize>, String)> {
     let state_ref = calendar_state.borrow();
-    if let Some(state) = state_ref.as_ref() {
-        let days_in_month = days_in_month(state.year, state.month);
-        let current = state.selected_day as i32;
-        let new_day = current + delta_days + (delta_weeks * 7);
+    let state = state_ref.as_ref()?;
+    let flat_todo = state.day_tasks.get(&state.selected_day)?.get(state.selected_task)?;
+    Some((flat_todo.path.clone(), flat_todo.todo.id.clone()))
+}
 
-        if new_day >= 1 && new_day <= days_in_month as i32 {
-            drop(state_ref);
-            update_calendar_selection(calendar_state, new_day as u32);
+/// Select a task by id on the given date, switching months if needed
+fn select_calendar_task_by_id(
+    calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    date: NaiveDate,
+    task_id: &str,
+) {
+    {
+        let mut state_ref = calendar_state.borrow_mut();
+        if let Some(state) = state_ref.as_mut() {
+            state.year = date.year();
+            state.month = date.month();
+            state.selected_day = date.day();
         }
     }
+    refresh_calendar_view(calendar_state, todos);
+
+    let index = calendar_state.borrow().as_ref().and_then(|state| {
+        state
+            .day_tasks
+            .get(&state.selected_day)
+            .and_then(|tasks| tasks.iter().position(|ft| ft.todo.id == task_id))
+    });
+    if let Some(index) = index {
+        if let Some(state) = calendar_state.borrow_mut().as_mut() {
+            state.selected_task = index;
+        }
+        refresh_calendar_view(calendar_state, todos);
+    }
 }
 
 /// Change calendar month (delta: -1 for previous, +1 for next)
-fn change_calendar_month(calendar_state: &Rc<RefCell<Option<CalendarState>>>, delta: i32) {
+fn change_calendar_month(
+    calendar_state: &Rc<RefCell<Option<CalendarState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta: i32,
+) {
     {
         let mut state_ref = calendar_state.borrow_mut();
         if let Some(state) = state_ref.as_mut() {
@@ -1699,6 +3604,7 @@ fn change_calendar_month(calendar_state: &Rc<RefCell<Option<CalendarState>>>, de
 
             state.year = new_year;
             state.month = new_month as u32;
+            state.selected_task = 0;
             // Keep selected day, but clamp to valid range for new month
             let max_day = days_in_month(new_year, new_month as u32);
             if state.selected_day > max_day {
@@ -1707,7 +3613,7 @@ fn change_calendar_month(calendar_state: &Rc<RefCell<Option<CalendarState>>>, de
         }
     }
     // Refresh the calendar with new month
-    refresh_calendar_view(calendar_state);
+    refresh_calendar_view(calendar_state, todos);
 }
 
 /// Get number of days in a month
@@ -1742,3 +3648,499 @@ fn get_selected_calendar_date(calendar_state: &Rc<RefCell<Option<CalendarState>>
         NaiveDate::from_ymd_opt(state.year, state.month, state.selected_day)
     })
 }
+
+/// Switch a tab to the calendar view, creating it on first use
+fn show_calendar_view(tab: &TabContent, notebook: &Notebook, page: usize) {
+    *tab.view_type.borrow_mut() = ViewType::Calendar;
+
+    // Create calendar if not exists
+    if tab.calendar_state.borrow().is_none() {
+        create_calendar_view(&tab.scrolled_calendar, &tab.calendar_state, &tab.todos);
+    } else {
+        refresh_calendar_view(&tab.calendar_state, &tab.todos);
+    }
+
+    tab.content_stack.set_visible_child_name("calendar");
+    if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+        let label = if tab.cluster_name.is_empty() {
+            "[calendar]".to_string()
+        } else {
+            format!("{} [cal]", tab.cluster_name)
+        };
+        notebook.set_tab_label_text(&page_widget, &label);
+    }
+}
+
+/// Switch a tab to the week view, creating it on first use
+fn show_week_view(tab: &TabContent, notebook: &Notebook, page: usize) {
+    *tab.view_type.borrow_mut() = ViewType::Week;
+
+    if tab.week_state.borrow().is_none() {
+        create_week_view(&tab.scrolled_week, &tab.week_state, &tab.todos);
+    } else {
+        refresh_week_view(&tab.week_state, &tab.todos);
+    }
+
+    tab.content_stack.set_visible_child_name("week");
+    if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+        let label = if tab.cluster_name.is_empty() {
+            "[week]".to_string()
+        } else {
+            format!("{} [week]", tab.cluster_name)
+        };
+        notebook.set_tab_label_text(&page_widget, &label);
+    }
+}
+
+/// Switch a tab to the board view, creating it on first use
+fn show_board_view(tab: &TabContent, notebook: &Notebook, page: usize, grouping: BoardGrouping) {
+    *tab.view_type.borrow_mut() = ViewType::Board;
+
+    if tab.board_state.borrow().is_none() {
+        create_board_view(&tab.scrolled_board, &tab.board_state, &tab.todos, grouping);
+    } else {
+        set_board_grouping(&tab.board_state, &tab.todos, grouping);
+    }
+
+    tab.content_stack.set_visible_child_name("board");
+    if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+        let label = if tab.cluster_name.is_empty() {
+            "[board]".to_string()
+        } else {
+            format!("{} [board]", tab.cluster_name)
+        };
+        notebook.set_tab_label_text(&page_widget, &label);
+    }
+}
+
+/// Switch a tab to the trash view, creating it on first use
+fn show_trash_view(tab: &TabContent, notebook: &Notebook, page: usize) {
+    *tab.view_type.borrow_mut() = ViewType::Trash;
+
+    if tab.trash_state.borrow().is_none() {
+        create_trash_view(&tab.scrolled_trash, &tab.trash_state);
+    } else {
+        refresh_trash_view(&tab.trash_state);
+    }
+
+    tab.content_stack.set_visible_child_name("trash");
+    if let Some(page_widget) = notebook.nth_page(Some(page as u32)) {
+        notebook.set_tab_label_text(&page_widget, "[trash]");
+    }
+}
+
+/// Put the selected trash item back into its cluster. Returns a message to show.
+fn restore_from_trash(tabs: &Rc<RefCell<Vec<TabContent>>>, trash_state: &Rc<RefCell<Option<TrashState>>>) -> Result<String, String> {
+    let item = selected_trash_item(trash_state).ok_or("No task selected")?;
+    let mut trash = Trash::load();
+    let cluster = trash.items.get(item).map(|trashed| trashed.cluster.clone()).ok_or("No task selected")?;
+    let restored = with_cluster(tabs, &cluster, |list| trash.restore(item, list));
+    refresh_trash_view(trash_state);
+    match restored {
+        Ok(()) => Ok(format!("Restored to '{}'", cluster)),
+        Err(err) => Err(format!("Could not restore task: {}", err)),
+    }
+}
+
+/// Bring the tabs showing a cluster up to date after its file changed on disk,
+/// keeping the selected task. Edits made since the change, which weren't
+/// saved over it, are merged in.
+fn reload_changed_cluster(tabs: &Rc<RefCell<Vec<TabContent>>>, cluster: &str, notification_label: &Label) {
+    let tabs_ref = tabs.borrow();
+    for tab in tabs_ref.iter().filter(|tab| tab.cluster_name == cluster) {
+        // Our own writes leave the file matching the tab
+        if !tab.todos.borrow().changed_on_disk() {
+            continue;
+        }
+
+        let selected_id = selected_flat_todo(&tab.list_box, &tab.flat_todos).map(|ft| ft.todo.id);
+        let unsaved = tab.todos.borrow().has_unsaved_changes();
+        let (message, is_error) = if unsaved {
+            let conflicts = match tab.todos.borrow_mut().merge_disk_changes() {
+                Some(conflicts) => conflicts,
+                None => continue,
+            };
+            let mut message = format!("Merged changes to '{}' from disk", cluster);
+            if !conflicts.is_empty() {
+                let details: Vec<String> = conflicts.iter().map(|c| format!("'{}' ({})", c.text, c.field)).collect();
+                message.push_str(&format!("; kept local version of {}", details.join(", ")));
+            }
+            (message, !conflicts.is_empty())
+        } else {
+            if !tab.todos.borrow_mut().reload() {
+                continue;
+            }
+            (format!("Reloaded '{}' from disk", cluster), false)
+        };
+        tab.todos.borrow().save_merge_base();
+        refresh_list_with_settings(&tab.todos, &tab.list_box, &tab.flat_todos, &tab.display_settings);
+        if let Some(id) = selected_id {
+            select_row_by_id(&tab.list_box, &tab.flat_todos, &id);
+        }
+        match *tab.view_type.borrow() {
+            ViewType::Calendar => refresh_calendar_view(&tab.calendar_state, &tab.todos),
+            ViewType::Week => refresh_week_view(&tab.week_state, &tab.todos),
+            ViewType::Board => refresh_board_view(&tab.board_state, &tab.todos),
+            ViewType::List | ViewType::Trash => {}
+        }
+        show_notification(notification_label, &message, is_error);
+    }
+}
+
+/// Change a cluster through the tab that has it open, refreshing that tab's
+/// list, so the tab does not later overwrite the change; otherwise load it
+fn with_cluster<R>(tabs: &Rc<RefCell<Vec<TabContent>>>, cluster: &str, f: impl FnOnce(&mut TodoList) -> R) -> R {
+    let open = tabs.borrow().iter()
+        .find(|tab| tab.cluster_name == cluster)
+        .map(|tab| (tab.todos.clone(), tab.list_box.clone(), tab.flat_todos.clone(), tab.display_settings.clone()));
+    match open {
+        Some((todos, list_box, flat_todos, display_settings)) => {
+            let result = f(&mut todos.borrow_mut());
+            refresh_list_with_settings(&todos, &list_box, &flat_todos, &display_settings);
+            result
+        }
+        None => f(&mut TodoList::load(cluster)),
+    }
+}
+
+/// Re-read a config file and apply it, returning its problems, or None if
+/// `name` isn't one that can be reloaded. A file that doesn't parse leaves
+/// what's in effect as it is.
+fn reload_config_file(
+    name: &str,
+    keybindings: &RefCell<Keybindings>,
+    css_provider: &gtk4::CssProvider,
+    tabs: &Rc<RefCell<Vec<TabContent>>>,
+) -> Option<Vec<String>> {
+    let result = match name {
+        Keybindings::FILE => Keybindings::try_load().map(|loaded| {
+            let problems = loaded.problems().to_vec();
+            *keybindings.borrow_mut() = loaded;
+            problems
+        }),
+        ColorConfig::FILE => ColorConfig::try_load().map(|colors| {
+            css_provider.load_from_data(&colors.generate_css());
+            Vec::new()
+        }),
+        Settings::FILE => Settings::reload().map(|()| {
+            refresh_date_views(tabs);
+            Vec::new()
+        }),
+        // Read again where they're used
+        ArchiveConfig::FILE => ArchiveConfig::try_load().map(|_| Vec::new()),
+        TrashConfig::FILE => TrashConfig::try_load().map(|_| Vec::new()),
+        FocusConfig::FILE => FocusConfig::try_load().map(|_| Vec::new()),
+        _ => return None,
+    };
+    Some(result.unwrap_or_else(|err| vec![err]))
+}
+
+/// Redraw calendar and week views, e.g. after the week start changed
+fn refresh_date_views(tabs: &Rc<RefCell<Vec<TabContent>>>) {
+    for tab in tabs.borrow().iter() {
+        refresh_calendar_view(&tab.calendar_state, &tab.todos);
+        if let Some(state) = tab.week_state.borrow_mut().as_mut() {
+            state.start = week_start(state.selected);
+        }
+        refresh_week_view(&tab.week_state, &tab.todos);
+    }
+}
+
+/// First day of the week containing the given date, per the week_start setting
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().days_since(Settings::current().week_start.weekday()) as i64)
+}
+
+/// Create and populate the week view for a tab
+fn create_week_view(
+    scrolled_week: &ScrolledWindow,
+    week_state: &Rc<RefCell<Option<WeekState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+) {
+    let today = Local::now().date_naive();
+
+    // Main container
+    let main_box = GtkBox::new(Orientation::Vertical, 8);
+    main_box.set_margin_start(8);
+    main_box.set_margin_end(8);
+    main_box.set_margin_top(8);
+    main_box.set_margin_bottom(8);
+
+    // Week range header with navigation
+    let header_box = GtkBox::new(Orientation::Horizontal, 8);
+    header_box.set_halign(gtk4::Align::Center);
+
+    let left_btn = Button::with_label("◀");
+    left_btn.add_css_class("calendar-nav-btn");
+    header_box.append(&left_btn);
+
+    let range_label = Label::new(None);
+    range_label.add_css_class("calendar-header");
+    range_label.set_width_chars(25);
+    header_box.append(&range_label);
+
+    let right_btn = Button::with_label("▶");
+    right_btn.add_css_class("calendar-nav-btn");
+    header_box.append(&right_btn);
+
+    main_box.append(&header_box);
+
+    // One column per day
+    let columns = GtkBox::new(Orientation::Horizontal, 4);
+    columns.set_homogeneous(true);
+    columns.set_vexpand(true);
+    main_box.append(&columns);
+
+    scrolled_week.set_child(Some(&main_box));
+
+    let state = WeekState {
+        start: week_start(today),
+        selected: today,
+        selected_task: 0,
+        columns,
+        range_label,
+        day_tasks: HashMap::new(),
+    };
+    *week_state.borrow_mut() = Some(state);
+
+    let week_state_left = week_state.clone();
+    let todos_left = todos.clone();
+    left_btn.connect_clicked(move |_| {
+        navigate_week(&week_state_left, &todos_left, -7);
+    });
+
+    let week_state_right = week_state.clone();
+    let todos_right = todos.clone();
+    right_btn.connect_clicked(move |_| {
+        navigate_week(&week_state_right, &todos_right, 7);
+    });
+
+    refresh_week_view(week_state, todos);
+}
+
+/// Refresh the week view with tasks from the tab's cluster
+fn refresh_week_view(
+    week_state: &Rc<RefCell<Option<WeekState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+) {
+    let mut state_ref = week_state.borrow_mut();
+    let state = match state_ref.as_mut() {
+        Some(s) => s,
+        None => return,
+    };
+
+    let start = state.start;
+    let end = start + Duration::days(6);
+    let range = if start.year() != end.year() {
+        format!("{} – {}", start.format("%b %d, %Y"), end.format("%b %d, %Y"))
+    } else {
+        format!("{} – {}", start.format("%b %d"), end.format("%b %d, %Y"))
+    };
+    state.range_label.set_text(&range);
+
+    while let Some(child) = state.columns.first_child() {
+        state.columns.remove(&child);
+    }
+
+    // Group tasks by day, skipping completed tasks from previous days
+    let today = Local::now().date_naive();
+    let mut day_tasks: HashMap<NaiveDate, Vec<FlatTodo>> = HashMap::new();
+    for flat_todo in todos.borrow().flatten() {
+        if flat_todo.todo.is_section {
+            continue;
+        }
+        let date = flat_todo.todo.due_date.unwrap_or(today);
+        if flat_todo.todo.is_done() && date < today {
+            continue;
+        }
+        if date >= start && date <= end {
+            day_tasks.entry(date).or_default().push(flat_todo);
+        }
+    }
+
+    // Keep the task selection within the selected day
+    let selected_count = day_tasks.get(&state.selected).map_or(0, |t| t.len());
+    if state.selected_task >= selected_count {
+        state.selected_task = selected_count.saturating_sub(1);
+    }
+
+    for offset in 0..7 {
+        let date = start + Duration::days(offset);
+
+        let frame = Frame::new(None);
+        frame.add_css_class("calendar-day");
+        frame.add_css_class("week-day");
+        if date == today {
+            frame.add_css_class("calendar-today");
+        }
+        if date == state.selected {
+            frame.add_css_class("calendar-selected");
+        }
+
+        let day_box = GtkBox::new(Orientation::Vertical, 4);
+        day_box.set_margin_start(4);
+        day_box.set_margin_end(4);
+        day_box.set_margin_top(4);
+        day_box.set_margin_bottom(4);
+
+        let day_label = Label::new(Some(&date.format("%a %d").to_string()));
+        day_label.set_halign(gtk4::Align::Start);
+        day_label.add_css_class("calendar-day-number");
+        day_box.append(&day_label);
+
+        if let Some(tasks) = day_tasks.get(&date) {
+            for (i, flat_todo) in tasks.iter().enumerate() {
+                let task_label = Label::new(Some(&flat_todo.todo.text));
+                task_label.set_halign(gtk4::Align::Fill);
+                task_label.set_xalign(0.0);
+                task_label.set_wrap(true);
+                task_label.add_css_class("week-task");
+                if flat_todo.todo.is_done() {
+                    task_label.add_css_class("calendar-task-completed");
+                }
+                match flat_todo.todo.priority {
+                    Priority::Max => task_label.add_css_class("calendar-task-max"),
+                    Priority::High => task_label.add_css_class("calendar-task-high"),
+                    Priority::Medium => task_label.add_css_class("calendar-task-medium"),
+                    _ => {}
+                }
+                if date == state.selected && i == state.selected_task {
+                    task_label.add_css_class("week-task-selected");
+                }
+                day_box.append(&task_label);
+            }
+        }
+
+        frame.set_child(Some(&day_box));
+        state.columns.append(&frame);
+    }
+
+    state.day_tasks = day_tasks;
+}
+
+/// Move the selected day, switching weeks when the selection leaves the current one
+fn navigate_week(
+    week_state: &Rc<RefCell<Option<WeekState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta_days: i64,
+) {
+    {
+        let mut state_ref = week_state.borrow_mut();
+        if let Some(state) = state_ref.as_mut() {
+            state.selected += Duration::days(delta_days);
+            state.start = week_start(state.selected);
+            state.selected_task = 0;
+        }
+    }
+    refresh_week_view(week_state, todos);
+}
+
+/// Move the task selection within the selected day
+fn select_week_task(
+    week_state: &Rc<RefCell<Option<WeekState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    delta: i32,
+) {
+    {
+        let mut state_ref = week_state.borrow_mut();
+        let state = match state_ref.as_mut() {
+            Some(s) => s,
+            None => return,
+        };
+        let count = state.day_tasks.get(&state.selected).map_or(0, |t| t.len()) as i32;
+        let new_index = state.selected_task as i32 + delta;
+        if new_index < 0 || new_index >= count {
+            return;
+        }
+        state.selected_task = new_index as usize;
+    }
+    refresh_week_view(week_state, todos);
+}
+
+/// Get the path and id of the task selected in the week view
+fn get_selected_week_task(week_state: &Rc<RefCell<Option<WeekState>>>) -> Option<(Vec<usize>, String)> {
+    let state_ref = week_state.borrow();
+    let state = state_ref.as_ref()?;
+    let flat_todo = state.day_tasks.get(&state.selected)?.get(state.selected_task)?;
+    Some((flat_todo.path.clone(), flat_todo.todo.id.clone()))
+}
+
+/// Select a task by id on the given date, switching weeks if needed
+fn select_week_task_by_id(
+    week_state: &Rc<RefCell<Option<WeekState>>>,
+    todos: &Rc<RefCell<TodoList>>,
+    date: NaiveDate,
+    task_id: &str,
+) {
+    {
+        let mut state_ref = week_state.borrow_mut();
+        if let Some(state) = state_ref.as_mut() {
+            state.selected = date;
+            state.start = week_start(date);
+        }
+    }
+    refresh_week_view(week_state, todos);
+
+    let index = week_state.borrow().as_ref().and_then(|state| {
+        state
+            .day_tasks
+            .get(&date)
+            .and_then(|tasks| tasks.iter().position(|ft| ft.todo.id == task_id))
+    });
+    if let Some(index) = index {
+        if let Some(state) = week_state.borrow_mut().as_mut() {
+            state.selected_task = index;
+        }
+        refresh_week_view(week_state, todos);
+    }
+}
+
+/// Apply a DateForward/DateBack/ClearDate action to the task at path.
+/// Returns the date the task now shows up on (today for undated tasks).
+fn reschedule_task(action: Action, todos: &Rc<RefCell<TodoList>>, path: &[usize]) -> NaiveDate {
+    let today = Local::now().date_naive();
+    match action {
+        Action::DateForward => todos.borrow_mut().shift_due_date_at_path(path, 1).unwrap_or(today),
+        Action::DateBack => todos.borrow_mut().shift_due_date_at_path(path, -1).unwrap_or(today),
+        _ => {
+            todos.borrow_mut().set_due_date_at_path(path, None);
+            today
+        }
+    }
+}
+
+/// Open the command bar to enter a date expression for the task at path
+fn start_date_prompt(
+    path: Vec<usize>,
+    input_mode: &Rc<RefCell<InputMode>>,
+    mode_label: &Label,
+    command_entry: &Entry,
+) {
+    *input_mode.borrow_mut() = InputMode::SetDate(path);
+    mode_label.set_text("DATE");
+    command_entry.set_placeholder_text(Some("Due date (today, fri, next mon, jan 15, +3, 3/15)..."));
+    command_entry.set_text("");
+    command_entry.set_sensitive(true);
+    command_entry.grab_focus();
+}
+
+/// Open the command bar to enter a passphrase, hiding what is typed
+fn start_passphrase_prompt(
+    prompt: PassphrasePrompt,
+    input_mode: &Rc<RefCell<InputMode>>,
+    mode_label: &Label,
+    command_entry: &Entry,
+) {
+    let placeholder = match &prompt {
+        PassphrasePrompt::Unlock(cluster) => format!("Passphrase for '{}'...", cluster),
+        PassphrasePrompt::Encrypt => "New passphrase...".to_string(),
+        PassphrasePrompt::ConfirmEncrypt(_) => "Repeat the passphrase...".to_string(),
+    };
+    *input_mode.borrow_mut() = InputMode::Passphrase(prompt);
+    mode_label.set_text("PASSPHRASE");
+    command_entry.set_placeholder_text(Some(&placeholder));
+    command_entry.set_text("");
+    command_entry.set_visibility(false);
+    command_entry.set_sensitive(true);
+    command_entry.grab_focus();
+}